@@ -0,0 +1,193 @@
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::api::auth::{enforce_password_policy, verify_second_factor};
+use crate::api::connections::user_upload_dir;
+use crate::api::tokens::to_response;
+use crate::api::AppState;
+use crate::db::repository::{
+    AccessTokenRepository, ConnectionRepository, QueryExecutionRepository, ScriptRepository,
+    SessionRepository, UserRepository, WorkspaceRepository,
+};
+use crate::models::{AccountExport, ChangePasswordRequest, DeleteAccountRequest, User};
+use crate::security::auth::{hash_password, verify_password, AuthUser};
+use crate::security::brute_force::BruteForceProtection;
+use crate::security::repository::{SecurityRepository, ENTITY_USER};
+
+async fn load_user(state: &AppState, auth_user: &AuthUser) -> Result<User, (StatusCode, String)> {
+    UserRepository::find_by_id(&state.db, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))
+}
+
+/// Wrong passwords count as failed logins, so a stolen session can't be used
+/// to guess the password, and a locked account can't be checked at all
+pub(crate) async fn check_password(
+    state: &AppState,
+    user: &User,
+    password: &str,
+    ip: &str,
+) -> Result<(), (StatusCode, String)> {
+    let locked = SecurityRepository::is_banned(&state.db, ENTITY_USER, &user.username)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if locked {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Account temporarily locked after too many failed attempts".to_string(),
+        ));
+    }
+
+    let valid = verify_password(password, &user.password_hash)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if !valid {
+        BruteForceProtection::record_login_failure(&state.db, ip, &user.username)
+            .await
+            .map_err(|e| (StatusCode::TOO_MANY_REQUESTS, e.to_string()))?;
+        return Err((StatusCode::FORBIDDEN, "Current password is incorrect".to_string()));
+    }
+    Ok(())
+}
+
+/// Changes the password; every other session of the account is signed out
+pub async fn change_password(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: AuthUser,
+    Json(req): Json<ChangePasswordRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let current_session = auth_user.require_session()?;
+    let user = load_user(&state, &auth_user).await?;
+
+    check_password(&state, &user, &req.current_password, &addr.ip().to_string()).await?;
+    enforce_password_policy(&state, &req.new_password, &user.username, &user.email).await?;
+
+    let password_hash = hash_password(&req.new_password)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    UserRepository::change_password(&state.db, &user.id, &password_hash, current_session)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("Password changed for {}", user.username);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Everything stored about the account as a downloadable JSON file.
+/// Connection passwords and token hashes are left out.
+pub async fn export_account(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let user = load_user(&state, &auth_user).await?;
+
+    let workspaces = WorkspaceRepository::find_by_user(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let sessions = SessionRepository::find_active_by_user(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let access_tokens = AccessTokenRepository::find_by_user(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let connections = ConnectionRepository::find_by_user(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let scripts = ScriptRepository::find_by_user(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let history = QueryExecutionRepository::find_by_user(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let disposition = format!("attachment; filename=\"nexusdb-export-{}.json\"", user.username);
+    let export = AccountExport {
+        exported_at: Utc::now(),
+        workspaces: workspaces.into_iter().map(Into::into).collect(),
+        sessions,
+        access_tokens: access_tokens.into_iter().map(|t| to_response(t, None)).collect(),
+        connections,
+        scripts,
+        history,
+        user,
+    };
+
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(export)))
+}
+
+/// Deletes the account with its personal workspace, history, sessions and
+/// tokens, plus the uploaded SQLite files no remaining connection uses. Needs
+/// the password, and a 2FA code when 2FA is enabled. Refused while the user is
+/// the only owner of a workspace other members still use.
+pub async fn delete_account(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: AuthUser,
+    Json(req): Json<DeleteAccountRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = load_user(&state, &auth_user).await?;
+
+    check_password(&state, &user, &req.password, &addr.ip().to_string()).await?;
+    if user.totp_enabled_at.is_some() {
+        let code = req.code.as_deref().unwrap_or_default();
+        if !verify_second_factor(&state, &user, code).await? {
+            return Err((StatusCode::FORBIDDEN, "Invalid two-factor code".to_string()));
+        }
+    }
+
+    let sole_owned = WorkspaceRepository::find_sole_owned(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !sole_owned.is_empty() {
+        let names: Vec<_> = sole_owned.iter().map(|w| w.name.as_str()).collect();
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Transfer ownership or delete these workspaces first: {}", names.join(", ")),
+        ));
+    }
+
+    UserRepository::delete(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if let Err(e) = remove_unused_uploads(&state, &user.id).await {
+        tracing::warn!("Failed to remove uploads of deleted user {}: {}", user.username, e);
+    }
+
+    tracing::info!("Account {} deleted", user.username);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Uploads behind connections of shared workspaces stay in place for the
+/// members who keep using them
+async fn remove_unused_uploads(state: &AppState, user_id: &str) -> Result<(), anyhow::Error> {
+    let uploads = user_upload_dir(&state.config, user_id);
+    let mut entries = match tokio::fs::read_dir(&uploads).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut kept = false;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path().canonicalize()?;
+        if ConnectionRepository::is_path_used(&state.db, &path.to_string_lossy()).await? {
+            kept = true;
+        } else {
+            tokio::fs::remove_file(&path).await?;
+        }
+    }
+
+    if !kept {
+        tokio::fs::remove_dir(&uploads).await?;
+    }
+    Ok(())
+}
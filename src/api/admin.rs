@@ -0,0 +1,190 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::api::{page_offset, AppState};
+use crate::db::repository::UserRepository;
+use crate::models::User;
+use crate::security::auth::AuthUser;
+use crate::security::brute_force::{BruteForceProtection, FailedAttempts};
+use crate::security::repository::{BannedEntity, SecurityRepository, ENTITY_IP, ENTITY_USER};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct UserSearchQuery {
+    pub q: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserPage {
+    pub items: Vec<User>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BanQuery {
+    pub entity_type: Option<String>,
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBanRequest {
+    pub entity_type: String,
+    pub value: String,
+    pub reason: Option<String>,
+    // Permanent when missing
+    pub duration_minutes: Option<i64>,
+}
+
+fn parse_entity_type(entity_type: &str) -> Result<&'static str, (StatusCode, String)> {
+    match entity_type.to_ascii_uppercase().as_str() {
+        "IP" => Ok(ENTITY_IP),
+        "USER" => Ok(ENTITY_USER),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid entity_type '{}', expected IP or USER", entity_type),
+        )),
+    }
+}
+
+pub async fn list_users(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UserSearchQuery>,
+) -> Result<Json<UserPage>, (StatusCode, String)> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    let (items, total) = UserRepository::search(
+        &state.db,
+        query.q.as_deref(),
+        per_page,
+        page_offset(page, per_page)?,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(UserPage {
+        items,
+        total,
+        page,
+        per_page,
+    }))
+}
+
+pub async fn disable_user(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<User>, (StatusCode, String)> {
+    if id == auth_user.user_id {
+        return Err((StatusCode::BAD_REQUEST, "You cannot disable your own account".to_string()));
+    }
+    set_disabled(&state, &id, true).await
+}
+
+pub async fn enable_user(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<User>, (StatusCode, String)> {
+    set_disabled(&state, &id, false).await
+}
+
+async fn set_disabled(
+    state: &AppState,
+    id: &str,
+    disabled: bool,
+) -> Result<Json<User>, (StatusCode, String)> {
+    let user = UserRepository::set_disabled(&state.db, id, disabled)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    tracing::info!(
+        "User {} {}",
+        user.username,
+        if disabled { "disabled" } else { "enabled" }
+    );
+    Ok(Json(user))
+}
+
+pub async fn list_bans(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BanQuery>,
+) -> Result<Json<Vec<BannedEntity>>, (StatusCode, String)> {
+    let entity_type = query.entity_type.as_deref().map(parse_entity_type).transpose()?;
+
+    let bans = SecurityRepository::list_active(&state.db, entity_type, query.value.as_deref())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(bans))
+}
+
+pub async fn create_ban(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateBanRequest>,
+) -> Result<(StatusCode, Json<BannedEntity>), (StatusCode, String)> {
+    let entity_type = parse_entity_type(&req.entity_type)?;
+
+    let value = req.value.trim();
+    if value.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "value is required".to_string()));
+    }
+    if entity_type == ENTITY_IP && value.parse::<std::net::IpAddr>().is_err() {
+        return Err((StatusCode::BAD_REQUEST, format!("'{}' is not a valid IP address", value)));
+    }
+
+    let duration = match req.duration_minutes {
+        Some(minutes) if minutes <= 0 => {
+            return Err((StatusCode::BAD_REQUEST, "duration_minutes must be positive".to_string()));
+        }
+        Some(minutes) => Some(chrono::Duration::minutes(minutes)),
+        None => None,
+    };
+
+    let ban = SecurityRepository::ban_entity(
+        &state.db,
+        entity_type,
+        value,
+        req.reason.as_deref(),
+        duration,
+        Some(&auth_user.username),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::warn!("{} banned {} {}", auth_user.username, entity_type, value);
+    Ok((StatusCode::CREATED, Json(ban)))
+}
+
+pub async fn lift_ban(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<BannedEntity>, (StatusCode, String)> {
+    let ban = SecurityRepository::lift(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Active ban not found".to_string()))?;
+
+    // Start over so the next failed login doesn't ban again right away
+    BruteForceProtection::clear_attempts(&ban.entity_type, &ban.value);
+
+    tracing::info!("{} lifted ban on {} {}", auth_user.username, ban.entity_type, ban.value);
+    Ok(Json(ban))
+}
+
+pub async fn list_failed_attempts() -> Json<Vec<FailedAttempts>> {
+    Json(BruteForceProtection::snapshot())
+}
@@ -1,24 +1,120 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
-    Json,
+    extract::{ConnectInfo, State},
+    extract::Path,
+    http::{header, HeaderMap, StatusCode},
+    Extension, Json,
 };
+use chrono::{DateTime, Duration, Utc};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use validator::Validate;
 
+use crate::api::email::send_verification_email;
 use crate::api::AppState;
-use crate::db::repository::UserRepository;
-use crate::models::{RegisterRequest, LoginRequest, AuthResponse, UserResponse};
-use crate::security::auth::{hash_password, verify_password, AuthUser};
+use crate::db::repository::{SessionRepository, UserRepository};
+use crate::models::{
+    AuthResponse, DisableTwoFactorRequest, LoginRequest, LoginResponse, LoginTwoFactorRequest,
+    MfaChallengeResponse, RecoveryCodesResponse, RefreshRequest, RegisterRequest, SessionResponse,
+    TwoFactorCodeRequest, TwoFactorSetupResponse, User, UserResponse,
+};
+use crate::security::auth::{generate_opaque_token, hash_password, hash_token, verify_password, AuthUser, Claims};
+use crate::security::brute_force::BruteForceProtection;
+use crate::security::pow::{PoWService, PowSolution};
+use crate::security::repository::{SecurityRepository, ENTITY_IP, ENTITY_USER};
+use crate::security::totp;
+
+const MAX_USER_AGENT_LEN: usize = 512;
+const TOTP_ISSUER: &str = "NexusDB";
+
+/// Checks the solved challenge from GET /api/pow/challenge. A missing solution
+/// is answered with 428 so clients know to fetch a challenge and retry.
+fn require_pow(solution: Option<&PowSolution>, ip: &str) -> Result<(), (StatusCode, String)> {
+    let solution = solution.ok_or_else(|| {
+        (StatusCode::PRECONDITION_REQUIRED, "Proof-of-work required".to_string())
+    })?;
+
+    if !PoWService::verify_solution(solution, ip) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid or expired proof-of-work".to_string()));
+    }
+    Ok(())
+}
+
+/// Rejects passwords that break the configured policy, listing every failed rule
+pub async fn enforce_password_policy(
+    state: &AppState,
+    password: &str,
+    username: &str,
+    email: &str,
+) -> Result<(), (StatusCode, String)> {
+    let failures = state
+        .password_policy
+        .check(password, username, email)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if !failures.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Password does not meet the policy: {}", failures.join("; ")),
+        ));
+    }
+    Ok(())
+}
+
+/// Opens a new session for the user and issues its first token pair
+async fn start_session(
+    state: &AppState,
+    user: User,
+    headers: &HeaderMap,
+    ip: &str,
+) -> Result<AuthResponse, (StatusCode, String)> {
+    let refresh_token = generate_opaque_token();
+    let expires_at = Utc::now() + Duration::days(state.config.refresh_token_days);
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|ua| ua.chars().take(MAX_USER_AGENT_LEN).collect::<String>());
+
+    let session = SessionRepository::create(
+        &state.db,
+        &user.id,
+        user_agent.as_deref(),
+        ip,
+        &hash_token(&refresh_token),
+        expires_at,
+    )
+    .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let token = state
+        .auth_service
+        .create_token(&user.id, &user.username, &session.id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(AuthResponse {
+        token,
+        expires_in: state.auth_service.expires_in(),
+        refresh_token,
+        user: UserResponse::from(user),
+    })
+}
 
 pub async fn register(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(req): Json<RegisterRequest>,
 ) -> Result<Json<AuthResponse>, (StatusCode, String)> {
     // Validate input
     req.validate()
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
 
+    // Before the proof-of-work, so a rejected password doesn't spend the challenge
+    enforce_password_policy(&state, &req.password, &req.username, &req.email).await?;
+
+    require_pow(req.pow.as_ref(), &addr.ip().to_string())?;
+
     // Check if user exists
     if UserRepository::find_by_username(&state.db, &req.username)
         .await
@@ -37,56 +133,385 @@ pub async fn register(
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Generate token
-    let token = state
-        .auth_service
-        .create_token(&user.id, &user.username)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // The account works without it, just limited until the address is confirmed
+    if let Err(e) = send_verification_email(&state, &user).await {
+        tracing::error!("Failed to send verification email to {}: {}", user.email, e);
+    }
 
-    Ok(Json(AuthResponse {
-        token,
-        user: UserResponse {
-            id: user.id,
-            username: user.username,
-            email: user.email,
-        },
-    }))
+    let response = start_session(&state, user, &headers, &addr.ip().to_string()).await?;
+    Ok(Json(response))
 }
 
 pub async fn login(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
-) -> Result<Json<AuthResponse>, (StatusCode, String)> {
+) -> Result<Json<LoginResponse>, (StatusCode, String)> {
+    let ip = addr.ip().to_string();
+
+    // Banned IPs never get here, see security_middleware
+    let locked = SecurityRepository::is_banned(&state.db, ENTITY_USER, &req.username)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if locked {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Account temporarily locked after too many failed attempts".to_string(),
+        ));
+    }
+
+    if BruteForceProtection::recent_failures(ENTITY_IP, &ip) > 0 {
+        require_pow(req.pow.as_ref(), &ip)?;
+    }
+
     // Find user
     let user = UserRepository::find_by_username(&state.db, &req.username)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()))?;
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     // Verify password
-    let valid = verify_password(&req.password, &user.password_hash)
+    let valid = match &user {
+        Some(user) => verify_password(&req.password, &user.password_hash)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        None => false,
+    };
+
+    let user = match user {
+        Some(user) if valid => user,
+        _ => {
+            // Unknown usernames count too, so failures don't reveal which accounts exist
+            BruteForceProtection::record_login_failure(&state.db, &ip, &req.username)
+                .await
+                .map_err(|e| (StatusCode::TOO_MANY_REQUESTS, e.to_string()))?;
+            return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
+        }
+    };
+
+    if user.disabled_at.is_some() {
+        return Err((StatusCode::FORBIDDEN, "Account disabled".to_string()));
+    }
+
+    // Second step at /api/auth/login/2fa; failed attempts are only cleared once it succeeds
+    if user.totp_enabled_at.is_some() {
+        let mfa_token = state
+            .auth_service
+            .create_mfa_token(&user.id)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        return Ok(Json(LoginResponse::MfaRequired(MfaChallengeResponse {
+            mfa_required: true,
+            mfa_token,
+            expires_in: state.auth_service.mfa_token_expires_in(),
+        })));
+    }
+
+    BruteForceProtection::clear_attempts(ENTITY_IP, &ip);
+    BruteForceProtection::clear_attempts(ENTITY_USER, &req.username);
+
+    let response = start_session(&state, user, &headers, &ip).await?;
+    Ok(Json(LoginResponse::Authenticated(response)))
+}
+
+/// Second login step for accounts with 2FA: trades the MFA token from `login`
+/// plus a TOTP or recovery code for a session.
+pub async fn login_two_factor(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<LoginTwoFactorRequest>,
+) -> Result<Json<AuthResponse>, (StatusCode, String)> {
+    let ip = addr.ip().to_string();
+
+    let user_id = state
+        .auth_service
+        .verify_mfa_token(&req.mfa_token)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid or expired MFA token".to_string()))?;
+
+    let user = UserRepository::find_by_id(&state.db, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Invalid or expired MFA token".to_string()))?;
+
+    let locked = SecurityRepository::is_banned(&state.db, ENTITY_USER, &user.username)
+        .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if locked {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Account temporarily locked after too many failed attempts".to_string(),
+        ));
+    }
+    if user.disabled_at.is_some() {
+        return Err((StatusCode::FORBIDDEN, "Account disabled".to_string()));
+    }
+
+    if !verify_second_factor(&state, &user, &req.code).await? {
+        BruteForceProtection::record_login_failure(&state.db, &ip, &user.username)
+            .await
+            .map_err(|e| (StatusCode::TOO_MANY_REQUESTS, e.to_string()))?;
+        return Err((StatusCode::UNAUTHORIZED, "Invalid two-factor code".to_string()));
+    }
+
+    BruteForceProtection::clear_attempts(ENTITY_IP, &ip);
+    BruteForceProtection::clear_attempts(ENTITY_USER, &user.username);
 
-    if !valid {
+    let response = start_session(&state, user, &headers, &ip).await?;
+    Ok(Json(response))
+}
+
+fn decrypt_totp_secret(state: &AppState, user: &User) -> Result<Option<String>, (StatusCode, String)> {
+    user.totp_secret
+        .as_deref()
+        .map(|secret| state.encryption_service.decrypt_credentials(secret))
+        .transpose()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Checks a TOTP code (at most once per time step) or consumes a recovery code
+pub async fn verify_second_factor(state: &AppState, user: &User, code: &str) -> Result<bool, (StatusCode, String)> {
+    let Some(secret) = decrypt_totp_secret(state, user)? else {
+        return Ok(false);
+    };
+
+    if let Some(step) = totp::verify(&secret, code, Utc::now().timestamp(), user.totp_last_step) {
+        return UserRepository::record_totp_step(&state.db, &user.id, step)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+    }
+
+    let code = totp::normalize_recovery_code(code);
+    if code.is_empty() || user.totp_enabled_at.is_none() {
+        return Ok(false);
+    }
+
+    let recovery_codes = UserRepository::unused_recovery_codes(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    for recovery_code in recovery_codes {
+        let matches = verify_password(&code, &recovery_code.code_hash)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if matches {
+            return UserRepository::use_recovery_code(&state.db, &recovery_code.id)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+    }
+    Ok(false)
+}
+
+/// New recovery codes in plain text (returned once) and their argon2 hashes
+fn new_recovery_codes() -> Result<(Vec<String>, Vec<String>), (StatusCode, String)> {
+    let codes = totp::generate_recovery_codes();
+    let hashes = codes
+        .iter()
+        .map(|code| hash_password(&totp::normalize_recovery_code(code)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok((codes, hashes))
+}
+
+async fn load_user(state: &AppState, auth_user: &AuthUser) -> Result<User, (StatusCode, String)> {
+    UserRepository::find_by_id(&state.db, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))
+}
+
+/// Starts 2FA enrollment with a fresh secret. Nothing changes for login until
+/// the secret is confirmed with a code.
+pub async fn enroll_two_factor(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<TwoFactorSetupResponse>, (StatusCode, String)> {
+    let user = load_user(&state, &auth_user).await?;
+    if user.totp_enabled_at.is_some() {
+        return Err((StatusCode::CONFLICT, "Two-factor authentication is already enabled".to_string()));
+    }
+
+    let secret = totp::generate_secret();
+    let encrypted = state
+        .encryption_service
+        .encrypt_credentials(&secret)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    UserRepository::set_pending_totp(&state.db, &user.id, &encrypted)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(TwoFactorSetupResponse {
+        otpauth_uri: totp::otpauth_uri(TOTP_ISSUER, &user.username, &secret),
+        secret,
+    }))
+}
+
+/// Confirms enrollment with a code from the authenticator app and returns
+/// the recovery codes, which are not shown again
+pub async fn confirm_two_factor(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, (StatusCode, String)> {
+    let user = load_user(&state, &auth_user).await?;
+    if user.totp_enabled_at.is_some() {
+        return Err((StatusCode::CONFLICT, "Two-factor authentication is already enabled".to_string()));
+    }
+    if user.totp_secret.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "Start enrollment first".to_string()));
+    }
+
+    if !verify_second_factor(&state, &user, &req.code).await? {
+        return Err((StatusCode::BAD_REQUEST, "Invalid two-factor code".to_string()));
+    }
+
+    let (recovery_codes, hashes) = new_recovery_codes()?;
+    UserRepository::enable_totp(&state.db, &user.id, &hashes)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("Two-factor authentication enabled for {}", user.username);
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Replaces all recovery codes; needs a current TOTP or recovery code
+pub async fn regenerate_recovery_codes(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, (StatusCode, String)> {
+    let user = load_user(&state, &auth_user).await?;
+    if user.totp_enabled_at.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "Two-factor authentication is not enabled".to_string()));
+    }
+    if !verify_second_factor(&state, &user, &req.code).await? {
+        return Err((StatusCode::BAD_REQUEST, "Invalid two-factor code".to_string()));
+    }
+
+    let (recovery_codes, hashes) = new_recovery_codes()?;
+    UserRepository::regenerate_recovery_codes(&state.db, &user.id, &hashes)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+pub async fn disable_two_factor(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(req): Json<DisableTwoFactorRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = load_user(&state, &auth_user).await?;
+    if user.totp_enabled_at.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "Two-factor authentication is not enabled".to_string()));
+    }
+
+    let password_ok = verify_password(&req.password, &user.password_hash)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !password_ok || !verify_second_factor(&state, &user, &req.code).await? {
         return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
     }
 
-    // Generate token
+    UserRepository::disable_totp(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("Two-factor authentication disabled for {}", user.username);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Exchanges a refresh token for a new token pair. Every refresh token works
+/// once: presenting one that was already used means it leaked, so the whole
+/// session is revoked.
+pub async fn refresh(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(req): Json<RefreshRequest>,
+) -> Result<Json<AuthResponse>, (StatusCode, String)> {
+    let invalid = || (StatusCode::UNAUTHORIZED, "Invalid refresh token".to_string());
+
+    let current = SessionRepository::find_refresh_token(&state.db, &hash_token(&req.refresh_token))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(invalid)?;
+
+    let session = SessionRepository::find_by_id(&state.db, &current.session_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(invalid)?;
+
+    let now = Utc::now();
+    if session.revoked_at.is_some() || current.expires_at <= now {
+        return Err(invalid());
+    }
+
+    let refresh_token = generate_opaque_token();
+    let expires_at = now + Duration::days(state.config.refresh_token_days);
+
+    let rotated = current.used_at.is_none()
+        && SessionRepository::rotate_refresh_token(&state.db, &current, &hash_token(&refresh_token), expires_at)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !rotated {
+        tracing::warn!(
+            "Refresh token reuse detected for session {}, revoking it",
+            session.id
+        );
+        SessionRepository::revoke(&state.db, &session.id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Err(invalid());
+    }
+
+    SessionRepository::touch(&state.db, &session.id, &addr.ip().to_string(), Duration::zero())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let user = UserRepository::find_by_id(&state.db, &session.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(invalid)?;
+    if user.disabled_at.is_some() {
+        return Err((StatusCode::FORBIDDEN, "Account disabled".to_string()));
+    }
+    let banned = SecurityRepository::is_banned_by_admin(&state.db, ENTITY_USER, &user.username)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if banned {
+        return Err((StatusCode::FORBIDDEN, "Account banned".to_string()));
+    }
+
     let token = state
         .auth_service
-        .create_token(&user.id, &user.username)
+        .create_token(&user.id, &user.username, &session.id)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     Ok(Json(AuthResponse {
         token,
-        user: UserResponse {
-            id: user.id,
-            username: user.username,
-            email: user.email,
-        },
+        expires_in: state.auth_service.expires_in(),
+        refresh_token,
+        user: UserResponse::from(user),
     }))
 }
 
+/// Ends the current session: the access token and every refresh token of the
+/// session stop working immediately.
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let expires_at = DateTime::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now);
+
+    SessionRepository::revoke_access_token(&state.db, &claims.jti, expires_at)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    SessionRepository::revoke(&state.db, &claims.sid)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn get_current_user(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
@@ -96,9 +521,58 @@ pub async fn get_current_user(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
         .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
 
-    Ok(Json(UserResponse {
-        id: user.id,
-        username: user.username,
-        email: user.email,
-    }))
+    Ok(Json(UserResponse::from(user)))
+}
+
+pub async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<Vec<SessionResponse>>, (StatusCode, String)> {
+    let sessions = SessionRepository::find_active_by_user(&state.db, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let response = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            current: Some(session.id.as_str()) == auth_user.session_id.as_deref(),
+            id: session.id,
+            user_agent: session.user_agent,
+            ip: session.ip,
+            created_at: session.created_at,
+            last_used_at: session.last_used_at,
+            expires_at: session.expires_at,
+        })
+        .collect();
+
+    Ok(Json(response))
+}
+
+/// Signs out one device. Revoking the current session works like logout.
+pub async fn revoke_session(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let revoked = SessionRepository::revoke_for_user(&state.db, &id, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if !revoked {
+        return Err((StatusCode::NOT_FOUND, "Session not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Signs out every other device, keeping the session that made the request
+pub async fn revoke_other_sessions(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let current = auth_user.require_session()?;
+    let revoked = SessionRepository::revoke_others(&state.db, &auth_user.user_id, current)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(serde_json::json!({ "revoked": revoked })))
 }
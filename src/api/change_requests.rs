@@ -0,0 +1,324 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::api::execution::{check_query, run_query};
+use crate::api::policies::connection_policy;
+use crate::api::workspaces::{connection_for, require_role};
+use crate::api::{detached, AppState};
+use crate::db::repository::{ChangeRequestRepository, ConnectionRepository, ReviewOutcome};
+use crate::models::{
+    ChangeRequest, ChangeRequestFilter, ChangeRequestResponse, ChangeRequestStatus, CreateChangeRequest,
+    ReviewDecision, ReviewRequest, WorkspaceRole,
+};
+use crate::security::auth::AuthUser;
+use crate::security::policy;
+use crate::security::tokens::{SCOPE_CONNECTIONS_READ, SCOPE_CONNECTIONS_WRITE, SCOPE_QUERY_EXECUTE};
+
+const MAX_LISTED_REQUESTS: i64 = 100;
+// Rows of a run kept with the request
+const MAX_STORED_ROWS: usize = 1000;
+
+/// Loads a change request of a connection the user can see, along with the
+/// user's role in the connection's workspace
+async fn change_request_for(
+    state: &AppState,
+    auth_user: &AuthUser,
+    id: &str,
+    request_id: &str,
+) -> Result<(ChangeRequest, WorkspaceRole), (StatusCode, String)> {
+    auth_user.require_connection(id)?;
+    let access = ConnectionRepository::find_by_id(&state.db, id, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Connection not found".to_string()))?;
+
+    let request = ChangeRequestRepository::find_by_id(&state.db, id, request_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Change request not found".to_string()))?;
+    Ok((request, access.role))
+}
+
+fn not_in_status(request: &ChangeRequest) -> (StatusCode, String) {
+    (
+        StatusCode::CONFLICT,
+        format!("Change request is {}", request.status.as_str()),
+    )
+}
+
+async fn respond(state: &AppState, request: ChangeRequest) -> Result<ChangeRequestResponse, (StatusCode, String)> {
+    let reviews = ChangeRequestRepository::find_reviews(&state.db, &request.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let approvals = reviews
+        .iter()
+        .filter(|review| review.decision == ReviewDecision::Approved)
+        .count();
+
+    Ok(ChangeRequestResponse {
+        summary: serde_json::from_str(&request.summary).unwrap_or_default(),
+        result: request.result.as_deref().and_then(|result| serde_json::from_str(result).ok()),
+        approvals,
+        reviews,
+        request,
+    })
+}
+
+/// Submits a write for a protected connection. It runs only once approved.
+pub async fn create_change_request(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Json(req): Json<CreateChangeRequest>,
+) -> Result<(StatusCode, Json<ChangeRequestResponse>), (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_QUERY_EXECUTE)?;
+    req.validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+    if req.justification.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A justification is required".to_string()));
+    }
+
+    let conn = connection_for(&state, &auth_user, &id, WorkspaceRole::Runner).await?;
+    let driver = state.drivers.get(&conn.db_type).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported database type: {}", conn.db_type),
+        )
+    })?;
+
+    let policy = connection_policy(&state, &conn.id).await?;
+    if !policy.require_approval {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "This connection doesn't require approval; run the query directly".to_string(),
+        ));
+    }
+    // Anything the policy would refuse at run time is refused now, before
+    // reviewers spend time on it
+    let summary = check_query(&policy, &driver.info(), &req.query)?;
+    if !policy::needs_approval(&policy, &summary.classes) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Only statements other than SELECT need approval; run the query directly".to_string(),
+        ));
+    }
+    let summary = serde_json::to_string(&summary).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let request = ChangeRequestRepository::create(
+        &state.db,
+        &conn,
+        &auth_user.user_id,
+        &req.query,
+        req.justification.trim(),
+        &summary,
+        &policy,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!(
+        "{} requested approval for change {} on connection {}",
+        auth_user.username,
+        request.id,
+        conn.id
+    );
+    Ok((StatusCode::CREATED, Json(respond(&state, request).await?)))
+}
+
+/// Newest first, optionally with a given `?status=`
+pub async fn list_change_requests(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Query(filter): Query<ChangeRequestFilter>,
+) -> Result<Json<Vec<ChangeRequestResponse>>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let conn = connection_for(&state, &auth_user, &id, WorkspaceRole::Viewer).await?;
+
+    let requests = ChangeRequestRepository::find_by_connection(&state.db, &conn.id, filter.status, MAX_LISTED_REQUESTS)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut responses = Vec::with_capacity(requests.len());
+    for request in requests {
+        responses.push(respond(&state, request).await?);
+    }
+    Ok(Json(responses))
+}
+
+pub async fn get_change_request(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((id, request_id)): Path<(String, String)>,
+) -> Result<Json<ChangeRequestResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let (request, _) = change_request_for(&state, &auth_user, &id, &request_id).await?;
+    Ok(Json(respond(&state, request).await?))
+}
+
+pub async fn approve_change_request(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((id, request_id)): Path<(String, String)>,
+    Json(req): Json<ReviewRequest>,
+) -> Result<Json<ChangeRequestResponse>, (StatusCode, String)> {
+    review(&state, &auth_user, &id, &request_id, req, ReviewDecision::Approved).await
+}
+
+pub async fn reject_change_request(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((id, request_id)): Path<(String, String)>,
+    Json(req): Json<ReviewRequest>,
+) -> Result<Json<ChangeRequestResponse>, (StatusCode, String)> {
+    review(&state, &auth_user, &id, &request_id, req, ReviewDecision::Rejected).await
+}
+
+/// Records a decision. Reviewers need the role the request asked for and
+/// can't review their own requests.
+async fn review(
+    state: &AppState,
+    auth_user: &AuthUser,
+    id: &str,
+    request_id: &str,
+    req: ReviewRequest,
+    decision: ReviewDecision,
+) -> Result<Json<ChangeRequestResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_WRITE)?;
+    // Approvals take a signed-in user, not a personal access token
+    auth_user.require_session()?;
+    req.validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+
+    let (request, role) = change_request_for(state, auth_user, id, request_id).await?;
+    require_role(role, request.approver_role)?;
+    if request.requested_by.as_deref() == Some(auth_user.user_id.as_str()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "You can't review your own change request".to_string(),
+        ));
+    }
+
+    let comment = req.comment.as_deref().map(str::trim).filter(|comment| !comment.is_empty());
+    let outcome = ChangeRequestRepository::review(&state.db, &request.id, &auth_user.user_id, decision, comment)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let request = match outcome {
+        ReviewOutcome::Recorded(request) => *request,
+        ReviewOutcome::AlreadyReviewed => {
+            return Err((StatusCode::CONFLICT, "You already reviewed this change request".to_string()));
+        }
+        ReviewOutcome::NotPending => return Err((StatusCode::CONFLICT, "Change request is no longer pending".to_string())),
+    };
+
+    tracing::info!(
+        "{} {} change {} on connection {}",
+        auth_user.username,
+        if decision == ReviewDecision::Approved { "approved" } else { "rejected" },
+        request.id,
+        id
+    );
+    Ok(Json(respond(state, request).await?))
+}
+
+/// Withdraws a request that hasn't run. Open to its requester and to the
+/// workspace's admins.
+pub async fn cancel_change_request(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((id, request_id)): Path<(String, String)>,
+) -> Result<Json<ChangeRequestResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_QUERY_EXECUTE)?;
+
+    let (request, role) = change_request_for(&state, &auth_user, &id, &request_id).await?;
+    if request.requested_by.as_deref() != Some(auth_user.user_id.as_str()) {
+        require_role(role, WorkspaceRole::Admin)?;
+    }
+
+    let request = ChangeRequestRepository::cancel(&state.db, &request.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| not_in_status(&request))?;
+
+    tracing::info!("{} cancelled change {} on connection {}", auth_user.username, request.id, id);
+    Ok(Json(respond(&state, request).await?))
+}
+
+/// Runs an approved request, once, as its requester. Whatever happens, the
+/// request is closed with the result or the error attached.
+pub async fn execute_change_request(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((id, request_id)): Path<(String, String)>,
+) -> Result<Json<ChangeRequestResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_QUERY_EXECUTE)?;
+
+    let (request, _) = change_request_for(&state, &auth_user, &id, &request_id).await?;
+    if request.requested_by.as_deref() != Some(auth_user.user_id.as_str()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only the requester can run a change request".to_string(),
+        ));
+    }
+    if request.status != ChangeRequestStatus::Approved {
+        return Err(not_in_status(&request));
+    }
+
+    // Outside the allowed hours the approval is kept for later
+    let policy = connection_policy(&state, &id).await?;
+    policy::check_hours(&policy, Utc::now()).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+
+    // Once claimed the request has to be closed, so the rest runs on its own
+    // task and a client that disconnects can't leave it executing forever
+    let request = detached(run_change_request(state.clone(), auth_user, id, request)).await?;
+    Ok(Json(respond(&state, request).await?))
+}
+
+/// Claims an approved request, runs it and attaches the outcome
+async fn run_change_request(
+    state: Arc<AppState>,
+    auth_user: AuthUser,
+    id: String,
+    request: ChangeRequest,
+) -> Result<ChangeRequest, (StatusCode, String)> {
+    let request = ChangeRequestRepository::claim(&state.db, &request.id, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::CONFLICT, "Change request is no longer approved".to_string()))?;
+
+    let outcome = match run_query(&state, &auth_user, &id, &request.query, None, true).await {
+        Ok(mut response) => {
+            if response.rows.len() > MAX_STORED_ROWS {
+                response.rows.truncate(MAX_STORED_ROWS);
+                if let Some(documents) = response.documents.as_mut() {
+                    documents.truncate(MAX_STORED_ROWS);
+                }
+                response.rows_count = response.rows.len();
+                response.truncated = true;
+            }
+            serde_json::to_string(&response).map_err(|e| e.to_string())
+        }
+        Err((_, message)) => Err(message),
+    };
+
+    let request = ChangeRequestRepository::finish(&state.db, &request.id, outcome.as_deref().map_err(String::as_str))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!(
+        "{} ran change {} on connection {}: {}",
+        auth_user.username,
+        request.id,
+        id,
+        request.status.as_str()
+    );
+    Ok(request)
+}
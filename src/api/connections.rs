@@ -1,23 +1,106 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{multipart::Field, Multipart, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
     Json,
 };
+use std::path::{Path as FsPath, PathBuf};
 use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
 use validator::Validate;
 
+use crate::api::precondition::{self, etag_header};
+use crate::api::workspaces::{connection_for, target_workspace};
 use crate::api::AppState;
-use crate::db::repository::ConnectionRepository;
-use crate::models::CreateConnectionRequest;
+use crate::config::Config;
+use crate::db::repository::{ConnectionRepository, SchemaCacheRepository};
+use crate::drivers::sqlite;
+use crate::models::{
+    Connection, ConnectionResponse, CreateConnectionRequest, UpdateConnectionRequest, WorkspaceFilter, WorkspaceRole,
+};
+use crate::monitor;
 use crate::security::auth::AuthUser;
+use crate::security::tokens::{SCOPE_CONNECTIONS_READ, SCOPE_CONNECTIONS_WRITE};
+
+/// What the API returns for a connection; `role` only in listings, which
+/// span workspaces
+pub fn to_response(conn: Connection, role: Option<WorkspaceRole>) -> ConnectionResponse {
+    ConnectionResponse {
+        id: conn.id,
+        workspace_id: conn.workspace_id,
+        name: conn.name,
+        db_type: conn.db_type,
+        host: conn.host,
+        port: conn.port,
+        username: conn.username,
+        database_name: conn.database_name,
+        status: conn.status,
+        last_checked_at: conn.last_checked_at,
+        created_at: conn.created_at,
+        updated_at: conn.updated_at,
+        role,
+    }
+}
+
+/// Tokens limited to some connections can't add new ones
+fn require_unrestricted(auth_user: &AuthUser) -> Result<(), (StatusCode, String)> {
+    if auth_user.connection_ids.is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Token is restricted to specific connections".to_string(),
+        ));
+    }
+    Ok(())
+}
 
 pub async fn create_connection(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
     Json(req): Json<CreateConnectionRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> Result<Json<ConnectionResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_WRITE)?;
+    require_unrestricted(&auth_user)?;
+
     req.validate()
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+    let workspace_id =
+        target_workspace(&state, &auth_user, req.workspace_id.as_deref(), WorkspaceRole::Admin).await?;
+
+    // Store the canonical driver name so aliases like "postgresql" resolve the same way
+    let info = state
+        .drivers
+        .get(&req.db_type)
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported database type: {}", req.db_type),
+            )
+        })?
+        .info();
+    if info.requires_host && req.host.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("{} connections require a host", info.display_name),
+        ));
+    }
+
+    // SQLite connections point at a file on the server instead of a host
+    let database_name = if info.name == "sqlite" {
+        let path = req
+            .database_name
+            .as_deref()
+            .ok_or_else(|| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    "SQLite connections require a file path in database_name".to_string(),
+                )
+            })?;
+        let resolved = resolve_sqlite_path(&state.config, &auth_user.user_id, path)?;
+        Some(resolved.to_string_lossy().into_owned())
+    } else {
+        req.database_name.clone()
+    };
 
     // Encrypt password
     let encrypted_password = state
@@ -29,53 +112,38 @@ pub async fn create_connection(
     let conn = ConnectionRepository::create(
         &state.db,
         &auth_user.user_id,
+        &workspace_id,
         &req.name,
-        &req.db_type,
+        info.name,
         &req.host,
         req.port,
         &req.username,
         &encrypted_password,
-        req.database_name.as_deref(),
+        database_name.as_deref(),
     )
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok(Json(serde_json::json!({
-        "id": conn.id,
-        "name": conn.name,
-        "db_type": conn.db_type,
-        "host": conn.host,
-        "port": conn.port,
-        "username": conn.username,
-        "database_name": conn.database_name,
-        "status": conn.status,
-        "created_at": conn.created_at,
-    })))
+    Ok(Json(to_response(conn, None)))
 }
 
+/// Connections of every workspace the user belongs to, or of `workspace_id`
 pub async fn list_connections(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
-) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
-    let connections = ConnectionRepository::find_by_user(&state.db, &auth_user.user_id)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Query(filter): Query<WorkspaceFilter>,
+) -> Result<Json<Vec<ConnectionResponse>>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let connections =
+        ConnectionRepository::find_accessible(&state.db, &auth_user.user_id, filter.workspace_id.as_deref())
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let response: Vec<_> = connections
         .into_iter()
-        .map(|conn| {
-            serde_json::json!({
-                "id": conn.id,
-                "name": conn.name,
-                "db_type": conn.db_type,
-                "host": conn.host,
-                "port": conn.port,
-                "username": conn.username,
-                "database_name": conn.database_name,
-                "status": conn.status,
-                "created_at": conn.created_at,
-            })
-        })
+        .filter(|access| auth_user.can_access_connection(&access.connection.id))
+        .map(|access| to_response(access.connection, Some(access.role)))
         .collect();
 
     Ok(Json(response))
@@ -85,22 +153,150 @@ pub async fn get_connection(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let conn = ConnectionRepository::find_by_id(&state.db, &id, &auth_user.user_id)
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let conn = connection_for(&state, &auth_user, &id, WorkspaceRole::Viewer).await?;
+
+    Ok((etag_header(&conn.updated_at), Json(to_response(conn, None))))
+}
+
+pub async fn update_connection(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<UpdateConnectionRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_WRITE)?;
+    require_unrestricted(&auth_user)?;
+
+    req.validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+    let expected = precondition::expected_version(&headers, req.updated_at)?;
+
+    let current = connection_for(&state, &auth_user, &id, WorkspaceRole::Admin).await?;
+    let info = state
+        .drivers
+        .get(&current.db_type)
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported database type: {}", current.db_type),
+            )
+        })?
+        .info();
+
+    let mut conn = current.clone();
+    if let Some(name) = req.name {
+        conn.name = name;
+    }
+    if let Some(host) = req.host {
+        if info.requires_host && host.trim().is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("{} connections require a host", info.display_name),
+            ));
+        }
+        conn.host = host;
+    }
+    if let Some(port) = req.port {
+        conn.port = port;
+    }
+    if let Some(username) = req.username {
+        conn.username = username;
+    }
+    if let Some(database_name) = req.database_name {
+        conn.database_name = if info.name == "sqlite" {
+            // Uploads live in the creator's directory, whoever edits the connection
+            let resolved = resolve_sqlite_path(&state.config, &current.user_id, &database_name)?;
+            Some(resolved.to_string_lossy().into_owned())
+        } else {
+            Some(database_name)
+        };
+    }
+
+    // The stored password must not follow the connection to a different server or account
+    let destination_changed = conn.host != current.host
+        || conn.port != current.port
+        || conn.username != current.username
+        || conn.database_name != current.database_name;
+    let password = match req.password {
+        Some(password) => Some(password),
+        None if destination_changed && info.requires_host => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "A new password is required when the host, port, username or database changes"
+                    .to_string(),
+            ));
+        }
+        // Connections without a host (SQLite) have no credentials to carry over
+        None if destination_changed => Some(String::new()),
+        None => None,
+    };
+    if let Some(password) = password {
+        conn.encrypted_password = state
+            .encryption_service
+            .encrypt_credentials(&password)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    // The last health check no longer describes a connection pointing elsewhere
+    let target_changed =
+        destination_changed || conn.encrypted_password != current.encrypted_password;
+    if target_changed {
+        conn.status = "disconnected".to_string();
+        conn.last_checked_at = None;
+    }
+
+    let conn = ConnectionRepository::update(&state.db, &conn, expected)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or_else(|| (StatusCode::NOT_FOUND, "Connection not found".to_string()))?;
+        .ok_or_else(precondition::version_conflict)?;
+
+    // The cached schema belongs to the previous target
+    if target_changed {
+        SchemaCacheRepository::delete(&state.db, &conn.id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    // An uploaded SQLite file that is no longer referenced goes away
+    if let Some(old_path) = current.database_name.as_deref() {
+        if info.name == "sqlite" && conn.database_name.as_deref() != Some(old_path) {
+            remove_upload_if_unused(&state, &current.user_id, old_path).await;
+        }
+    }
+
+    Ok((etag_header(&conn.updated_at), Json(to_response(conn, None))))
+}
+
+pub async fn test_connection(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let conn = connection_for(&state, &auth_user, &id, WorkspaceRole::Viewer).await?;
+
+    // A failed check is a valid answer, only bookkeeping errors are 500s
+    let result = monitor::check_connection(&state, &conn).await;
+    let conn = connection_for(&state, &auth_user, &id, WorkspaceRole::Viewer).await?;
+
+    let (latency_ms, server_version, error) = match result {
+        Ok(test) => (Some(test.latency_ms), Some(test.server_version), None),
+        Err(e) => (None, None, Some(e.to_string())),
+    };
 
     Ok(Json(serde_json::json!({
         "id": conn.id,
-        "name": conn.name,
-        "db_type": conn.db_type,
-        "host": conn.host,
-        "port": conn.port,
-        "username": conn.username,
-        "database_name": conn.database_name,
+        "success": error.is_none(),
         "status": conn.status,
-        "created_at": conn.created_at,
+        "latency_ms": latency_ms,
+        "server_version": server_version,
+        "error": error,
+        "last_checked_at": conn.last_checked_at,
     })))
 }
 
@@ -109,13 +305,237 @@ pub async fn delete_connection(
     auth_user: AuthUser,
     Path(id): Path<String>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    let deleted = ConnectionRepository::delete(&state.db, &id, &auth_user.user_id)
+    auth_user.require_scope(SCOPE_CONNECTIONS_WRITE)?;
+
+    let conn = connection_for(&state, &auth_user, &id, WorkspaceRole::Admin).await?;
+
+    let deleted = ConnectionRepository::delete(&state.db, &id)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    if deleted {
-        Ok(StatusCode::NO_CONTENT)
-    } else {
-        Err((StatusCode::NOT_FOUND, "Connection not found".to_string()))
+    if !deleted {
+        return Err((StatusCode::NOT_FOUND, "Connection not found".to_string()));
     }
+
+    // Uploaded SQLite files go away with the last connection using them
+    if conn.db_type == "sqlite" {
+        if let Some(path) = conn.database_name.as_deref() {
+            remove_upload_if_unused(&state, &conn.user_id, path).await;
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn upload_sqlite_database(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    mut multipart: Multipart,
+) -> Result<Json<ConnectionResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_WRITE)?;
+    require_unrestricted(&auth_user)?;
+
+    let dir = user_upload_dir(&state.config, &auth_user.user_id);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut name: Option<String> = None;
+    let mut workspace_id: Option<String> = None;
+    let mut stored: Option<(PathBuf, String)> = None;
+
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+    {
+        match field.name() {
+            Some("name") => {
+                name = Some(
+                    field
+                        .text()
+                        .await
+                        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?,
+                );
+            }
+            Some("workspace_id") => {
+                workspace_id = Some(
+                    field
+                        .text()
+                        .await
+                        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?,
+                );
+            }
+            Some("file") => {
+                let file_name = field
+                    .file_name()
+                    .map(str::to_string)
+                    .ok_or_else(|| (StatusCode::BAD_REQUEST, "Missing file name".to_string()))?;
+
+                if !sqlite::has_allowed_extension(&file_name) {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!("Only {} files are accepted", sqlite::ALLOWED_EXTENSIONS.join(", ")),
+                    ));
+                }
+
+                let extension = FsPath::new(&file_name)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .unwrap_or("db")
+                    .to_lowercase();
+                let path = dir.join(format!("{}.{}", Uuid::new_v4(), extension));
+
+                if let Err(e) = write_upload(&mut field, &path).await {
+                    let _ = tokio::fs::remove_file(&path).await;
+                    return Err(e);
+                }
+
+                stored = Some((path, file_name));
+            }
+            _ => {}
+        }
+    }
+
+    let (path, file_name) = stored
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Missing file field".to_string()))?;
+
+    let name = name.filter(|n| !n.trim().is_empty()).unwrap_or(file_name);
+    if name.len() > 100 {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err((StatusCode::BAD_REQUEST, "Validation error: name is too long".to_string()));
+    }
+
+    let workspace_id = workspace_id.filter(|w| !w.trim().is_empty());
+    let workspace_id =
+        match target_workspace(&state, &auth_user, workspace_id.as_deref(), WorkspaceRole::Admin).await {
+            Ok(workspace_id) => workspace_id,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&path).await;
+                return Err(e);
+            }
+        };
+
+    let path = path
+        .canonicalize()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let encrypted_password = state
+        .encryption_service
+        .encrypt_credentials("")
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let conn = ConnectionRepository::create(
+        &state.db,
+        &auth_user.user_id,
+        &workspace_id,
+        &name,
+        "sqlite",
+        "",
+        0,
+        "",
+        &encrypted_password,
+        Some(&path.to_string_lossy()),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(to_response(conn, None)))
+}
+
+async fn write_upload(field: &mut Field<'_>, path: &FsPath) -> Result<(), (StatusCode, String)> {
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut header = Vec::with_capacity(16);
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+    {
+        if header.len() < 16 {
+            let take = (16 - header.len()).min(chunk.len());
+            header.extend_from_slice(&chunk[..take]);
+            if header.len() == 16 && !sqlite::has_sqlite_header(&header) {
+                return Err((StatusCode::BAD_REQUEST, "File is not a SQLite database".to_string()));
+            }
+        }
+
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    if !sqlite::has_sqlite_header(&header) {
+        return Err((StatusCode::BAD_REQUEST, "File is not a SQLite database".to_string()));
+    }
+
+    file.flush()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+pub fn user_upload_dir(config: &Config, user_id: &str) -> PathBuf {
+    FsPath::new(&config.upload_dir).join(user_id)
+}
+
+pub fn is_uploaded_file(config: &Config, user_id: &str, path: &FsPath) -> bool {
+    user_upload_dir(config, user_id)
+        .canonicalize()
+        .map(|dir| path.starts_with(dir))
+        .unwrap_or(false)
+}
+
+/// Deletes a file uploaded by `user_id` once no connection points at it.
+/// Other connections may share the upload, and paths outside the upload
+/// directory are never touched.
+pub async fn remove_upload_if_unused(state: &AppState, user_id: &str, path: &str) {
+    if !is_uploaded_file(&state.config, user_id, FsPath::new(path)) {
+        return;
+    }
+    match ConnectionRepository::is_path_used(&state.db, path).await {
+        Ok(true) => {}
+        Ok(false) => {
+            if let Err(e) = tokio::fs::remove_file(path).await {
+                tracing::warn!("Failed to remove uploaded database {}: {}", path, e);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to check whether {} is still in use: {}", path, e),
+    }
+}
+
+/// Checks a server-side SQLite path against the allowed directories. Uploads
+/// of other users and the metadata database itself are never reachable.
+fn resolve_sqlite_path(
+    config: &Config,
+    user_id: &str,
+    path: &str,
+) -> Result<PathBuf, (StatusCode, String)> {
+    let mut allowed: Vec<PathBuf> = config.sqlite_allowed_dirs.iter().map(PathBuf::from).collect();
+    allowed.push(user_upload_dir(config, user_id));
+
+    let resolved = sqlite::resolve_path(path, &allowed)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    if let Ok(uploads) = FsPath::new(&config.upload_dir).canonicalize() {
+        if resolved.starts_with(&uploads) && !is_uploaded_file(config, user_id, &resolved) {
+            return Err((StatusCode::FORBIDDEN, "SQLite path is not accessible".to_string()));
+        }
+    }
+
+    let metadata_path = config
+        .database_url
+        .trim_start_matches("sqlite://")
+        .trim_start_matches("sqlite:")
+        .split('?')
+        .next()
+        .unwrap_or_default();
+    if let Ok(metadata) = FsPath::new(metadata_path).canonicalize() {
+        if resolved == metadata {
+            return Err((StatusCode::FORBIDDEN, "SQLite path is not accessible".to_string()));
+        }
+    }
+
+    Ok(resolved)
 }
@@ -0,0 +1,9 @@
+use axum::{extract::State, Json};
+use std::sync::Arc;
+
+use crate::api::AppState;
+use crate::drivers::DriverInfo;
+
+pub async fn list_drivers(State(state): State<Arc<AppState>>) -> Json<Vec<DriverInfo>> {
+    Json(state.drivers.list())
+}
@@ -0,0 +1,315 @@
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, Utc};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::api::account::check_password;
+use crate::api::auth::enforce_password_policy;
+use crate::api::AppState;
+use crate::db::repository::{EmailTokenRepository, UserRepository};
+use crate::mailer::Email;
+use crate::models::{
+    ChangeEmailRequest, EmailTokenRequest, ForgotPasswordRequest, ResetPasswordRequest, User, EMAIL_TOKEN_CHANGE,
+    EMAIL_TOKEN_RESET, EMAIL_TOKEN_VERIFY,
+};
+use crate::security::auth::{generate_opaque_token, hash_password, hash_token, AuthUser};
+use crate::security::brute_force::BruteForceProtection;
+use crate::security::repository::ENTITY_USER;
+
+const VERIFY_TOKEN_HOURS: i64 = 24;
+const RESET_TOKEN_MINUTES: i64 = 60;
+const CHANGE_TOKEN_HOURS: i64 = 24;
+// Minimum time between two emails of the same kind to one account
+const RESEND_INTERVAL_SECS: i64 = 60;
+
+/// Creates a single-use token for `purpose` and returns it in plain text
+async fn issue_token(
+    state: &AppState,
+    user: &User,
+    purpose: &str,
+    new_email: Option<&str>,
+    ttl: Duration,
+) -> Result<String, anyhow::Error> {
+    let token = generate_opaque_token();
+    EmailTokenRepository::create(&state.db, &user.id, purpose, &hash_token(&token), new_email, Utc::now() + ttl)
+        .await?;
+    Ok(token)
+}
+
+async fn recently_sent(state: &AppState, user: &User, purpose: &str) -> Result<bool, anyhow::Error> {
+    let latest = EmailTokenRepository::find_latest(&state.db, &user.id, purpose).await?;
+    Ok(latest
+        .map(|token| token.created_at > Utc::now() - Duration::seconds(RESEND_INTERVAL_SECS))
+        .unwrap_or(false))
+}
+
+pub async fn send_verification_email(state: &AppState, user: &User) -> Result<(), anyhow::Error> {
+    let token = issue_token(state, user, EMAIL_TOKEN_VERIFY, None, Duration::hours(VERIFY_TOKEN_HOURS)).await?;
+    let link = format!("{}/verify-email?token={}", state.config.app_base_url, token);
+
+    state
+        .mailer
+        .send(&Email {
+            to: user.email.clone(),
+            subject: "Verify your NexusDB email address".to_string(),
+            body: format!(
+                "Hi {},\n\nConfirm this email address for your NexusDB account by opening this link:\n\n{}\n\nThe link expires in {} hours. If you didn't create an account, ignore this email.\n",
+                user.username, link, VERIFY_TOKEN_HOURS
+            ),
+        })
+        .await
+}
+
+async fn send_reset_email(state: &AppState, user: &User) -> Result<(), anyhow::Error> {
+    let token = issue_token(state, user, EMAIL_TOKEN_RESET, None, Duration::minutes(RESET_TOKEN_MINUTES)).await?;
+    let link = format!("{}/reset-password?token={}", state.config.app_base_url, token);
+
+    state
+        .mailer
+        .send(&Email {
+            to: user.email.clone(),
+            subject: "Reset your NexusDB password".to_string(),
+            body: format!(
+                "Hi {},\n\nSomeone asked to reset the password of your NexusDB account. Choose a new one here:\n\n{}\n\nThe link works once and expires in {} minutes. If it wasn't you, ignore this email; your password stays the same.\n",
+                user.username, link, RESET_TOKEN_MINUTES
+            ),
+        })
+        .await
+}
+
+/// Sends the confirmation link for an email change to the new address. The
+/// account keeps its current address until the link is opened.
+async fn send_email_change(state: &AppState, user: &User, new_email: &str) -> Result<(), anyhow::Error> {
+    let token = issue_token(
+        state,
+        user,
+        EMAIL_TOKEN_CHANGE,
+        Some(new_email),
+        Duration::hours(CHANGE_TOKEN_HOURS),
+    )
+    .await?;
+    let link = format!("{}/confirm-email?token={}", state.config.app_base_url, token);
+
+    state
+        .mailer
+        .send(&Email {
+            to: new_email.to_string(),
+            subject: "Confirm your new NexusDB email address".to_string(),
+            body: format!(
+                "Hi {},\n\nConfirm that this is the new email address of your NexusDB account by opening this link:\n\n{}\n\nThe link expires in {} hours. If you didn't ask for this change, ignore this email.\n",
+                user.username, link, CHANGE_TOKEN_HOURS
+            ),
+        })
+        .await
+}
+
+/// Confirms the address with the token from the verification email
+pub async fn verify_email(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<EmailTokenRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let token = EmailTokenRepository::consume(&state.db, &hash_token(&req.token), EMAIL_TOKEN_VERIFY)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Invalid or expired token".to_string()))?;
+
+    UserRepository::mark_email_verified(&state.db, &token.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Starts an email change: needs the password and sends a confirmation link
+/// to the new address
+pub async fn request_email_change(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: AuthUser,
+    Json(req): Json<ChangeEmailRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    req.validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+
+    let user = UserRepository::find_by_id(&state.db, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    check_password(&state, &user, &req.password, &addr.ip().to_string()).await?;
+
+    let new_email = req.new_email.trim();
+    if new_email.eq_ignore_ascii_case(&user.email) {
+        return Err((StatusCode::BAD_REQUEST, "That is already your email address".to_string()));
+    }
+    let taken = UserRepository::find_by_email(&state.db, new_email)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !taken.is_empty() {
+        return Err((StatusCode::CONFLICT, "Email address is already in use".to_string()));
+    }
+
+    let throttled = recently_sent(&state, &user, EMAIL_TOKEN_CHANGE)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if throttled {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("Wait {} seconds before asking for another email", RESEND_INTERVAL_SECS),
+        ));
+    }
+
+    send_email_change(&state, &user, new_email).await.map_err(|e| {
+        tracing::error!("Failed to send email change confirmation to {}: {}", new_email, e);
+        (StatusCode::BAD_GATEWAY, "Could not send the email".to_string())
+    })?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Switches the account to the new address with the token from the email
+/// change link. The old address gets a notice afterwards.
+pub async fn confirm_email_change(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<EmailTokenRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let token = EmailTokenRepository::consume(&state.db, &hash_token(&req.token), EMAIL_TOKEN_CHANGE)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Invalid or expired token".to_string()))?;
+    let new_email = token
+        .new_email
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Invalid or expired token".to_string()))?;
+
+    let old = UserRepository::find_by_id(&state.db, &token.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Invalid or expired token".to_string()))?;
+
+    UserRepository::change_email(&state.db, &old.id, &new_email)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::CONFLICT, "Email address is already in use".to_string()))?;
+
+    let notice = Email {
+        to: old.email.clone(),
+        subject: "Your NexusDB email address was changed".to_string(),
+        body: format!(
+            "Hi {},\n\nThe email address of your NexusDB account was changed to {}. If you didn't do this, reset your password and contact an administrator.\n",
+            old.username, new_email
+        ),
+    };
+    if let Err(e) = state.mailer.send(&notice).await {
+        tracing::error!("Failed to send email change notice to {}: {}", old.email, e);
+    }
+
+    tracing::info!("Email address of {} changed", old.username);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Sends a new verification email; the previous link stops working
+pub async fn resend_verification(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = UserRepository::find_by_id(&state.db, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    if user.email_verified_at.is_some() {
+        return Err((StatusCode::CONFLICT, "Email address is already verified".to_string()));
+    }
+
+    let throttled = recently_sent(&state, &user, EMAIL_TOKEN_VERIFY)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if throttled {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("Wait {} seconds before asking for another email", RESEND_INTERVAL_SECS),
+        ));
+    }
+
+    send_verification_email(&state, &user).await.map_err(|e| {
+        tracing::error!("Failed to send verification email to {}: {}", user.email, e);
+        (StatusCode::BAD_GATEWAY, "Could not send the email".to_string())
+    })?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Emails a reset link to the accounts using this address. The answer is
+/// always 202, whether or not the address is known, and the email is sent in
+/// the background so response times don't tell either.
+pub async fn forgot_password(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ForgotPasswordRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let users = UserRepository::find_by_email(&state.db, req.email.trim())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tokio::spawn(async move {
+        for user in users.iter().filter(|user| user.disabled_at.is_none()) {
+            match recently_sent(&state, user, EMAIL_TOKEN_RESET).await {
+                Ok(false) => {}
+                Ok(true) => continue,
+                Err(e) => {
+                    tracing::error!("Failed to check reset emails for {}: {}", user.username, e);
+                    continue;
+                }
+            }
+            if let Err(e) = send_reset_email(&state, user).await {
+                tracing::error!("Failed to send password reset email to {}: {}", user.email, e);
+            }
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Sets a new password with the token from the reset email. Every session of
+/// the account is revoked, so a stolen refresh token stops working too.
+pub async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let invalid = || (StatusCode::BAD_REQUEST, "Invalid or expired token".to_string());
+    let token_hash = hash_token(&req.token);
+
+    // The token is only spent once the new password is accepted
+    let token = EmailTokenRepository::find_valid(&state.db, &token_hash, EMAIL_TOKEN_RESET)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(invalid)?;
+
+    let user = UserRepository::find_by_id(&state.db, &token.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(invalid)?;
+
+    enforce_password_policy(&state, &req.new_password, &user.username, &user.email).await?;
+
+    EmailTokenRepository::consume(&state.db, &token_hash, EMAIL_TOKEN_RESET)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(invalid)?;
+
+    let password_hash = hash_password(&req.new_password)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    UserRepository::reset_password(&state.db, &user.id, &password_hash)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Failed logins with the old password shouldn't lock out the new one
+    BruteForceProtection::clear_attempts(ENTITY_USER, &user.username);
+
+    tracing::info!("Password reset for {}", user.username);
+    Ok(StatusCode::NO_CONTENT)
+}
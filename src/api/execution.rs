@@ -1,39 +1,230 @@
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     Json,
 };
+use chrono::Utc;
 use std::sync::Arc;
+use std::time::Instant;
 
+use crate::api::policies::connection_policy;
 use crate::api::AppState;
-use crate::models::{ExecuteQueryRequest, QueryResponse};
+use crate::api::workspaces::connection_for;
+use crate::db::repository::QueryExecutionRepository;
+use crate::drivers::{DriverInfo, DriverSession, QueryLanguage, QueryOutput, SessionLimits};
+use crate::models::{ExecuteQueryRequest, ExecutionPolicy, QueryResponse, QuerySummary, WorkspaceRole};
 use crate::security::auth::AuthUser;
+use crate::security::policy;
+use crate::security::tokens::SCOPE_QUERY_EXECUTE;
+use crate::security::validation::analyze;
 
 pub async fn execute_query(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
     Json(req): Json<ExecuteQueryRequest>,
 ) -> Result<Json<QueryResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_QUERY_EXECUTE)?;
+
+    let response = run_query(
+        &state,
+        &auth_user,
+        &req.connection_id,
+        &req.query,
+        req.execution_id.as_deref(),
+        false,
+    )
+    .await?;
+    Ok(Json(response))
+}
+
+/// Runs a query on a connection the user may run queries on and records it in the history.
+/// `approved` is only set for the run of an approved change request.
+pub(crate) async fn run_query(
+    state: &AppState,
+    auth_user: &AuthUser,
+    connection_id: &str,
+    query: &str,
+    execution_id: Option<&str>,
+    approved: bool,
+) -> Result<QueryResponse, (StatusCode, String)> {
     tracing::info!(
-        "User {} executing query on connection {}: {}",
+        "User {} ({}) executing query on connection {}",
+        auth_user.username,
         auth_user.user_id,
-        req.connection_id,
-        req.query
+        connection_id
     );
 
-    // In a real implementation, we would:
-    // 1. Get connection details from DB
-    // 2. Connect to the specific DB (Redis, Mongo, SQL)
-    // 3. Execute query
-    // 4. Return results
-
-    // For now, we just log it and return a simulation acknowledgement
-    // The frontend handles the actual simulation logic for now.
-
-    Ok(Json(QueryResponse {
-        columns: vec!["info".to_string()],
-        rows: vec![serde_json::json!({"info": "Query logged in backend"})],
-        execution_time_ms: 0,
-        rows_count: 0,
-    }))
+    let conn = connection_for(state, auth_user, connection_id, WorkspaceRole::Runner).await?;
+
+    let driver = state.drivers.get(&conn.db_type).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported database type: {}", conn.db_type),
+        )
+    })?;
+
+    // The connection's policy is checked before anything reaches the server
+    let info = driver.info();
+    let policy = connection_policy(state, &conn.id).await?;
+    policy::check_hours(&policy, Utc::now()).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+    let summary = check_query(&policy, &info, query)?;
+    if !approved {
+        policy::check_approval(&policy, &summary.classes).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+    }
+    let limits = policy::session_limits(&policy);
+
+    let password = state
+        .encryption_service
+        .decrypt_credentials(&conn.encrypted_password)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let started = Instant::now();
+    let result = match driver.connect(&conn, &password).await {
+        Ok(mut session) => {
+            let result = run_in_session(state, auth_user, session.as_mut(), query, execution_id, &limits).await;
+            session.close().await;
+            result
+        }
+        Err(e) => Err(e),
+    };
+    let execution_time_ms = started.elapsed().as_millis() as i64;
+
+    // Record the run in the history whether it succeeded or not
+    let (rows_affected, error_message) = match &result {
+        Ok(output) => (output.rows_affected.map(|n| n as i64), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    if let Err(e) = QueryExecutionRepository::create(
+        &state.db,
+        &auth_user.user_id,
+        &conn.id,
+        query,
+        execution_time_ms,
+        rows_affected,
+        result.is_ok(),
+        error_message.as_deref(),
+    )
+    .await
+    {
+        tracing::error!("Failed to record query execution: {}", e);
+    }
+
+    let mut output = result.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let mut truncated = false;
+    if let Some(max_rows) = policy.max_rows.map(|rows| rows as usize) {
+        if output.rows.len() > max_rows {
+            output.rows.truncate(max_rows);
+            if let Some(documents) = output.documents.as_mut() {
+                documents.truncate(max_rows);
+            }
+            truncated = true;
+        }
+    }
+    let rows_count = output.rows.len();
+
+    Ok(QueryResponse {
+        columns: output.columns,
+        rows: output.rows,
+        execution_time_ms,
+        rows_count,
+        documents: output.documents,
+        truncated,
+        findings: summary.findings,
+    })
+}
+
+/// Classifies the query's statements and checks them against the connection's
+/// policy, before anything reaches the server
+pub(crate) fn check_query(
+    policy: &ExecutionPolicy,
+    info: &DriverInfo,
+    query: &str,
+) -> Result<QuerySummary, (StatusCode, String)> {
+    let summary = match info.query_language {
+        QueryLanguage::Sql => {
+            let analysis = analyze(info.name, query).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+            policy::check_findings(policy, &analysis).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+            QuerySummary {
+                classes: analysis.classes(),
+                statements: analysis.statements,
+                findings: analysis.findings,
+            }
+        }
+        QueryLanguage::Redis => QuerySummary {
+            classes: policy::classify_redis(query),
+            statements: Vec::new(),
+            findings: Vec::new(),
+        },
+        QueryLanguage::Mongo => QuerySummary {
+            classes: policy::classify_mongo(query),
+            statements: Vec::new(),
+            findings: Vec::new(),
+        },
+    };
+    policy::check_statements(policy, &summary.classes).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+    Ok(summary)
+}
+
+/// Applies the policy's session limits, then runs the query under its
+/// statement timeout while it can be cancelled through `execution_id`
+async fn run_in_session(
+    state: &AppState,
+    auth_user: &AuthUser,
+    session: &mut dyn DriverSession,
+    query: &str,
+    execution_id: Option<&str>,
+    limits: &SessionLimits,
+) -> Result<QueryOutput, anyhow::Error> {
+    session.restrict(limits).await?;
+
+    let cancel_token = session.cancel_token();
+    // Dropped with this future, so the entry goes away even if the client
+    // disconnects before the query ends
+    let _registration = match (execution_id, cancel_token.clone()) {
+        (Some(execution_id), Some(token)) => Some(
+            state
+                .running_queries
+                .register(execution_id, &auth_user.user_id, token)
+                .ok_or_else(|| anyhow::anyhow!("A query with execution id {} is already running", execution_id))?,
+        ),
+        _ => None,
+    };
+
+    let result = match limits.statement_timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, session.execute(query)).await {
+            Ok(result) => result,
+            Err(_) => {
+                if let Some(token) = &cancel_token {
+                    let _ = token.cancel().await;
+                }
+                Err(anyhow::anyhow!(
+                    "Query exceeded the statement timeout of {} seconds",
+                    timeout.as_secs()
+                ))
+            }
+        },
+        None => session.execute(query).await,
+    };
+
+    result
+}
+
+pub async fn cancel_query(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(execution_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_QUERY_EXECUTE)?;
+
+    let token = state
+        .running_queries
+        .get(&execution_id, &auth_user.user_id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "No running query with that id".to_string()))?;
+
+    token
+        .cancel()
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    Ok(StatusCode::ACCEPTED)
 }
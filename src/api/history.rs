@@ -0,0 +1,102 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use std::sync::Arc;
+
+use crate::api::execution::run_query;
+use crate::api::{page_offset, AppState};
+use crate::db::repository::QueryExecutionRepository;
+use crate::models::{HistoryEntry, HistoryPage, HistoryQuery, QueryResponse};
+use crate::security::auth::AuthUser;
+use crate::security::tokens::{SCOPE_HISTORY_READ, SCOPE_QUERY_EXECUTE};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+pub async fn list_history(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Query(filter): Query<HistoryQuery>,
+) -> Result<Json<HistoryPage>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_HISTORY_READ)?;
+
+    // Tokens limited to some connections must ask for one of them
+    if auth_user.connection_ids.is_some() {
+        match filter.connection_id.as_deref() {
+            Some(connection_id) => auth_user.require_connection(connection_id)?,
+            None => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "connection_id is required for tokens restricted to specific connections".to_string(),
+                ));
+            }
+        }
+    }
+
+    let page = filter.page.unwrap_or(1).max(1);
+    let per_page = filter.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    let (items, total) = QueryExecutionRepository::search(
+        &state.db,
+        &auth_user.user_id,
+        &filter,
+        per_page,
+        page_offset(page, per_page)?,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(HistoryPage {
+        items,
+        total,
+        page,
+        per_page,
+    }))
+}
+
+pub async fn get_history_entry(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<HistoryEntry>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_HISTORY_READ)?;
+
+    let entry = QueryExecutionRepository::find_by_id(&state.db, &id, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "History entry not found".to_string()))?;
+
+    if !auth_user.can_access_connection(&entry.execution.connection_id) {
+        return Err((StatusCode::NOT_FOUND, "History entry not found".to_string()));
+    }
+
+    Ok(Json(entry))
+}
+
+/// Runs the query of a history entry again on the same connection. The new
+/// run is recorded as a history entry of its own.
+pub async fn rerun_history_entry(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<QueryResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_QUERY_EXECUTE)?;
+
+    let entry = QueryExecutionRepository::find_by_id(&state.db, &id, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "History entry not found".to_string()))?;
+
+    let response = run_query(
+        &state,
+        &auth_user,
+        &entry.execution.connection_id,
+        &entry.execution.query,
+        None,
+        false,
+    )
+    .await?;
+    Ok(Json(response))
+}
@@ -0,0 +1,275 @@
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, Utc};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::api::workspaces::{require_manage, workspace_for};
+use crate::api::AppState;
+use crate::db::repository::{
+    AuditRepository, InvitationAcceptance, InvitationRepository, UserRepository, WorkspaceRepository,
+};
+use crate::mailer::Email;
+use crate::models::{
+    CreateInvitationRequest, InvitationCreated, InvitationPreview, InvitationTokenRequest, NewAuditEvent,
+    WorkspaceInvitation, WorkspaceResponse, WorkspaceRole, AUDIT_INVITATION_CREATED, AUDIT_INVITATION_REVOKED,
+};
+use crate::security::auth::AuthUser;
+use crate::security::invitations;
+
+const DEFAULT_INVITATION_HOURS: i64 = 72;
+const MAX_INVITATION_HOURS: i64 = 30 * 24;
+
+fn invalid_invitation() -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, "Invalid or expired invitation".to_string())
+}
+
+/// Checks the signature and loads the invitation if it can still be accepted
+async fn pending_invitation(state: &AppState, token: &str) -> Result<WorkspaceInvitation, (StatusCode, String)> {
+    let id = invitations::verify(&state.config.jwt_secret, token, Utc::now().timestamp())
+        .ok_or_else(invalid_invitation)?;
+
+    let invitation = InvitationRepository::find_by_id(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(invalid_invitation)?;
+
+    if invitation.accepted_at.is_some() || invitation.revoked_at.is_some() || invitation.expires_at <= Utc::now() {
+        return Err(invalid_invitation());
+    }
+    Ok(invitation)
+}
+
+/// Invites by email, or creates a link to hand out when no email is given.
+/// Needs the admin role, and the owner role to invite owners.
+pub async fn create_invitation(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Json(req): Json<CreateInvitationRequest>,
+) -> Result<(StatusCode, Json<InvitationCreated>), (StatusCode, String)> {
+    req.validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+
+    let workspace = workspace_for(&state, &auth_user, &id, WorkspaceRole::Admin).await?;
+    if workspace.workspace.personal_user_id.is_some() {
+        return Err((StatusCode::BAD_REQUEST, "The personal workspace can't be shared".to_string()));
+    }
+    require_manage(workspace.role, req.role)?;
+
+    let hours = req.expires_in_hours.unwrap_or(DEFAULT_INVITATION_HOURS);
+    if !(1..=MAX_INVITATION_HOURS).contains(&hours) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("expires_in_hours must be between 1 and {}", MAX_INVITATION_HOURS),
+        ));
+    }
+
+    let email = req.email.as_deref().map(str::trim);
+    if let Some(email) = email {
+        let member = WorkspaceRepository::has_member_with_email(&state.db, &id, email)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if member {
+            return Err((StatusCode::CONFLICT, "User is already a member".to_string()));
+        }
+    }
+
+    let invitation = InvitationRepository::create(
+        &state.db,
+        &id,
+        email,
+        req.role,
+        &auth_user.user_id,
+        Utc::now() + Duration::hours(hours),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let token = invitations::sign(&state.config.jwt_secret, &invitation.id, invitation.expires_at.timestamp());
+    let link = format!("{}/invite?token={}", state.config.app_base_url, token);
+
+    if let Some(email) = email {
+        let sent = state
+            .mailer
+            .send(&Email {
+                to: email.to_string(),
+                subject: format!("You're invited to {} on NexusDB", workspace.workspace.name),
+                body: format!(
+                    "Hi,\n\n{} invited you to join the {} workspace on NexusDB as {}. Accept the invitation here:\n\n{}\n\nIf you don't have an account yet, sign up with this email address first. The link works once and expires in {} hours.\n",
+                    auth_user.username,
+                    workspace.workspace.name,
+                    req.role.as_str(),
+                    link,
+                    hours
+                ),
+            })
+            .await;
+
+        // An invitation nobody received shouldn't stay pending
+        if let Err(e) = sent {
+            tracing::error!("Failed to send invitation to {}: {}", email, e);
+            let _ = InvitationRepository::revoke(&state.db, &id, &invitation.id).await;
+            return Err((StatusCode::BAD_GATEWAY, "Failed to send the invitation email".to_string()));
+        }
+    }
+
+    let ip = addr.ip().to_string();
+    AuditRepository::record(
+        &state.db,
+        &id,
+        AUDIT_INVITATION_CREATED,
+        &NewAuditEvent {
+            actor_id: Some(&auth_user.user_id),
+            role: Some(invitation.role),
+            invitation_id: Some(&invitation.id),
+            ip_address: Some(&ip),
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("{} invited {} to workspace {}", auth_user.username, email.unwrap_or("a link holder"), id);
+    Ok((StatusCode::CREATED, Json(InvitationCreated { invitation, token, link })))
+}
+
+/// Invitations that can still be accepted
+pub async fn list_invitations(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<WorkspaceInvitation>>, (StatusCode, String)> {
+    workspace_for(&state, &auth_user, &id, WorkspaceRole::Admin).await?;
+
+    let invitations = InvitationRepository::find_pending(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(invitations))
+}
+
+pub async fn revoke_invitation(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: AuthUser,
+    Path((id, invitation_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let workspace = workspace_for(&state, &auth_user, &id, WorkspaceRole::Admin).await?;
+
+    let invitation = InvitationRepository::find_by_id(&state.db, &invitation_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .filter(|invitation| invitation.workspace_id == id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Invitation not found".to_string()))?;
+    require_manage(workspace.role, invitation.role)?;
+
+    InvitationRepository::revoke(&state.db, &id, &invitation_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Invitation is no longer pending".to_string()))?;
+
+    let ip = addr.ip().to_string();
+    AuditRepository::record(
+        &state.db,
+        &id,
+        AUDIT_INVITATION_REVOKED,
+        &NewAuditEvent {
+            actor_id: Some(&auth_user.user_id),
+            role: Some(invitation.role),
+            invitation_id: Some(&invitation.id),
+            ip_address: Some(&ip),
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Public: what the link offers, so the frontend can show it before the
+/// invitee signs in or registers
+pub async fn preview_invitation(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<InvitationTokenRequest>,
+) -> Result<Json<InvitationPreview>, (StatusCode, String)> {
+    let invitation = pending_invitation(&state, &req.token).await?;
+
+    let workspace = WorkspaceRepository::find_by_id(&state.db, &invitation.workspace_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(invalid_invitation)?;
+    let invited_by = match invitation.invited_by.as_deref() {
+        Some(user_id) => UserRepository::find_by_id(&state.db, user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map(|user| user.username),
+        None => None,
+    };
+
+    Ok(Json(InvitationPreview {
+        workspace_name: workspace.name,
+        role: invitation.role,
+        email: invitation.email,
+        invited_by,
+        expires_at: invitation.expires_at,
+    }))
+}
+
+/// Joins the workspace with the invitation's role. Email invitations only
+/// work for the account using that address.
+pub async fn accept_invitation(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: AuthUser,
+    Json(req): Json<InvitationTokenRequest>,
+) -> Result<Json<WorkspaceResponse>, (StatusCode, String)> {
+    let invitation = pending_invitation(&state, &req.token).await?;
+
+    if let Some(email) = invitation.email.as_deref() {
+        let user = UserRepository::find_by_id(&state.db, &auth_user.user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+        if !user.email.eq_ignore_ascii_case(email) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "This invitation was sent to a different email address".to_string(),
+            ));
+        }
+        if state.config.require_email_verification && user.email_verified_at.is_none() {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Verify your email address to accept this invitation".to_string(),
+            ));
+        }
+    }
+
+    let accepted = InvitationRepository::accept(&state.db, &invitation.id, &auth_user.user_id, &addr.ip().to_string())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let invitation = match accepted {
+        InvitationAcceptance::Accepted(invitation) => invitation,
+        InvitationAcceptance::AlreadyMember => {
+            return Err((StatusCode::CONFLICT, "You are already a member of this workspace".to_string()));
+        }
+        InvitationAcceptance::Unavailable => return Err(invalid_invitation()),
+    };
+
+    tracing::info!(
+        "{} joined workspace {} as {} through invitation {}",
+        auth_user.username,
+        invitation.workspace_id,
+        invitation.role.as_str(),
+        invitation.id
+    );
+
+    let workspace = workspace_for(&state, &auth_user, &invitation.workspace_id, WorkspaceRole::Viewer).await?;
+    Ok(Json(workspace.into()))
+}
@@ -1,41 +1,82 @@
+pub mod account;
+pub mod admin;
 pub mod auth;
+pub mod change_requests;
 pub mod connections;
+pub mod drivers;
+pub mod email;
+pub mod execution;
+pub mod history;
+pub mod invitations;
+pub mod mongo;
+pub mod policies;
+pub mod pow;
+pub mod precondition;
+pub mod redis_keys;
+pub mod schema;
 pub mod scripts;
+pub mod tokens;
+pub mod workspaces;
 pub mod health;
 
-use axum::{
-    routing::{get, post},
-    Router,
-};
+use axum::http::StatusCode;
+use std::future::Future;
 use std::sync::Arc;
 
 use crate::config::Config;
 use crate::db::DbPool;
+use crate::drivers::{DriverRegistry, RunningQueries};
+use crate::mailer::Mailer;
+use crate::security::password_policy::PasswordPolicy;
 use crate::security::auth::AuthService;
 use crate::security::encryption::EncryptionService;
 
 pub struct AppState {
+    pub config: Arc<Config>,
     pub db: DbPool,
     pub auth_service: Arc<AuthService>,
     pub encryption_service: Arc<EncryptionService>,
+    pub drivers: Arc<DriverRegistry>,
+    pub running_queries: RunningQueries,
+    pub mailer: Arc<dyn Mailer>,
+    pub password_policy: PasswordPolicy,
 }
 
-pub fn create_router(state: Arc<AppState>) -> Router {
-    Router::new()
-        // Health check (no auth required)
-        .route("/health", get(health::health_check))
-        // Auth routes
-        .route("/api/auth/register", post(auth::register))
-        .route("/api/auth/login", post(auth::login))
-        .route("/api/auth/me", get(auth::get_current_user))
-        // Connection routes (auth required)
-        .route("/api/connections", post(connections::create_connection))
-        .route("/api/connections", get(connections::list_connections))
-        .route("/api/connections/:id", get(connections::get_connection))
-        .route("/api/connections/:id", axum::routing::delete(connections::delete_connection))
-        // Script routes (auth required)
-        .route("/api/scripts", post(scripts::create_script))
-        .route("/api/scripts", get(scripts::list_scripts))
-        .route("/api/scripts/:id", axum::routing::delete(scripts::delete_script))
-        .with_state(state)
+/// Rows to skip for a 1-based page, refusing pages too far out to count
+pub fn page_offset(page: i64, per_page: i64) -> Result<i64, (StatusCode, String)> {
+    (page - 1)
+        .checked_mul(per_page)
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "page is out of range".to_string()))
+}
+
+/// Runs `work` on its own task, so it still completes if the client
+/// disconnects and the handler's future is dropped
+pub async fn detached<T, F>(work: F) -> Result<T, (StatusCode, String)>
+where
+    F: Future<Output = Result<T, (StatusCode, String)>> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::spawn(work)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_detached_work_outlives_the_caller() {
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+        let handler = detached(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let _ = done_tx.send(());
+            Ok(())
+        });
+
+        // The client goes away while the work is still running
+        assert!(tokio::time::timeout(Duration::from_millis(10), handler).await.is_err());
+        assert!(tokio::time::timeout(Duration::from_secs(5), done_rx).await.unwrap().is_ok());
+    }
 }
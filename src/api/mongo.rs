@@ -0,0 +1,80 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use std::sync::Arc;
+
+use crate::api::AppState;
+use crate::api::workspaces::connection_for;
+use crate::drivers::mongodb as mongo_driver;
+use crate::models::{Connection, WorkspaceRole};
+use crate::security::auth::AuthUser;
+use crate::security::tokens::SCOPE_CONNECTIONS_READ;
+
+async fn load_mongo_connection(
+    state: &AppState,
+    auth_user: &AuthUser,
+    id: &str,
+) -> Result<(Connection, String), (StatusCode, String)> {
+    let conn = connection_for(state, auth_user, id, WorkspaceRole::Viewer).await?;
+
+    if conn.db_type != "mongodb" {
+        return Err((StatusCode::BAD_REQUEST, "Connection is not a MongoDB connection".to_string()));
+    }
+
+    let password = state
+        .encryption_service
+        .decrypt_credentials(&conn.encrypted_password)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok((conn, password))
+}
+
+pub async fn list_databases(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let (conn, password) = load_mongo_connection(&state, &auth_user, &id).await?;
+
+    let databases = mongo_driver::list_databases(&conn, &password)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    Ok(Json(databases))
+}
+
+pub async fn list_collections(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((id, database)): Path<(String, String)>,
+) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let (conn, password) = load_mongo_connection(&state, &auth_user, &id).await?;
+
+    let collections = mongo_driver::list_collections(&conn, &password, &database)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    Ok(Json(collections))
+}
+
+pub async fn list_indexes(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((id, database, collection)): Path<(String, String, String)>,
+) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let (conn, password) = load_mongo_connection(&state, &auth_user, &id).await?;
+
+    let indexes = mongo_driver::list_indexes(&conn, &password, &database, &collection)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    Ok(Json(indexes))
+}
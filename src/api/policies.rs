@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use std::sync::Arc;
+
+use crate::api::workspaces::connection_for;
+use crate::api::AppState;
+use crate::db::repository::ConnectionPolicyRepository;
+use crate::models::{ConnectionPolicyResponse, ExecutionPolicy, StatementClass, WorkspaceRole};
+use crate::security::auth::AuthUser;
+use crate::security::policy;
+use crate::security::tokens::{SCOPE_CONNECTIONS_READ, SCOPE_CONNECTIONS_WRITE};
+
+/// Policy queries on the connection go through; the default one allows everything
+pub(crate) async fn connection_policy(
+    state: &AppState,
+    connection_id: &str,
+) -> Result<ExecutionPolicy, (StatusCode, String)> {
+    let policy = ConnectionPolicyRepository::find(&state.db, connection_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(policy.map(Into::into).unwrap_or_default())
+}
+
+pub async fn get_policy(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<ConnectionPolicyResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let conn = connection_for(&state, &auth_user, &id, WorkspaceRole::Viewer).await?;
+
+    let stored = ConnectionPolicyRepository::find(&state.db, &conn.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let response = match stored {
+        Some(row) => ConnectionPolicyResponse {
+            connection_id: row.connection_id.clone(),
+            updated_by: row.updated_by.clone(),
+            updated_at: Some(row.updated_at),
+            policy: row.into(),
+        },
+        None => ConnectionPolicyResponse {
+            connection_id: conn.id,
+            policy: ExecutionPolicy::default(),
+            updated_by: None,
+            updated_at: None,
+        },
+    };
+    Ok(Json(response))
+}
+
+/// Replaces the connection's policy. Needs the admin role in its workspace.
+pub async fn update_policy(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Json(mut req): Json<ExecutionPolicy>,
+) -> Result<Json<ConnectionPolicyResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_WRITE)?;
+    // A token limited to this connection shouldn't be able to loosen its limits
+    if auth_user.connection_ids.is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Token is restricted to specific connections".to_string(),
+        ));
+    }
+
+    let conn = connection_for(&state, &auth_user, &id, WorkspaceRole::Admin).await?;
+
+    policy::validate(&req).map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+    req.allowed_statements = StatementClass::ALL
+        .into_iter()
+        .filter(|class| req.allowed_statements.contains(class))
+        .collect();
+
+    let row = ConnectionPolicyRepository::upsert(&state.db, &conn.id, &req, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("{} updated the execution policy of connection {}", auth_user.username, conn.id);
+    Ok(Json(ConnectionPolicyResponse {
+        connection_id: row.connection_id.clone(),
+        updated_by: row.updated_by.clone(),
+        updated_at: Some(row.updated_at),
+        policy: row.into(),
+    }))
+}
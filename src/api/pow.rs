@@ -0,0 +1,28 @@
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::AppState;
+use crate::security::pow::{Challenge, PoWService};
+
+pub async fn get_challenge(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Json<Challenge> {
+    let ip = addr.ip().to_string();
+    let difficulty = PoWService::difficulty_for(
+        &ip,
+        state.config.pow_base_difficulty,
+        state.config.pow_max_difficulty,
+    );
+
+    Json(PoWService::generate_challenge(
+        &ip,
+        difficulty,
+        Duration::from_secs(state.config.pow_challenge_ttl_secs),
+    ))
+}
@@ -0,0 +1,75 @@
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use chrono::{DateTime, Utc};
+
+/// ETag for a resource version; the quoted `updated_at` timestamp
+pub fn etag(updated_at: &DateTime<Utc>) -> String {
+    format!("\"{}\"", updated_at.to_rfc3339())
+}
+
+pub fn etag_header(updated_at: &DateTime<Utc>) -> [(header::HeaderName, String); 1] {
+    [(header::ETAG, etag(updated_at))]
+}
+
+/// Version the client expects to modify, taken from `If-Match` or from the
+/// `updated_at` sent in the body. Updates without one are rejected so that
+/// concurrent editors cannot silently overwrite each other.
+pub fn expected_version(
+    headers: &HeaderMap,
+    body_updated_at: Option<DateTime<Utc>>,
+) -> Result<DateTime<Utc>, (StatusCode, String)> {
+    if let Some(value) = headers.get(header::IF_MATCH) {
+        return parse_if_match(value).ok_or_else(|| {
+            (
+                StatusCode::PRECONDITION_FAILED,
+                "If-Match does not match the current version".to_string(),
+            )
+        });
+    }
+
+    body_updated_at.ok_or_else(|| {
+        (
+            StatusCode::PRECONDITION_REQUIRED,
+            "Send If-Match with the resource ETag or the current updated_at".to_string(),
+        )
+    })
+}
+
+fn parse_if_match(value: &HeaderValue) -> Option<DateTime<Utc>> {
+    let value = value.to_str().ok()?.trim();
+    let value = value.strip_prefix("W/").unwrap_or(value);
+    let value = value.strip_prefix('"')?.strip_suffix('"')?;
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+pub fn version_conflict() -> (StatusCode, String) {
+    (
+        StatusCode::PRECONDITION_FAILED,
+        "The resource was modified by someone else, reload it and try again".to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_etag_roundtrip() {
+        let now = Utc::now();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, etag(&now).parse().unwrap());
+        assert_eq!(expected_version(&headers, None).unwrap(), now);
+
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("\"garbage\""));
+        assert_eq!(
+            expected_version(&headers, Some(now)).unwrap_err().0,
+            StatusCode::PRECONDITION_FAILED
+        );
+
+        assert_eq!(
+            expected_version(&HeaderMap::new(), None).unwrap_err().0,
+            StatusCode::PRECONDITION_REQUIRED
+        );
+    }
+}
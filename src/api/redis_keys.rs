@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use redis::aio::MultiplexedConnection;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::api::policies::connection_policy;
+use crate::api::AppState;
+use crate::api::workspaces::connection_for;
+use crate::drivers::redis::{self as redis_driver, KeyValue, KeyWrite};
+use crate::models::{StatementClass, WorkspaceRole};
+use crate::security::auth::AuthUser;
+use crate::security::policy;
+use crate::security::tokens::{SCOPE_CONNECTIONS_READ, SCOPE_QUERY_EXECUTE};
+
+const DEFAULT_SCAN_COUNT: usize = 100;
+const DEFAULT_VALUE_LIMIT: usize = 1000;
+const MAX_VALUE_LIMIT: usize = 10_000;
+
+#[derive(Debug, Deserialize)]
+pub struct ScanKeysQuery {
+    pub pattern: Option<String>,
+    pub cursor: Option<u64>,
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KeyQuery {
+    pub key: String,
+    pub limit: Option<usize>,
+}
+
+/// Connects for a key operation of the given class, once the connection's
+/// policy allows it
+async fn open_redis(
+    state: &AppState,
+    auth_user: &AuthUser,
+    id: &str,
+    required: WorkspaceRole,
+    class: StatementClass,
+) -> Result<MultiplexedConnection, (StatusCode, String)> {
+    let conn = connection_for(state, auth_user, id, required).await?;
+
+    if conn.db_type != "redis" {
+        return Err((StatusCode::BAD_REQUEST, "Connection is not a Redis connection".to_string()));
+    }
+
+    let policy = connection_policy(state, &conn.id).await?;
+    policy::check_hours(&policy, Utc::now()).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+    policy::check_statements(&policy, &[class]).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+    policy::check_approval(&policy, &[class]).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+
+    let password = state
+        .encryption_service
+        .decrypt_credentials(&conn.encrypted_password)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    redis_driver::connect(&conn, &password)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))
+}
+
+pub async fn scan_keys(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Query(params): Query<ScanKeysQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let mut redis = open_redis(&state, &auth_user, &id, WorkspaceRole::Viewer, StatementClass::Select).await?;
+
+    let pattern = params.pattern.unwrap_or_else(|| "*".to_string());
+    let count = params.count.unwrap_or(DEFAULT_SCAN_COUNT).clamp(1, MAX_VALUE_LIMIT);
+
+    let (cursor, keys) = redis_driver::scan_keys(&mut redis, params.cursor.unwrap_or(0), &pattern, count)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    Ok(Json(serde_json::json!({
+        // A cursor of 0 means the whole keyspace has been walked
+        "cursor": cursor,
+        "keys": keys,
+    })))
+}
+
+pub async fn get_key(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Query(params): Query<KeyQuery>,
+) -> Result<Json<KeyValue>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_QUERY_EXECUTE)?;
+
+    let mut redis = open_redis(&state, &auth_user, &id, WorkspaceRole::Runner, StatementClass::Select).await?;
+    let limit = params.limit.unwrap_or(DEFAULT_VALUE_LIMIT).clamp(1, MAX_VALUE_LIMIT);
+
+    let value = redis_driver::read_key(&mut redis, &params.key, limit)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Key not found".to_string()))?;
+
+    Ok(Json(value))
+}
+
+pub async fn put_key(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Json(req): Json<KeyWrite>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_QUERY_EXECUTE)?;
+
+    let mut redis = open_redis(&state, &auth_user, &id, WorkspaceRole::Runner, StatementClass::Dml).await?;
+
+    redis_driver::write_key(&mut redis, &req)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_key(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Query(params): Query<KeyQuery>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_QUERY_EXECUTE)?;
+
+    let mut redis = open_redis(&state, &auth_user, &id, WorkspaceRole::Runner, StatementClass::Dml).await?;
+
+    let deleted = redis_driver::delete_key(&mut redis, &params.key)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, "Key not found".to_string()))
+    }
+}
@@ -0,0 +1,187 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::api::AppState;
+use crate::api::workspaces::connection_for;
+use crate::db::repository::SchemaCacheRepository;
+use crate::drivers::{Catalog, TableInfo};
+use crate::models::WorkspaceRole;
+use crate::security::auth::AuthUser;
+use crate::security::tokens::SCOPE_CONNECTIONS_READ;
+
+#[derive(Debug, Serialize)]
+pub struct SchemaResponse {
+    pub connection_id: String,
+    pub refreshed_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub catalog: Catalog,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TablesQuery {
+    pub schema: Option<String>,
+    // table, view, collection, ...
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TableQuery {
+    pub schema: Option<String>,
+}
+
+/// Returns the cached catalog of a connection, introspecting the target
+/// database when there is none yet or when `refresh` is set
+async fn load_catalog(
+    state: &AppState,
+    auth_user: &AuthUser,
+    id: &str,
+    refresh: bool,
+) -> Result<SchemaResponse, (StatusCode, String)> {
+    let conn = connection_for(state, auth_user, id, WorkspaceRole::Viewer).await?;
+
+    if !refresh {
+        let cached = SchemaCacheRepository::find(&state.db, &conn.id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // A cache written by an older layout is simply rebuilt
+        if let Some(cache) = cached {
+            if let Ok(catalog) = serde_json::from_str(&cache.catalog) {
+                return Ok(SchemaResponse {
+                    connection_id: cache.connection_id,
+                    refreshed_at: cache.refreshed_at,
+                    catalog,
+                });
+            }
+        }
+    }
+
+    let driver = state.drivers.get(&conn.db_type).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported database type: {}", conn.db_type),
+        )
+    })?;
+    let password = state
+        .encryption_service
+        .decrypt_credentials(&conn.encrypted_password)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut session = driver
+        .connect(&conn, &password)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+    let catalog = session.introspect().await;
+    session.close().await;
+    let catalog = catalog.map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    let serialized = serde_json::to_string(&catalog)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let cache = SchemaCacheRepository::upsert(&state.db, &conn.id, &serialized)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(SchemaResponse {
+        connection_id: cache.connection_id,
+        refreshed_at: cache.refreshed_at,
+        catalog,
+    })
+}
+
+pub async fn get_schema(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<SchemaResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    Ok(Json(load_catalog(&state, &auth_user, &id, false).await?))
+}
+
+pub async fn refresh_schema(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<SchemaResponse>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    Ok(Json(load_catalog(&state, &auth_user, &id, true).await?))
+}
+
+pub async fn list_databases(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<String>>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let schema = load_catalog(&state, &auth_user, &id, false).await?;
+    Ok(Json(schema.catalog.databases))
+}
+
+pub async fn list_schemas(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<String>>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let schema = load_catalog(&state, &auth_user, &id, false).await?;
+    Ok(Json(schema.catalog.schemas))
+}
+
+pub async fn list_tables(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Query(params): Query<TablesQuery>,
+) -> Result<Json<Vec<TableInfo>>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let schema = load_catalog(&state, &auth_user, &id, false).await?;
+
+    let tables = schema
+        .catalog
+        .tables
+        .into_iter()
+        .filter(|t| params.schema.is_none() || t.schema == params.schema)
+        .filter(|t| match &params.kind {
+            Some(kind) => &t.kind == kind,
+            None => true,
+        })
+        .collect();
+
+    Ok(Json(tables))
+}
+
+pub async fn get_table(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((id, table)): Path<(String, String)>,
+    Query(params): Query<TableQuery>,
+) -> Result<Json<TableInfo>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_CONNECTIONS_READ)?;
+
+    let schema = load_catalog(&state, &auth_user, &id, false).await?;
+
+    let mut matches: Vec<TableInfo> = schema
+        .catalog
+        .tables
+        .into_iter()
+        .filter(|t| t.name == table && (params.schema.is_none() || t.schema == params.schema))
+        .collect();
+
+    match matches.len() {
+        0 => Err((StatusCode::NOT_FOUND, "Table not found".to_string())),
+        1 => Ok(Json(matches.remove(0))),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            "Table name exists in several schemas, pass ?schema=".to_string(),
+        )),
+    }
+}
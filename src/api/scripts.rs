@@ -1,20 +1,38 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
     Json,
 };
+use chrono::{DateTime, Utc};
 use std::sync::Arc;
 use serde::Deserialize;
 
+use crate::api::precondition::{self, etag_header};
+use crate::api::workspaces::{script_for, target_workspace};
 use crate::api::AppState;
 use crate::db::repository::ScriptRepository;
+use crate::models::{WorkspaceFilter, WorkspaceRole};
 use crate::security::auth::AuthUser;
+use crate::security::tokens::{SCOPE_SCRIPTS_READ, SCOPE_SCRIPTS_WRITE};
 
 #[derive(Debug, Deserialize)]
 pub struct CreateScriptRequest {
     pub name: String,
     pub query: String,
     pub db_type: String,
+    // The user's personal workspace when missing
+    pub workspace_id: Option<String>,
+}
+
+/// Partial update, fields left out keep their current value
+#[derive(Debug, Deserialize)]
+pub struct UpdateScriptRequest {
+    pub name: Option<String>,
+    pub query: Option<String>,
+    pub db_type: Option<String>,
+    // Version precondition when If-Match is not sent
+    pub updated_at: Option<DateTime<Utc>>,
 }
 
 pub async fn create_script(
@@ -22,9 +40,14 @@ pub async fn create_script(
     auth_user: AuthUser,
     Json(req): Json<CreateScriptRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_SCRIPTS_WRITE)?;
+    let workspace_id =
+        target_workspace(&state, &auth_user, req.workspace_id.as_deref(), WorkspaceRole::Editor).await?;
+
     let script = ScriptRepository::create(
         &state.db,
         &auth_user.user_id,
+        &workspace_id,
         &req.name,
         &req.query,
         &req.db_type,
@@ -34,30 +57,40 @@ pub async fn create_script(
 
     Ok(Json(serde_json::json!({
         "id": script.id,
+        "workspace_id": script.workspace_id,
         "name": script.name,
         "query": script.query,
         "db_type": script.db_type,
         "created_at": script.created_at,
+        "updated_at": script.updated_at,
     })))
 }
 
+/// Scripts of every workspace the user belongs to, or of `workspace_id`
 pub async fn list_scripts(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
+    Query(filter): Query<WorkspaceFilter>,
 ) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
-    let scripts = ScriptRepository::find_by_user(&state.db, &auth_user.user_id)
+    auth_user.require_scope(SCOPE_SCRIPTS_READ)?;
+
+    let scripts = ScriptRepository::find_accessible(&state.db, &auth_user.user_id, filter.workspace_id.as_deref())
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let response: Vec<_> = scripts
         .into_iter()
-        .map(|script| {
+        .map(|access| {
+            let script = access.script;
             serde_json::json!({
                 "id": script.id,
+                "workspace_id": script.workspace_id,
                 "name": script.name,
                 "query": script.query,
                 "db_type": script.db_type,
                 "created_at": script.created_at,
+                "updated_at": script.updated_at,
+                "role": access.role,
             })
         })
         .collect();
@@ -65,12 +98,54 @@ pub async fn list_scripts(
     Ok(Json(response))
 }
 
+pub async fn update_script(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<UpdateScriptRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth_user.require_scope(SCOPE_SCRIPTS_WRITE)?;
+
+    let expected = precondition::expected_version(&headers, req.updated_at)?;
+
+    let mut script = script_for(&state, &auth_user, &id, WorkspaceRole::Editor).await?;
+
+    if let Some(name) = req.name {
+        script.name = name;
+    }
+    if let Some(query) = req.query {
+        script.query = query;
+    }
+    if let Some(db_type) = req.db_type {
+        script.db_type = db_type;
+    }
+
+    let script = ScriptRepository::update(&state.db, &script, expected)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(precondition::version_conflict)?;
+
+    Ok((etag_header(&script.updated_at), Json(serde_json::json!({
+        "id": script.id,
+        "workspace_id": script.workspace_id,
+        "name": script.name,
+        "query": script.query,
+        "db_type": script.db_type,
+        "created_at": script.created_at,
+        "updated_at": script.updated_at,
+    }))))
+}
+
 pub async fn delete_script(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
     Path(id): Path<String>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    let deleted = ScriptRepository::delete(&state.db, &id, &auth_user.user_id)
+    auth_user.require_scope(SCOPE_SCRIPTS_WRITE)?;
+
+    script_for(&state, &auth_user, &id, WorkspaceRole::Editor).await?;
+    let deleted = ScriptRepository::delete(&state.db, &id)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
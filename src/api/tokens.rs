@@ -0,0 +1,117 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::api::AppState;
+use crate::db::repository::{AccessTokenRepository, ConnectionRepository};
+use crate::models::{AccessTokenResponse, CreateAccessTokenRequest, PersonalAccessToken};
+use crate::security::auth::{hash_token, AuthUser};
+use crate::security::tokens::{generate_personal_token, join_list, split_list, ALL_SCOPES};
+
+pub fn to_response(token: PersonalAccessToken, plain: Option<String>) -> AccessTokenResponse {
+    AccessTokenResponse {
+        id: token.id,
+        name: token.name,
+        scopes: split_list(&token.scopes),
+        connection_ids: token.connection_ids.as_deref().map(split_list),
+        created_at: token.created_at,
+        expires_at: token.expires_at,
+        last_used_at: token.last_used_at,
+        token: plain,
+    }
+}
+
+pub async fn create_token(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateAccessTokenRequest>,
+) -> Result<(StatusCode, Json<AccessTokenResponse>), (StatusCode, String)> {
+    req.validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+
+    if state.config.require_email_verification && !auth_user.email_verified {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Verify your email address to create access tokens".to_string(),
+        ));
+    }
+
+    let mut scopes = req.scopes.clone();
+    scopes.sort();
+    scopes.dedup();
+    if let Some(unknown) = scopes.iter().find(|s| !ALL_SCOPES.contains(&s.as_str())) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown scope '{}', expected one of: {}", unknown, ALL_SCOPES.join(", ")),
+        ));
+    }
+
+    if matches!(req.expires_at, Some(expires_at) if expires_at <= Utc::now()) {
+        return Err((StatusCode::BAD_REQUEST, "expires_at must be in the future".to_string()));
+    }
+
+    let connection_ids = match &req.connection_ids {
+        Some(ids) if ids.is_empty() => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "connection_ids must not be empty; omit it to allow every connection".to_string(),
+            ));
+        }
+        Some(ids) => {
+            for id in ids {
+                ConnectionRepository::find_by_id(&state.db, id, &auth_user.user_id)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+                    .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Connection {} not found", id)))?;
+            }
+            Some(join_list(ids))
+        }
+        None => None,
+    };
+
+    let plain = generate_personal_token();
+    let token = AccessTokenRepository::create(
+        &state.db,
+        &auth_user.user_id,
+        req.name.trim(),
+        &hash_token(&plain),
+        &join_list(&scopes),
+        connection_ids.as_deref(),
+        req.expires_at,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok((StatusCode::CREATED, Json(to_response(token, Some(plain)))))
+}
+
+pub async fn list_tokens(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<Vec<AccessTokenResponse>>, (StatusCode, String)> {
+    let tokens = AccessTokenRepository::find_by_user(&state.db, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(tokens.into_iter().map(|t| to_response(t, None)).collect()))
+}
+
+pub async fn delete_token(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let deleted = AccessTokenRepository::delete(&state.db, &id, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if !deleted {
+        return Err((StatusCode::NOT_FOUND, "Token not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
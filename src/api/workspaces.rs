@@ -0,0 +1,365 @@
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::api::connections::remove_upload_if_unused;
+use crate::api::{page_offset, AppState};
+use crate::db::repository::{
+    AuditRepository, ConnectionRepository, ScriptRepository, UserRepository, WorkspaceRepository,
+};
+use crate::models::{
+    AddMemberRequest, AuditEvent, Connection, NewAuditEvent, Script, UpdateMemberRequest, WorkspaceAccess,
+    WorkspaceMember, WorkspaceRequest, WorkspaceResponse, WorkspaceRole, AUDIT_MEMBER_ADDED,
+    AUDIT_MEMBER_REMOVED, AUDIT_MEMBER_ROLE_CHANGED,
+};
+use crate::security::auth::AuthUser;
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditPage {
+    pub items: Vec<AuditEvent>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+pub fn require_role(role: WorkspaceRole, required: WorkspaceRole) -> Result<(), (StatusCode, String)> {
+    if role < required {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("Requires the {} role in this workspace", required.as_str()),
+        ));
+    }
+    Ok(())
+}
+
+/// Loads a connection the user can reach with at least the `required` role.
+/// Connections of workspaces the user is not a member of are reported as missing.
+pub async fn connection_for(
+    state: &AppState,
+    auth_user: &AuthUser,
+    id: &str,
+    required: WorkspaceRole,
+) -> Result<Connection, (StatusCode, String)> {
+    auth_user.require_connection(id)?;
+    let access = ConnectionRepository::find_by_id(&state.db, id, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Connection not found".to_string()))?;
+
+    require_role(access.role, required)?;
+    Ok(access.connection)
+}
+
+pub async fn script_for(
+    state: &AppState,
+    auth_user: &AuthUser,
+    id: &str,
+    required: WorkspaceRole,
+) -> Result<Script, (StatusCode, String)> {
+    let access = ScriptRepository::find_by_id(&state.db, id, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Script not found".to_string()))?;
+
+    require_role(access.role, required)?;
+    Ok(access.script)
+}
+
+pub async fn workspace_for(
+    state: &AppState,
+    auth_user: &AuthUser,
+    id: &str,
+    required: WorkspaceRole,
+) -> Result<WorkspaceAccess, (StatusCode, String)> {
+    let access = WorkspaceRepository::find_for_member(&state.db, id, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Workspace not found".to_string()))?;
+
+    require_role(access.role, required)?;
+    Ok(access)
+}
+
+/// Workspace new connections or scripts go to: the requested one, where the
+/// user needs the `required` role, or their personal workspace
+pub async fn target_workspace(
+    state: &AppState,
+    auth_user: &AuthUser,
+    workspace_id: Option<&str>,
+    required: WorkspaceRole,
+) -> Result<String, (StatusCode, String)> {
+    match workspace_id {
+        Some(id) => Ok(workspace_for(state, auth_user, id, required).await?.workspace.id),
+        None => WorkspaceRepository::find_personal(&state.db, &auth_user.user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map(|w| w.id)
+            .ok_or_else(|| (StatusCode::INTERNAL_SERVER_ERROR, "Personal workspace is missing".to_string())),
+    }
+}
+
+/// Only owners hand out or take away the owner role; admins manage the rest
+pub fn require_manage(actor: WorkspaceRole, target: WorkspaceRole) -> Result<(), (StatusCode, String)> {
+    require_role(actor, WorkspaceRole::Admin)?;
+    if target == WorkspaceRole::Owner {
+        require_role(actor, WorkspaceRole::Owner)?;
+    }
+    Ok(())
+}
+
+async fn audit(
+    state: &AppState,
+    workspace_id: &str,
+    action: &str,
+    auth_user: &AuthUser,
+    target_user_id: &str,
+    role: WorkspaceRole,
+    addr: &SocketAddr,
+) -> Result<(), (StatusCode, String)> {
+    let ip = addr.ip().to_string();
+    AuditRepository::record(
+        &state.db,
+        workspace_id,
+        action,
+        &NewAuditEvent {
+            actor_id: Some(&auth_user.user_id),
+            target_user_id: Some(target_user_id),
+            role: Some(role),
+            invitation_id: None,
+            ip_address: Some(&ip),
+        },
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+pub async fn list_workspaces(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<Vec<WorkspaceResponse>>, (StatusCode, String)> {
+    let workspaces = WorkspaceRepository::find_by_user(&state.db, &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(workspaces.into_iter().map(WorkspaceResponse::from).collect()))
+}
+
+pub async fn create_workspace(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(req): Json<WorkspaceRequest>,
+) -> Result<(StatusCode, Json<WorkspaceResponse>), (StatusCode, String)> {
+    req.validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+
+    let workspace = WorkspaceRepository::create(&state.db, req.name.trim(), &auth_user.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("{} created workspace {}", auth_user.username, workspace.workspace.id);
+    Ok((StatusCode::CREATED, Json(workspace.into())))
+}
+
+pub async fn get_workspace(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<WorkspaceResponse>, (StatusCode, String)> {
+    let workspace = workspace_for(&state, &auth_user, &id, WorkspaceRole::Viewer).await?;
+    Ok(Json(workspace.into()))
+}
+
+pub async fn update_workspace(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Json(req): Json<WorkspaceRequest>,
+) -> Result<Json<WorkspaceResponse>, (StatusCode, String)> {
+    req.validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Validation error: {}", e)))?;
+
+    workspace_for(&state, &auth_user, &id, WorkspaceRole::Owner).await?;
+    WorkspaceRepository::rename(&state.db, &id, req.name.trim())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let workspace = workspace_for(&state, &auth_user, &id, WorkspaceRole::Owner).await?;
+    Ok(Json(workspace.into()))
+}
+
+/// Deletes a shared workspace with its connections and scripts
+pub async fn delete_workspace(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let workspace = workspace_for(&state, &auth_user, &id, WorkspaceRole::Owner).await?;
+    if workspace.workspace.personal_user_id.is_some() {
+        return Err((StatusCode::BAD_REQUEST, "The personal workspace can't be deleted".to_string()));
+    }
+
+    let connections = ConnectionRepository::find_by_workspace(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    WorkspaceRepository::delete(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Uploaded SQLite files go away with their connections, unless a
+    // connection elsewhere still uses them
+    for conn in connections.iter().filter(|c| c.db_type == "sqlite") {
+        if let Some(path) = conn.database_name.as_deref() {
+            remove_upload_if_unused(&state, &conn.user_id, path).await;
+        }
+    }
+
+    tracing::info!("{} deleted workspace {}", auth_user.username, id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_members(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<WorkspaceMember>>, (StatusCode, String)> {
+    workspace_for(&state, &auth_user, &id, WorkspaceRole::Viewer).await?;
+
+    let members = WorkspaceRepository::list_members(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(members))
+}
+
+pub async fn add_member(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Json(req): Json<AddMemberRequest>,
+) -> Result<(StatusCode, Json<Vec<WorkspaceMember>>), (StatusCode, String)> {
+    let workspace = workspace_for(&state, &auth_user, &id, WorkspaceRole::Admin).await?;
+    if workspace.workspace.personal_user_id.is_some() {
+        return Err((StatusCode::BAD_REQUEST, "The personal workspace can't be shared".to_string()));
+    }
+    require_manage(workspace.role, req.role)?;
+
+    let user = UserRepository::find_by_username(&state.db, &req.username)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    let added = WorkspaceRepository::add_member(&state.db, &id, &user.id, req.role)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !added {
+        return Err((StatusCode::CONFLICT, "User is already a member".to_string()));
+    }
+    audit(&state, &id, AUDIT_MEMBER_ADDED, &auth_user, &user.id, req.role, &addr).await?;
+
+    tracing::info!("{} added {} to workspace {} as {}", auth_user.username, user.username, id, req.role.as_str());
+    let members = WorkspaceRepository::list_members(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok((StatusCode::CREATED, Json(members)))
+}
+
+pub async fn update_member(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: AuthUser,
+    Path((id, user_id)): Path<(String, String)>,
+    Json(req): Json<UpdateMemberRequest>,
+) -> Result<Json<Vec<WorkspaceMember>>, (StatusCode, String)> {
+    let workspace = workspace_for(&state, &auth_user, &id, WorkspaceRole::Viewer).await?;
+
+    let current = WorkspaceRepository::member_role(&state.db, &id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Member not found".to_string()))?;
+    require_manage(workspace.role, current.max(req.role))?;
+
+    let updated = WorkspaceRepository::update_member_role(&state.db, &id, &user_id, req.role)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !updated {
+        return Err((StatusCode::CONFLICT, "A workspace needs at least one owner".to_string()));
+    }
+    audit(&state, &id, AUDIT_MEMBER_ROLE_CHANGED, &auth_user, &user_id, req.role, &addr).await?;
+
+    let members = WorkspaceRepository::list_members(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(members))
+}
+
+/// Removes a member; anyone may leave a workspace on their own
+pub async fn remove_member(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: AuthUser,
+    Path((id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let workspace = workspace_for(&state, &auth_user, &id, WorkspaceRole::Viewer).await?;
+    if workspace.workspace.personal_user_id.is_some() {
+        return Err((StatusCode::BAD_REQUEST, "Nobody can leave a personal workspace".to_string()));
+    }
+
+    let current = WorkspaceRepository::member_role(&state.db, &id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Member not found".to_string()))?;
+    if user_id != auth_user.user_id {
+        require_manage(workspace.role, current)?;
+    }
+
+    let removed = WorkspaceRepository::remove_member(&state.db, &id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !removed {
+        return Err((StatusCode::CONFLICT, "A workspace needs at least one owner".to_string()));
+    }
+    audit(&state, &id, AUDIT_MEMBER_REMOVED, &auth_user, &user_id, current, &addr).await?;
+
+    tracing::info!("{} removed {} from workspace {}", auth_user.username, user_id, id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Membership changes and invitation activity, newest first
+pub async fn list_audit(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(id): Path<String>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditPage>, (StatusCode, String)> {
+    workspace_for(&state, &auth_user, &id, WorkspaceRole::Admin).await?;
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    let (items, total) = AuditRepository::find_by_workspace(&state.db, &id, per_page, page_offset(page, per_page)?)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(AuditPage {
+        items,
+        total,
+        page,
+        per_page,
+    }))
+}
@@ -3,12 +3,44 @@ use std::env;
 #[derive(Debug, Clone)]
 pub struct Config {
     pub jwt_secret: String,
-    pub jwt_expiration_hours: i64,
+    pub access_token_minutes: i64,
+    pub refresh_token_days: i64,
+    pub require_2fa: bool,
+    pub require_email_verification: bool,
+    pub password_min_length: usize,
+    pub password_max_length: usize,
+    pub password_require_lowercase: bool,
+    pub password_require_uppercase: bool,
+    pub password_require_digit: bool,
+    pub password_require_symbol: bool,
+    // Range directory or hash list file in the Have I Been Pwned format
+    pub breached_passwords_path: Option<String>,
+    pub breached_passwords_min_count: u64,
+    // Frontend URL used in the links of verification and reset emails
+    pub app_base_url: String,
+    pub mailer: String,
+    pub mail_from: String,
+    pub mail_dir: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub smtp_starttls: bool,
+    // Lets AUTH go out over a connection without TLS
+    pub smtp_allow_plaintext_auth: bool,
     pub encryption_key: Vec<u8>,
     pub server_host: String,
     pub server_port: u16,
     pub cors_origin: String,
     pub database_url: String,
+    pub upload_dir: String,
+    pub sqlite_allowed_dirs: Vec<String>,
+    pub max_upload_mb: usize,
+    pub connection_check_interval_secs: u64,
+    pub admin_user_ids: Vec<String>,
+    pub pow_base_difficulty: u32,
+    pub pow_max_difficulty: u32,
+    pub pow_challenge_ttl_secs: u64,
 }
 
 impl Config {
@@ -22,10 +54,16 @@ impl Config {
             tracing::warn!("JWT_SECRET should be at least 32 characters for security");
         }
 
-        let jwt_expiration_hours = env::var("JWT_EXPIRATION_HOURS")
-            .unwrap_or_else(|_| "24".to_string())
+        // Access tokens are short-lived; clients renew them with the refresh token
+        let access_token_minutes = env::var("ACCESS_TOKEN_MINUTES")
+            .unwrap_or_else(|_| "15".to_string())
             .parse()
-            .unwrap_or(24);
+            .unwrap_or(15);
+
+        let refresh_token_days = env::var("REFRESH_TOKEN_DAYS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or(30);
 
         let encryption_key_hex = env::var("ENCRYPTION_KEY")
             .unwrap_or_else(|_| {
@@ -40,6 +78,42 @@ impl Config {
             return Err(anyhow::anyhow!("ENCRYPTION_KEY must be 32 bytes (64 hex chars)"));
         }
 
+        // Users without 2FA can't use connections (and their credentials) when enabled
+        let require_2fa = env::var("REQUIRE_2FA")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+
+        // Unverified accounts can't use connections or create access tokens when enabled
+        let require_email_verification = env::var("REQUIRE_EMAIL_VERIFICATION")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(true);
+
+        let password_min_length = env::var("PASSWORD_MIN_LENGTH")
+            .unwrap_or_else(|_| "8".to_string())
+            .parse()
+            .unwrap_or(8);
+        // Argon2 cost grows with the input, keep it bounded
+        let password_max_length = env::var("PASSWORD_MAX_LENGTH")
+            .unwrap_or_else(|_| "128".to_string())
+            .parse::<usize>()
+            .unwrap_or(128)
+            .max(password_min_length);
+        let flag = |name: &str, default: bool| {
+            env::var(name)
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(default)
+        };
+        let password_require_lowercase = flag("PASSWORD_REQUIRE_LOWERCASE", true);
+        let password_require_uppercase = flag("PASSWORD_REQUIRE_UPPERCASE", true);
+        let password_require_digit = flag("PASSWORD_REQUIRE_DIGIT", true);
+        let password_require_symbol = flag("PASSWORD_REQUIRE_SYMBOL", false);
+
+        let breached_passwords_path = env::var("BREACHED_PASSWORDS_PATH").ok().filter(|v| !v.is_empty());
+        let breached_passwords_min_count = env::var("BREACHED_PASSWORDS_MIN_COUNT")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .unwrap_or(1);
+
         let server_host = env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
         let server_port = env::var("SERVER_PORT")
             .unwrap_or_else(|_| "8080".to_string())
@@ -52,14 +126,115 @@ impl Config {
         let database_url = env::var("DATABASE_URL")
             .unwrap_or_else(|_| "sqlite:./nexusdb.db".to_string());
 
+        let upload_dir = env::var("UPLOAD_DIR").unwrap_or_else(|_| "./uploads".to_string());
+
+        // Server-side directories that SQLite connections may point at
+        let sqlite_allowed_dirs = env::var("SQLITE_ALLOWED_DIRS")
+            .unwrap_or_else(|_| "./data".to_string())
+            .split(',')
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty())
+            .collect();
+
+        let max_upload_mb = env::var("MAX_UPLOAD_MB")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .unwrap_or(100);
+
+        // 0 disables the background connection monitor
+        let connection_check_interval_secs = env::var("CONNECTION_CHECK_INTERVAL_SECS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse()
+            .unwrap_or(300);
+
+        // Users that get the admin role when the server starts. IDs, not usernames:
+        // a name can be registered or taken over by someone else, an ID can't
+        let admin_user_ids = env::var("ADMIN_USER_IDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty())
+            .collect();
+
+        // Proof-of-work: leading hex zeros of sha256(challenge_id + nonce)
+        let pow_base_difficulty = env::var("POW_BASE_DIFFICULTY")
+            .unwrap_or_else(|_| "4".to_string())
+            .parse()
+            .unwrap_or(4);
+
+        let pow_max_difficulty = env::var("POW_MAX_DIFFICULTY")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse::<u32>()
+            .unwrap_or(6)
+            .max(pow_base_difficulty);
+
+        let pow_challenge_ttl_secs = env::var("POW_CHALLENGE_TTL_SECS")
+            .unwrap_or_else(|_| "120".to_string())
+            .parse()
+            .unwrap_or(120);
+
+        let app_base_url = env::var("APP_BASE_URL")
+            .unwrap_or_else(|_| cors_origin.clone())
+            .trim_end_matches('/')
+            .to_string();
+
+        // "log" writes emails to MAIL_DIR, "smtp" sends them
+        let mailer = env::var("MAILER").unwrap_or_else(|_| "log".to_string());
+        let mail_from = env::var("MAIL_FROM")
+            .unwrap_or_else(|_| "NexusDB <no-reply@localhost>".to_string());
+        let mail_dir = env::var("MAIL_DIR").unwrap_or_else(|_| "./mail".to_string());
+
+        let smtp_host = env::var("SMTP_HOST").unwrap_or_else(|_| "localhost".to_string());
+        let smtp_port = env::var("SMTP_PORT")
+            .unwrap_or_else(|_| "587".to_string())
+            .parse()
+            .unwrap_or(587);
+        let smtp_username = env::var("SMTP_USERNAME").ok().filter(|v| !v.is_empty());
+        let smtp_password = env::var("SMTP_PASSWORD").ok().filter(|v| !v.is_empty());
+        let smtp_starttls = env::var("SMTP_STARTTLS")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(true);
+        let smtp_allow_plaintext_auth = env::var("SMTP_ALLOW_PLAINTEXT_AUTH")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+
         Ok(Config {
             jwt_secret,
-            jwt_expiration_hours,
+            access_token_minutes,
+            refresh_token_days,
+            require_2fa,
+            require_email_verification,
+            password_min_length,
+            password_max_length,
+            password_require_lowercase,
+            password_require_uppercase,
+            password_require_digit,
+            password_require_symbol,
+            breached_passwords_path,
+            breached_passwords_min_count,
+            app_base_url,
+            mailer,
+            mail_from,
+            mail_dir,
+            smtp_host,
+            smtp_port,
+            smtp_username,
+            smtp_password,
+            smtp_starttls,
+            smtp_allow_plaintext_auth,
             encryption_key,
             server_host,
             server_port,
             cors_origin,
             database_url,
+            upload_dir,
+            sqlite_allowed_dirs,
+            max_upload_mb,
+            connection_check_interval_secs,
+            admin_user_ids,
+            pow_base_difficulty,
+            pow_max_difficulty,
+            pow_challenge_ttl_secs,
         })
     }
 }
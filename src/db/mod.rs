@@ -2,6 +2,8 @@ pub mod repository;
 
 use sqlx::{sqlite::SqlitePool, Pool, Sqlite};
 
+use crate::db::repository::insert_personal_workspace;
+
 pub type DbPool = Pool<Sqlite>;
 
 pub async fn create_pool(database_url: &str) -> Result<DbPool, anyhow::Error> {
@@ -18,6 +20,12 @@ async fn run_migrations(pool: &DbPool) -> Result<(), anyhow::Error> {
             username TEXT UNIQUE NOT NULL,
             email TEXT UNIQUE NOT NULL,
             password_hash TEXT NOT NULL,
+            role TEXT NOT NULL DEFAULT 'user',
+            disabled_at TEXT,
+            totp_secret TEXT,
+            totp_enabled_at TEXT,
+            totp_last_step INTEGER,
+            email_verified_at TEXT,
             created_at TEXT NOT NULL,
             updated_at TEXT NOT NULL
         )
@@ -38,7 +46,9 @@ async fn run_migrations(pool: &DbPool) -> Result<(), anyhow::Error> {
             username TEXT NOT NULL,
             encrypted_password TEXT NOT NULL,
             database_name TEXT,
+            workspace_id TEXT REFERENCES workspaces(id) ON DELETE CASCADE,
             status TEXT NOT NULL DEFAULT 'disconnected',
+            last_checked_at TEXT,
             created_at TEXT NOT NULL,
             updated_at TEXT NOT NULL,
             FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
@@ -56,6 +66,7 @@ async fn run_migrations(pool: &DbPool) -> Result<(), anyhow::Error> {
             name TEXT NOT NULL,
             query TEXT NOT NULL,
             db_type TEXT NOT NULL,
+            workspace_id TEXT REFERENCES workspaces(id) ON DELETE CASCADE,
             created_at TEXT NOT NULL,
             updated_at TEXT NOT NULL,
             FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
@@ -85,6 +96,408 @@ async fn run_migrations(pool: &DbPool) -> Result<(), anyhow::Error> {
     .execute(pool)
     .await?;
 
+    // Temporary or permanent bans of IPs and usernames
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS banned_entities (
+            id TEXT PRIMARY KEY,
+            entity_type TEXT NOT NULL,
+            value TEXT NOT NULL,
+            reason TEXT,
+            banned_at TEXT NOT NULL,
+            expires_at TEXT,
+            created_by TEXT
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_banned_entities_value ON banned_entities (entity_type, value)",
+    )
+    .execute(pool)
+    .await?;
+
+    // History is always listed per user, newest first
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_query_executions_user ON query_executions (user_id, executed_at)",
+    )
+    .execute(pool)
+    .await?;
+
+    // Last introspection of each connection, stored as JSON
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_cache (
+            connection_id TEXT PRIMARY KEY,
+            catalog TEXT NOT NULL,
+            refreshed_at TEXT NOT NULL,
+            FOREIGN KEY (connection_id) REFERENCES connections(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Login sessions; each one owns a chain of rotating refresh tokens
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS sessions (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            user_agent TEXT,
+            ip TEXT,
+            created_at TEXT NOT NULL,
+            last_used_at TEXT,
+            expires_at TEXT NOT NULL,
+            revoked_at TEXT,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions (user_id)")
+        .execute(pool)
+        .await?;
+
+    // Only the SHA-256 of each refresh token is stored
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS refresh_tokens (
+            id TEXT PRIMARY KEY,
+            session_id TEXT NOT NULL,
+            token_hash TEXT UNIQUE NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            used_at TEXT,
+            FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Access tokens revoked before they expire, by jti
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS revoked_tokens (
+            jti TEXT PRIMARY KEY,
+            expires_at TEXT NOT NULL,
+            revoked_at TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // One-time 2FA recovery codes, hashed with argon2
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS recovery_codes (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            code_hash TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            used_at TEXT,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Personal access tokens for CLI and automation, stored as SHA-256
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS personal_access_tokens (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            token_hash TEXT UNIQUE NOT NULL,
+            scopes TEXT NOT NULL,
+            connection_ids TEXT,
+            created_at TEXT NOT NULL,
+            expires_at TEXT,
+            last_used_at TEXT,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Connections and scripts belong to workspaces; every user has a personal one
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS workspaces (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            personal_user_id TEXT UNIQUE,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            FOREIGN KEY (personal_user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS workspace_members (
+            workspace_id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            role TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (workspace_id, user_id),
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_workspace_members_user ON workspace_members (user_id)")
+        .execute(pool)
+        .await?;
+
+    // Pending and past invitations; `email` is NULL for shareable links
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS workspace_invitations (
+            id TEXT PRIMARY KEY,
+            workspace_id TEXT NOT NULL,
+            email TEXT,
+            role TEXT NOT NULL,
+            invited_by TEXT,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            accepted_at TEXT,
+            accepted_by TEXT,
+            revoked_at TEXT,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE,
+            FOREIGN KEY (invited_by) REFERENCES users(id) ON DELETE SET NULL,
+            FOREIGN KEY (accepted_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_workspace_invitations_workspace ON workspace_invitations (workspace_id)")
+        .execute(pool)
+        .await?;
+
+    // Membership changes of each workspace, who made them and from where
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS workspace_audit (
+            id TEXT PRIMARY KEY,
+            workspace_id TEXT NOT NULL,
+            actor_id TEXT,
+            action TEXT NOT NULL,
+            target_user_id TEXT,
+            role TEXT,
+            invitation_id TEXT,
+            ip_address TEXT,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE,
+            FOREIGN KEY (actor_id) REFERENCES users(id) ON DELETE SET NULL,
+            FOREIGN KEY (target_user_id) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_workspace_audit_workspace ON workspace_audit (workspace_id, created_at)")
+        .execute(pool)
+        .await?;
+
+    // Single-use tokens sent by email (verification, password reset)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS email_tokens (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            purpose TEXT NOT NULL,
+            token_hash TEXT UNIQUE NOT NULL,
+            -- Address waiting for confirmation, for email changes
+            new_email TEXT,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            used_at TEXT,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Execution limits per connection; connections without a row run unrestricted
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS connection_policies (
+            connection_id TEXT PRIMARY KEY,
+            read_only BOOLEAN NOT NULL DEFAULT 0,
+            allowed_statements TEXT NOT NULL,
+            max_rows INTEGER,
+            statement_timeout_secs INTEGER,
+            allowed_from TEXT,
+            allowed_until TEXT,
+            utc_offset_minutes INTEGER NOT NULL DEFAULT 0,
+            updated_by TEXT,
+            updated_at TEXT NOT NULL,
+            FOREIGN KEY (connection_id) REFERENCES connections(id) ON DELETE CASCADE,
+            FOREIGN KEY (updated_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Writes on protected connections waiting for approval, and what their
+    // single run returned
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS change_requests (
+            id TEXT PRIMARY KEY,
+            workspace_id TEXT NOT NULL,
+            connection_id TEXT NOT NULL,
+            requested_by TEXT,
+            query TEXT NOT NULL,
+            justification TEXT NOT NULL,
+            summary TEXT NOT NULL,
+            status TEXT NOT NULL,
+            required_approvals INTEGER NOT NULL,
+            approver_role TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            decided_at TEXT,
+            executed_by TEXT,
+            executed_at TEXT,
+            result TEXT,
+            error TEXT,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE,
+            FOREIGN KEY (connection_id) REFERENCES connections(id) ON DELETE CASCADE,
+            FOREIGN KEY (requested_by) REFERENCES users(id) ON DELETE SET NULL,
+            FOREIGN KEY (executed_by) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_change_requests_connection ON change_requests (connection_id, created_at)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS change_request_reviews (
+            id TEXT PRIMARY KEY,
+            change_request_id TEXT NOT NULL,
+            reviewer_id TEXT,
+            decision TEXT NOT NULL,
+            comment TEXT,
+            created_at TEXT NOT NULL,
+            UNIQUE (change_request_id, reviewer_id),
+            FOREIGN KEY (change_request_id) REFERENCES change_requests(id) ON DELETE CASCADE,
+            FOREIGN KEY (reviewer_id) REFERENCES users(id) ON DELETE SET NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Columns added after the tables were first created
+    add_column_if_missing(pool, "connections", "last_checked_at", "TEXT").await?;
+    add_column_if_missing(pool, "users", "role", "TEXT NOT NULL DEFAULT 'user'").await?;
+    add_column_if_missing(pool, "users", "disabled_at", "TEXT").await?;
+    add_column_if_missing(pool, "users", "totp_secret", "TEXT").await?;
+    add_column_if_missing(pool, "users", "totp_enabled_at", "TEXT").await?;
+    add_column_if_missing(pool, "users", "totp_last_step", "INTEGER").await?;
+    if add_column_if_missing(pool, "users", "email_verified_at", "TEXT").await? {
+        // Accounts from before email verification existed keep working
+        sqlx::query("UPDATE users SET email_verified_at = created_at")
+            .execute(pool)
+            .await?;
+    }
+    add_column_if_missing(pool, "email_tokens", "new_email", "TEXT").await?;
+    add_column_if_missing(
+        pool,
+        "connections",
+        "workspace_id",
+        "TEXT REFERENCES workspaces(id) ON DELETE CASCADE",
+    )
+    .await?;
+    add_column_if_missing(
+        pool,
+        "scripts",
+        "workspace_id",
+        "TEXT REFERENCES workspaces(id) ON DELETE CASCADE",
+    )
+    .await?;
+
+    // Users from before workspaces get their personal one, holding what they owned
+    let orphans: Vec<(String,)> = sqlx::query_as(
+        "SELECT id FROM users WHERE id NOT IN (SELECT personal_user_id FROM workspaces WHERE personal_user_id IS NOT NULL)",
+    )
+    .fetch_all(pool)
+    .await?;
+    for (user_id,) in orphans {
+        let mut tx = pool.begin().await?;
+        insert_personal_workspace(&mut tx, &user_id).await?;
+        tx.commit().await?;
+    }
+    for table in ["connections", "scripts"] {
+        sqlx::query(&format!(
+            "UPDATE {0} SET workspace_id = (SELECT id FROM workspaces WHERE personal_user_id = {0}.user_id) WHERE workspace_id IS NULL",
+            table
+        ))
+        .execute(pool)
+        .await?;
+    }
+    add_column_if_missing(pool, "connection_policies", "max_statements", "INTEGER").await?;
+    add_column_if_missing(
+        pool,
+        "connection_policies",
+        "allow_unfiltered_writes",
+        "BOOLEAN NOT NULL DEFAULT 1",
+    )
+    .await?;
+    add_column_if_missing(pool, "connection_policies", "require_approval", "BOOLEAN NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "connection_policies", "required_approvals", "INTEGER NOT NULL DEFAULT 1").await?;
+    add_column_if_missing(pool, "connection_policies", "approver_role", "TEXT NOT NULL DEFAULT 'admin'").await?;
+    add_column_if_missing(pool, "sessions", "user_agent", "TEXT").await?;
+    add_column_if_missing(pool, "sessions", "ip", "TEXT").await?;
+    add_column_if_missing(pool, "sessions", "last_used_at", "TEXT").await?;
+
     tracing::info!("Database migrations completed");
     Ok(())
 }
+
+/// Returns whether the column had to be added
+async fn add_column_if_missing(
+    pool: &DbPool,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<bool, anyhow::Error> {
+    let columns: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info(?)")
+        .bind(table)
+        .fetch_all(pool)
+        .await?;
+
+    if columns.iter().any(|(name,)| name == column) {
+        return Ok(false);
+    }
+
+    sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+        .execute(pool)
+        .await?;
+    Ok(true)
+}
@@ -1,6 +1,7 @@
-use crate::models::{User, Connection, Script, QueryExecution};
+use crate::models::{User, Connection, Script, QueryExecution, HistoryEntry, HistoryQuery, SchemaCache, Session, RefreshToken, RecoveryCode, PersonalAccessToken, EmailToken, Workspace, WorkspaceAccess, WorkspaceMember, WorkspaceRole, ConnectionAccess, ScriptAccess, ConnectionPolicy, ExecutionPolicy, WorkspaceInvitation, AuditEvent, NewAuditEvent, AUDIT_INVITATION_ACCEPTED, ChangeRequest, ChangeRequestReview, ChangeRequestStatus, ReviewDecision};
+use sqlx::{QueryBuilder, Sqlite};
 use crate::db::DbPool;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 pub struct UserRepository;
@@ -14,6 +15,7 @@ impl UserRepository {
     ) -> Result<User, anyhow::Error> {
         let id = Uuid::new_v4().to_string();
         let now = Utc::now();
+        let mut tx = pool.begin().await?;
 
         let user = sqlx::query_as::<_, User>(
             r#"
@@ -28,9 +30,12 @@ impl UserRepository {
         .bind(password_hash)
         .bind(now.to_rfc3339())
         .bind(now.to_rfc3339())
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        insert_personal_workspace(&mut tx, &user.id).await?;
+        tx.commit().await?;
+
         Ok(user)
     }
 
@@ -49,14 +54,377 @@ impl UserRepository {
             .await?;
         Ok(user)
     }
+
+    /// Matched case-insensitively, so accounts registered with different casing all match
+    pub async fn find_by_email(pool: &DbPool, email: &str) -> Result<Vec<User>, anyhow::Error> {
+        let users = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = ? COLLATE NOCASE")
+            .bind(email)
+            .fetch_all(pool)
+            .await?;
+        Ok(users)
+    }
+
+    pub async fn mark_email_verified(pool: &DbPool, id: &str) -> Result<(), anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        sqlx::query("UPDATE users SET email_verified_at = ?, updated_at = ? WHERE id = ? AND email_verified_at IS NULL")
+            .bind(&now)
+            .bind(&now)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Sets a new password after a reset and signs the user out everywhere.
+    /// The reset link proves the email address too, so it counts as verified.
+    pub async fn reset_password(pool: &DbPool, id: &str, password_hash: &str) -> Result<(), anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET password_hash = ?, email_verified_at = COALESCE(email_verified_at, ?), updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(password_hash)
+        .bind(&now)
+        .bind(&now)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE sessions SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL")
+            .bind(&now)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Sets a new password and signs out every session but `keep`
+    pub async fn change_password(pool: &DbPool, id: &str, password_hash: &str, keep: &str) -> Result<(), anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("UPDATE users SET password_hash = ?, updated_at = ? WHERE id = ?")
+            .bind(password_hash)
+            .bind(&now)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE sessions SET revoked_at = ? WHERE user_id = ? AND id <> ? AND revoked_at IS NULL")
+            .bind(&now)
+            .bind(id)
+            .bind(keep)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Switches to a confirmed address; None when another account already uses it
+    pub async fn change_email(pool: &DbPool, id: &str, email: &str) -> Result<Option<User>, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE users SET email = ?, email_verified_at = ?, updated_at = ?
+            WHERE id = ? AND NOT EXISTS (SELECT 1 FROM users WHERE email = ? COLLATE NOCASE AND id <> ?)
+            RETURNING *
+            "#,
+        )
+        .bind(email)
+        .bind(&now)
+        .bind(&now)
+        .bind(id)
+        .bind(email)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(user)
+    }
+
+    /// Removes the user with their personal workspace and the shared ones
+    /// where nobody else is left. What they created in workspaces that other
+    /// members still use is handed over to one of those members. History,
+    /// sessions and tokens go through ON DELETE CASCADE.
+    pub async fn delete(pool: &DbPool, id: &str) -> Result<bool, anyhow::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM workspaces
+            WHERE personal_user_id = ?
+               OR (id IN (SELECT workspace_id FROM workspace_members WHERE user_id = ?)
+                   AND NOT EXISTS (SELECT 1 FROM workspace_members m WHERE m.workspace_id = workspaces.id AND m.user_id <> ?))
+            "#,
+        )
+        .bind(id)
+        .bind(id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        for table in ["connections", "scripts"] {
+            sqlx::query(&format!(
+                r#"
+                UPDATE {0} SET user_id = (
+                    SELECT m.user_id FROM workspace_members m
+                    WHERE m.workspace_id = {0}.workspace_id AND m.user_id <> ?
+                    ORDER BY m.role = 'owner' DESC, m.role = 'admin' DESC, m.created_at
+                    LIMIT 1
+                )
+                WHERE user_id = ?
+                "#,
+                table
+            ))
+            .bind(id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let result = sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Page of users whose username or email contains `q`, plus the total match count
+    pub async fn search(
+        pool: &DbPool,
+        q: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<User>, i64), anyhow::Error> {
+        let pattern = format!("%{}%", escape_like(q.unwrap_or_default()));
+
+        let (total,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM users WHERE username LIKE ? ESCAPE '\\' OR email LIKE ? ESCAPE '\\'"
+        )
+        .bind(&pattern)
+        .bind(&pattern)
+        .fetch_one(pool)
+        .await?;
+
+        let users = sqlx::query_as::<_, User>(
+            r#"
+            SELECT * FROM users
+            WHERE username LIKE ? ESCAPE '\' OR email LIKE ? ESCAPE '\'
+            ORDER BY created_at DESC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        Ok((users, total))
+    }
+
+    /// Disables the account (keeping its data) or re-enables it
+    pub async fn set_disabled(pool: &DbPool, id: &str, disabled: bool) -> Result<Option<User>, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let user = sqlx::query_as::<_, User>(
+            "UPDATE users SET disabled_at = ?, updated_at = ? WHERE id = ? RETURNING *"
+        )
+        .bind(disabled.then(|| now.clone()))
+        .bind(&now)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(user)
+    }
+
+    /// Stores a new, not yet confirmed TOTP secret. Any previous 2FA setup is dropped.
+    pub async fn set_pending_totp(pool: &DbPool, id: &str, encrypted_secret: &str) -> Result<(), anyhow::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            "UPDATE users SET totp_secret = ?, totp_enabled_at = NULL, totp_last_step = NULL, updated_at = ? WHERE id = ?"
+        )
+        .bind(encrypted_secret)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Turns 2FA on with the pending secret and replaces the recovery codes
+    pub async fn enable_totp(pool: &DbPool, id: &str, recovery_code_hashes: &[String]) -> Result<(), anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("UPDATE users SET totp_enabled_at = ?, updated_at = ? WHERE id = ?")
+            .bind(&now)
+            .bind(&now)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        replace_recovery_codes(&mut tx, id, recovery_code_hashes, &now).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn disable_totp(pool: &DbPool, id: &str) -> Result<(), anyhow::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            "UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL, updated_at = ? WHERE id = ?"
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Records the TOTP step just accepted. False if that step (or a later one)
+    /// was already used, i.e. the code is being replayed.
+    pub async fn record_totp_step(pool: &DbPool, id: &str, step: i64) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET totp_last_step = ? WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)"
+        )
+        .bind(step)
+        .bind(id)
+        .bind(step)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn regenerate_recovery_codes(pool: &DbPool, id: &str, hashes: &[String]) -> Result<(), anyhow::Error> {
+        let mut tx = pool.begin().await?;
+        replace_recovery_codes(&mut tx, id, hashes, &Utc::now().to_rfc3339()).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn unused_recovery_codes(pool: &DbPool, id: &str) -> Result<Vec<RecoveryCode>, anyhow::Error> {
+        let codes = sqlx::query_as::<_, RecoveryCode>(
+            "SELECT id, code_hash FROM recovery_codes WHERE user_id = ? AND used_at IS NULL"
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await?;
+        Ok(codes)
+    }
+
+    /// Consumes a recovery code; false if it was used concurrently
+    pub async fn use_recovery_code(pool: &DbPool, code_id: &str) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query("UPDATE recovery_codes SET used_at = ? WHERE id = ? AND used_at IS NULL")
+            .bind(Utc::now().to_rfc3339())
+            .bind(code_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Gives the user the role. False when there's no such user.
+    pub async fn grant_role(pool: &DbPool, user_id: &str, role: &str) -> Result<bool, anyhow::Error> {
+        sqlx::query("UPDATE users SET role = ?, updated_at = ? WHERE id = ? AND role <> ?")
+            .bind(role)
+            .bind(Utc::now().to_rfc3339())
+            .bind(user_id)
+            .bind(role)
+            .execute(pool)
+            .await?;
+        let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+        Ok(exists > 0)
+    }
+}
+
+async fn replace_recovery_codes(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    user_id: &str,
+    hashes: &[String],
+    now: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut **tx)
+        .await?;
+
+    for hash in hashes {
+        sqlx::query("INSERT INTO recovery_codes (id, user_id, code_hash, created_at) VALUES (?, ?, ?, ?)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(user_id)
+            .bind(hash)
+            .bind(now)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Creates the private workspace of a new user, with them as owner
+pub async fn insert_personal_workspace(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), anyhow::Error> {
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+
+    sqlx::query(
+        "INSERT INTO workspaces (id, name, personal_user_id, created_at, updated_at) VALUES (?, 'Personal', ?, ?, ?)"
+    )
+    .bind(&id)
+    .bind(user_id)
+    .bind(&now)
+    .bind(&now)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query("INSERT INTO workspace_members (workspace_id, user_id, role, created_at) VALUES (?, ?, ?, ?)")
+        .bind(&id)
+        .bind(user_id)
+        .bind(WorkspaceRole::Owner)
+        .bind(&now)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
 }
 
 pub struct ConnectionRepository;
 
 impl ConnectionRepository {
+    #[allow(clippy::too_many_arguments)]
     pub async fn create(
         pool: &DbPool,
         user_id: &str,
+        workspace_id: &str,
         name: &str,
         db_type: &str,
         host: &str,
@@ -71,13 +439,14 @@ impl ConnectionRepository {
         let conn = sqlx::query_as::<_, Connection>(
             r#"
             INSERT INTO connections 
-            (id, user_id, name, db_type, host, port, username, encrypted_password, database_name, status, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 'disconnected', ?, ?)
+            (id, user_id, workspace_id, name, db_type, host, port, username, encrypted_password, database_name, status, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'disconnected', ?, ?)
             RETURNING *
             "#,
         )
         .bind(&id)
         .bind(user_id)
+        .bind(workspace_id)
         .bind(name)
         .bind(db_type)
         .bind(host)
@@ -93,6 +462,7 @@ impl ConnectionRepository {
         Ok(conn)
     }
 
+    /// Connections created by the user, whatever workspace they are in
     pub async fn find_by_user(pool: &DbPool, user_id: &str) -> Result<Vec<Connection>, anyhow::Error> {
         let connections = sqlx::query_as::<_, Connection>(
             "SELECT * FROM connections WHERE user_id = ? ORDER BY created_at DESC"
@@ -103,21 +473,121 @@ impl ConnectionRepository {
         Ok(connections)
     }
 
-    pub async fn find_by_id(pool: &DbPool, id: &str, user_id: &str) -> Result<Option<Connection>, anyhow::Error> {
-        let conn = sqlx::query_as::<_, Connection>(
-            "SELECT * FROM connections WHERE id = ? AND user_id = ?"
+    /// Connections of every workspace the user is a member of, or of just one
+    pub async fn find_accessible(
+        pool: &DbPool,
+        user_id: &str,
+        workspace_id: Option<&str>,
+    ) -> Result<Vec<ConnectionAccess>, anyhow::Error> {
+        let connections = sqlx::query_as::<_, ConnectionAccess>(
+            r#"
+            SELECT c.*, m.role FROM connections c
+            JOIN workspace_members m ON m.workspace_id = c.workspace_id AND m.user_id = ?
+            WHERE (? IS NULL OR c.workspace_id = ?)
+            ORDER BY c.created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(workspace_id)
+        .bind(workspace_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(connections)
+    }
+
+    /// The connection and the user's role in its workspace; None when the
+    /// user is not a member there
+    pub async fn find_by_id(pool: &DbPool, id: &str, user_id: &str) -> Result<Option<ConnectionAccess>, anyhow::Error> {
+        let conn = sqlx::query_as::<_, ConnectionAccess>(
+            r#"
+            SELECT c.*, m.role FROM connections c
+            JOIN workspace_members m ON m.workspace_id = c.workspace_id AND m.user_id = ?
+            WHERE c.id = ?
+            "#,
         )
-        .bind(id)
         .bind(user_id)
+        .bind(id)
         .fetch_optional(pool)
         .await?;
         Ok(conn)
     }
 
-    pub async fn delete(pool: &DbPool, id: &str, user_id: &str) -> Result<bool, anyhow::Error> {
-        let result = sqlx::query("DELETE FROM connections WHERE id = ? AND user_id = ?")
+    pub async fn find_by_workspace(pool: &DbPool, workspace_id: &str) -> Result<Vec<Connection>, anyhow::Error> {
+        let connections = sqlx::query_as::<_, Connection>("SELECT * FROM connections WHERE workspace_id = ?")
+            .bind(workspace_id)
+            .fetch_all(pool)
+            .await?;
+        Ok(connections)
+    }
+
+    /// Whether any connection still points at this SQLite file
+    pub async fn is_path_used(pool: &DbPool, path: &str) -> Result<bool, anyhow::Error> {
+        let (used,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM connections WHERE database_name = ?)")
+            .bind(path)
+            .fetch_one(pool)
+            .await?;
+        Ok(used)
+    }
+
+    /// Saves the editable fields of `conn` only if the stored row is still at
+    /// `expected_updated_at`. Returns None when the row changed or is missing.
+    pub async fn update(
+        pool: &DbPool,
+        conn: &Connection,
+        expected_updated_at: DateTime<Utc>,
+    ) -> Result<Option<Connection>, anyhow::Error> {
+        let updated = sqlx::query_as::<_, Connection>(
+            r#"
+            UPDATE connections
+            SET name = ?, host = ?, port = ?, username = ?, encrypted_password = ?,
+                database_name = ?, status = ?, last_checked_at = ?, updated_at = ?
+            WHERE id = ? AND updated_at = ?
+            RETURNING *
+            "#,
+        )
+        .bind(&conn.name)
+        .bind(&conn.host)
+        .bind(conn.port)
+        .bind(&conn.username)
+        .bind(&conn.encrypted_password)
+        .bind(&conn.database_name)
+        .bind(&conn.status)
+        .bind(conn.last_checked_at.map(|t| t.to_rfc3339()))
+        .bind(Utc::now().to_rfc3339())
+        .bind(&conn.id)
+        .bind(expected_updated_at.to_rfc3339())
+        .fetch_optional(pool)
+        .await?;
+        Ok(updated)
+    }
+
+    pub async fn find_all(pool: &DbPool) -> Result<Vec<Connection>, anyhow::Error> {
+        let connections = sqlx::query_as::<_, Connection>("SELECT * FROM connections")
+            .fetch_all(pool)
+            .await?;
+        Ok(connections)
+    }
+
+    /// Records the outcome of a health check without touching updated_at
+    pub async fn update_status(
+        pool: &DbPool,
+        id: &str,
+        status: &str,
+        checked_at: DateTime<Utc>,
+    ) -> Result<(), anyhow::Error> {
+        sqlx::query("UPDATE connections SET status = ?, last_checked_at = ? WHERE id = ?")
+            .bind(status)
+            .bind(checked_at.to_rfc3339())
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Callers check the user's role in the workspace first
+    pub async fn delete(pool: &DbPool, id: &str) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query("DELETE FROM connections WHERE id = ?")
             .bind(id)
-            .bind(user_id)
             .execute(pool)
             .await?;
         Ok(result.rows_affected() > 0)
@@ -130,6 +600,7 @@ impl ScriptRepository {
     pub async fn create(
         pool: &DbPool,
         user_id: &str,
+        workspace_id: &str,
         name: &str,
         query: &str,
         db_type: &str,
@@ -139,13 +610,14 @@ impl ScriptRepository {
 
         let script = sqlx::query_as::<_, Script>(
             r#"
-            INSERT INTO scripts (id, user_id, name, query, db_type, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO scripts (id, user_id, workspace_id, name, query, db_type, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             RETURNING *
             "#,
         )
         .bind(&id)
         .bind(user_id)
+        .bind(workspace_id)
         .bind(name)
         .bind(query)
         .bind(db_type)
@@ -157,6 +629,7 @@ impl ScriptRepository {
         Ok(script)
     }
 
+    /// Scripts created by the user, whatever workspace they are in
     pub async fn find_by_user(pool: &DbPool, user_id: &str) -> Result<Vec<Script>, anyhow::Error> {
         let scripts = sqlx::query_as::<_, Script>(
             "SELECT * FROM scripts WHERE user_id = ? ORDER BY created_at DESC"
@@ -167,12 +640,1365 @@ impl ScriptRepository {
         Ok(scripts)
     }
 
-    pub async fn delete(pool: &DbPool, id: &str, user_id: &str) -> Result<bool, anyhow::Error> {
-        let result = sqlx::query("DELETE FROM scripts WHERE id = ? AND user_id = ?")
+    /// Same as `ConnectionRepository::find_accessible`
+    pub async fn find_accessible(
+        pool: &DbPool,
+        user_id: &str,
+        workspace_id: Option<&str>,
+    ) -> Result<Vec<ScriptAccess>, anyhow::Error> {
+        let scripts = sqlx::query_as::<_, ScriptAccess>(
+            r#"
+            SELECT s.*, m.role FROM scripts s
+            JOIN workspace_members m ON m.workspace_id = s.workspace_id AND m.user_id = ?
+            WHERE (? IS NULL OR s.workspace_id = ?)
+            ORDER BY s.created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(workspace_id)
+        .bind(workspace_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(scripts)
+    }
+
+    pub async fn find_by_id(pool: &DbPool, id: &str, user_id: &str) -> Result<Option<ScriptAccess>, anyhow::Error> {
+        let script = sqlx::query_as::<_, ScriptAccess>(
+            r#"
+            SELECT s.*, m.role FROM scripts s
+            JOIN workspace_members m ON m.workspace_id = s.workspace_id AND m.user_id = ?
+            WHERE s.id = ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(script)
+    }
+
+    /// Same optimistic concurrency rules as `ConnectionRepository::update`
+    pub async fn update(
+        pool: &DbPool,
+        script: &Script,
+        expected_updated_at: DateTime<Utc>,
+    ) -> Result<Option<Script>, anyhow::Error> {
+        let updated = sqlx::query_as::<_, Script>(
+            r#"
+            UPDATE scripts
+            SET name = ?, query = ?, db_type = ?, updated_at = ?
+            WHERE id = ? AND updated_at = ?
+            RETURNING *
+            "#,
+        )
+        .bind(&script.name)
+        .bind(&script.query)
+        .bind(&script.db_type)
+        .bind(Utc::now().to_rfc3339())
+        .bind(&script.id)
+        .bind(expected_updated_at.to_rfc3339())
+        .fetch_optional(pool)
+        .await?;
+        Ok(updated)
+    }
+
+    /// Callers check the user's role in the workspace first
+    pub async fn delete(pool: &DbPool, id: &str) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query("DELETE FROM scripts WHERE id = ?")
             .bind(id)
-            .bind(user_id)
             .execute(pool)
             .await?;
         Ok(result.rows_affected() > 0)
     }
 }
+
+pub struct QueryExecutionRepository;
+
+impl QueryExecutionRepository {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create(
+        pool: &DbPool,
+        user_id: &str,
+        connection_id: &str,
+        query: &str,
+        execution_time_ms: i64,
+        rows_affected: Option<i64>,
+        success: bool,
+        error_message: Option<&str>,
+    ) -> Result<QueryExecution, anyhow::Error> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let execution = sqlx::query_as::<_, QueryExecution>(
+            r#"
+            INSERT INTO query_executions
+            (id, user_id, connection_id, query, execution_time_ms, rows_affected, success, error_message, executed_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(connection_id)
+        .bind(query)
+        .bind(execution_time_ms)
+        .bind(rows_affected)
+        .bind(success)
+        .bind(error_message)
+        .bind(now.to_rfc3339())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(execution)
+    }
+
+    /// Filtered page of a user's history, newest first, plus the total match count
+    pub async fn search(
+        pool: &DbPool,
+        user_id: &str,
+        filter: &HistoryQuery,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<HistoryEntry>, i64), anyhow::Error> {
+        let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM query_executions e");
+        push_history_filters(&mut count, user_id, filter);
+        let (total,): (i64,) = count.build_query_as().fetch_one(pool).await?;
+
+        let mut select = QueryBuilder::<Sqlite>::new(
+            "SELECT e.*, c.name AS connection_name FROM query_executions e \
+             LEFT JOIN connections c ON c.id = e.connection_id",
+        );
+        push_history_filters(&mut select, user_id, filter);
+        select
+            .push(" ORDER BY e.executed_at DESC LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let entries = select.build_query_as::<HistoryEntry>().fetch_all(pool).await?;
+
+        Ok((entries, total))
+    }
+
+    /// Whole history of a user, oldest first
+    pub async fn find_by_user(pool: &DbPool, user_id: &str) -> Result<Vec<QueryExecution>, anyhow::Error> {
+        let executions = sqlx::query_as::<_, QueryExecution>(
+            "SELECT * FROM query_executions WHERE user_id = ? ORDER BY executed_at"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(executions)
+    }
+
+    pub async fn find_by_id(pool: &DbPool, id: &str, user_id: &str) -> Result<Option<HistoryEntry>, anyhow::Error> {
+        let entry = sqlx::query_as::<_, HistoryEntry>(
+            "SELECT e.*, c.name AS connection_name FROM query_executions e \
+             LEFT JOIN connections c ON c.id = e.connection_id \
+             WHERE e.id = ? AND e.user_id = ?"
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(entry)
+    }
+}
+
+fn push_history_filters<'a>(builder: &mut QueryBuilder<'a, Sqlite>, user_id: &'a str, filter: &'a HistoryQuery) {
+    builder.push(" WHERE e.user_id = ").push_bind(user_id);
+
+    if let Some(connection_id) = &filter.connection_id {
+        builder.push(" AND e.connection_id = ").push_bind(connection_id);
+    }
+    if let Some(success) = filter.success {
+        builder.push(" AND e.success = ").push_bind(success);
+    }
+    // executed_at is stored as RFC 3339 in UTC, so text comparison follows time order
+    if let Some(from) = filter.from {
+        builder.push(" AND e.executed_at >= ").push_bind(from.to_rfc3339());
+    }
+    if let Some(to) = filter.to {
+        builder.push(" AND e.executed_at <= ").push_bind(to.to_rfc3339());
+    }
+    if let Some(q) = filter.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", escape_like(q));
+        builder
+            .push(" AND (e.query LIKE ")
+            .push_bind(pattern.clone())
+            .push(" ESCAPE '\\' OR e.error_message LIKE ")
+            .push_bind(pattern)
+            .push(" ESCAPE '\\')");
+    }
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+pub struct SchemaCacheRepository;
+
+impl SchemaCacheRepository {
+    pub async fn find(pool: &DbPool, connection_id: &str) -> Result<Option<SchemaCache>, anyhow::Error> {
+        let cache = sqlx::query_as::<_, SchemaCache>(
+            "SELECT * FROM schema_cache WHERE connection_id = ?"
+        )
+        .bind(connection_id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(cache)
+    }
+
+    pub async fn upsert(pool: &DbPool, connection_id: &str, catalog: &str) -> Result<SchemaCache, anyhow::Error> {
+        let cache = sqlx::query_as::<_, SchemaCache>(
+            r#"
+            INSERT INTO schema_cache (connection_id, catalog, refreshed_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(connection_id) DO UPDATE SET
+                catalog = excluded.catalog,
+                refreshed_at = excluded.refreshed_at
+            RETURNING *
+            "#,
+        )
+        .bind(connection_id)
+        .bind(catalog)
+        .bind(Utc::now().to_rfc3339())
+        .fetch_one(pool)
+        .await?;
+        Ok(cache)
+    }
+
+    pub async fn delete(pool: &DbPool, connection_id: &str) -> Result<(), anyhow::Error> {
+        sqlx::query("DELETE FROM schema_cache WHERE connection_id = ?")
+            .bind(connection_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
+
+pub struct ConnectionPolicyRepository;
+
+impl ConnectionPolicyRepository {
+    pub async fn find(pool: &DbPool, connection_id: &str) -> Result<Option<ConnectionPolicy>, anyhow::Error> {
+        let policy = sqlx::query_as::<_, ConnectionPolicy>(
+            "SELECT * FROM connection_policies WHERE connection_id = ?"
+        )
+        .bind(connection_id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(policy)
+    }
+
+    pub async fn upsert(
+        pool: &DbPool,
+        connection_id: &str,
+        policy: &ExecutionPolicy,
+        updated_by: &str,
+    ) -> Result<ConnectionPolicy, anyhow::Error> {
+        let allowed_statements = policy
+            .allowed_statements
+            .iter()
+            .map(|class| class.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let hours = policy.allowed_hours.as_ref();
+
+        let policy = sqlx::query_as::<_, ConnectionPolicy>(
+            r#"
+            INSERT INTO connection_policies
+                (connection_id, read_only, allowed_statements, max_rows, statement_timeout_secs,
+                 allowed_from, allowed_until, utc_offset_minutes, max_statements, allow_unfiltered_writes,
+                 require_approval, required_approvals, approver_role, updated_by, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(connection_id) DO UPDATE SET
+                read_only = excluded.read_only,
+                allowed_statements = excluded.allowed_statements,
+                max_rows = excluded.max_rows,
+                statement_timeout_secs = excluded.statement_timeout_secs,
+                allowed_from = excluded.allowed_from,
+                allowed_until = excluded.allowed_until,
+                utc_offset_minutes = excluded.utc_offset_minutes,
+                max_statements = excluded.max_statements,
+                allow_unfiltered_writes = excluded.allow_unfiltered_writes,
+                require_approval = excluded.require_approval,
+                required_approvals = excluded.required_approvals,
+                approver_role = excluded.approver_role,
+                updated_by = excluded.updated_by,
+                updated_at = excluded.updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(connection_id)
+        .bind(policy.read_only)
+        .bind(allowed_statements)
+        .bind(policy.max_rows)
+        .bind(policy.statement_timeout_secs)
+        .bind(hours.map(|h| h.from.as_str()))
+        .bind(hours.map(|h| h.until.as_str()))
+        .bind(hours.map(|h| h.utc_offset_minutes).unwrap_or(0))
+        .bind(policy.max_statements)
+        .bind(policy.allow_unfiltered_writes)
+        .bind(policy.require_approval)
+        .bind(policy.required_approvals)
+        .bind(policy.approver_role)
+        .bind(updated_by)
+        .bind(Utc::now().to_rfc3339())
+        .fetch_one(pool)
+        .await?;
+        Ok(policy)
+    }
+}
+
+pub struct SessionRepository;
+
+impl SessionRepository {
+    /// Opens a session together with its first refresh token
+    pub async fn create(
+        pool: &DbPool,
+        user_id: &str,
+        user_agent: Option<&str>,
+        ip: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Session, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        let session = sqlx::query_as::<_, Session>(
+            r#"
+            INSERT INTO sessions (id, user_id, user_agent, ip, created_at, last_used_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(ip)
+        .bind(&now)
+        .bind(&now)
+        .bind(expires_at.to_rfc3339())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO refresh_tokens (id, session_id, token_hash, created_at, expires_at) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&session.id)
+        .bind(token_hash)
+        .bind(&now)
+        .bind(expires_at.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(session)
+    }
+
+    pub async fn find_by_id(pool: &DbPool, id: &str) -> Result<Option<Session>, anyhow::Error> {
+        let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(session)
+    }
+
+    /// Sessions of the user that are neither revoked nor expired, most recently used first
+    pub async fn find_active_by_user(pool: &DbPool, user_id: &str) -> Result<Vec<Session>, anyhow::Error> {
+        let sessions = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT * FROM sessions
+            WHERE user_id = ? AND revoked_at IS NULL AND expires_at > ?
+            ORDER BY COALESCE(last_used_at, created_at) DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(Utc::now().to_rfc3339())
+        .fetch_all(pool)
+        .await?;
+        Ok(sessions)
+    }
+
+    /// Records activity on the session. Writes at most once per `min_interval`
+    /// so authenticated requests don't all turn into database writes.
+    pub async fn touch(
+        pool: &DbPool,
+        id: &str,
+        ip: &str,
+        min_interval: chrono::Duration,
+    ) -> Result<(), anyhow::Error> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE sessions SET last_used_at = ?, ip = ?
+            WHERE id = ? AND (last_used_at IS NULL OR last_used_at < ? OR ip IS NOT ?)
+            "#,
+        )
+        .bind(now.to_rfc3339())
+        .bind(ip)
+        .bind(id)
+        .bind((now - min_interval).to_rfc3339())
+        .bind(ip)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn find_refresh_token(pool: &DbPool, token_hash: &str) -> Result<Option<RefreshToken>, anyhow::Error> {
+        let token = sqlx::query_as::<_, RefreshToken>("SELECT * FROM refresh_tokens WHERE token_hash = ?")
+            .bind(token_hash)
+            .fetch_optional(pool)
+            .await?;
+        Ok(token)
+    }
+
+    /// Marks `old` as used and stores its replacement, extending the session.
+    /// Returns false when `old` was already used, e.g. by a concurrent request.
+    pub async fn rotate_refresh_token(
+        pool: &DbPool,
+        old: &RefreshToken,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        let used = sqlx::query("UPDATE refresh_tokens SET used_at = ? WHERE id = ? AND used_at IS NULL")
+            .bind(&now)
+            .bind(&old.id)
+            .execute(&mut *tx)
+            .await?;
+        if used.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "INSERT INTO refresh_tokens (id, session_id, token_hash, created_at, expires_at) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&old.session_id)
+        .bind(token_hash)
+        .bind(&now)
+        .bind(expires_at.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE sessions SET expires_at = ? WHERE id = ?")
+            .bind(expires_at.to_rfc3339())
+            .bind(&old.session_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Revokes the session; its refresh tokens and access tokens stop working
+    pub async fn revoke(pool: &DbPool, id: &str) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query("UPDATE sessions SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes one session of the user; false if it doesn't exist, belongs to
+    /// someone else or was already revoked
+    pub async fn revoke_for_user(pool: &DbPool, id: &str, user_id: &str) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query(
+            "UPDATE sessions SET revoked_at = ? WHERE id = ? AND user_id = ? AND revoked_at IS NULL"
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes every session of the user except `keep`, returning how many were revoked
+    pub async fn revoke_others(pool: &DbPool, user_id: &str, keep: &str) -> Result<u64, anyhow::Error> {
+        let result = sqlx::query(
+            "UPDATE sessions SET revoked_at = ? WHERE user_id = ? AND id <> ? AND revoked_at IS NULL"
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(user_id)
+        .bind(keep)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Adds an access token to the revocation list until it would have expired anyway
+    pub async fn revoke_access_token(pool: &DbPool, jti: &str, expires_at: DateTime<Utc>) -> Result<(), anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < ?")
+            .bind(&now)
+            .execute(pool)
+            .await?;
+
+        sqlx::query("INSERT OR IGNORE INTO revoked_tokens (jti, expires_at, revoked_at) VALUES (?, ?, ?)")
+            .bind(jti)
+            .bind(expires_at.to_rfc3339())
+            .bind(&now)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether the access token was revoked itself or through its session
+    pub async fn is_access_revoked(pool: &DbPool, jti: &str, session_id: &str) -> Result<bool, anyhow::Error> {
+        let (revoked,): (bool,) = sqlx::query_as(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = ?)
+                OR EXISTS (SELECT 1 FROM sessions WHERE id = ? AND revoked_at IS NOT NULL)
+            "#,
+        )
+        .bind(jti)
+        .bind(session_id)
+        .fetch_one(pool)
+        .await?;
+        Ok(revoked)
+    }
+}
+
+pub struct AccessTokenRepository;
+
+impl AccessTokenRepository {
+    pub async fn create(
+        pool: &DbPool,
+        user_id: &str,
+        name: &str,
+        token_hash: &str,
+        scopes: &str,
+        connection_ids: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<PersonalAccessToken, anyhow::Error> {
+        let token = sqlx::query_as::<_, PersonalAccessToken>(
+            r#"
+            INSERT INTO personal_access_tokens (id, user_id, name, token_hash, scopes, connection_ids, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(name)
+        .bind(token_hash)
+        .bind(scopes)
+        .bind(connection_ids)
+        .bind(Utc::now().to_rfc3339())
+        .bind(expires_at.map(|e| e.to_rfc3339()))
+        .fetch_one(pool)
+        .await?;
+        Ok(token)
+    }
+
+    pub async fn find_by_user(pool: &DbPool, user_id: &str) -> Result<Vec<PersonalAccessToken>, anyhow::Error> {
+        let tokens = sqlx::query_as::<_, PersonalAccessToken>(
+            "SELECT * FROM personal_access_tokens WHERE user_id = ? ORDER BY created_at DESC"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(tokens)
+    }
+
+    /// Token matching the hash, if it hasn't expired
+    pub async fn find_valid_by_hash(pool: &DbPool, token_hash: &str) -> Result<Option<PersonalAccessToken>, anyhow::Error> {
+        let token = sqlx::query_as::<_, PersonalAccessToken>(
+            "SELECT * FROM personal_access_tokens WHERE token_hash = ? AND (expires_at IS NULL OR expires_at > ?)"
+        )
+        .bind(token_hash)
+        .bind(Utc::now().to_rfc3339())
+        .fetch_optional(pool)
+        .await?;
+        Ok(token)
+    }
+
+    /// Same throttling as SessionRepository::touch
+    pub async fn touch(pool: &DbPool, id: &str, min_interval: chrono::Duration) -> Result<(), anyhow::Error> {
+        let now = Utc::now();
+
+        sqlx::query(
+            "UPDATE personal_access_tokens SET last_used_at = ? WHERE id = ? AND (last_used_at IS NULL OR last_used_at < ?)"
+        )
+        .bind(now.to_rfc3339())
+        .bind(id)
+        .bind((now - min_interval).to_rfc3339())
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(pool: &DbPool, id: &str, user_id: &str) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query("DELETE FROM personal_access_tokens WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+pub struct EmailTokenRepository;
+
+impl EmailTokenRepository {
+    /// Stores a new token, replacing the unused ones the user had for the same purpose
+    pub async fn create(
+        pool: &DbPool,
+        user_id: &str,
+        purpose: &str,
+        token_hash: &str,
+        new_email: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), anyhow::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("DELETE FROM email_tokens WHERE user_id = ? AND purpose = ? AND used_at IS NULL")
+            .bind(user_id)
+            .bind(purpose)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO email_tokens (id, user_id, purpose, token_hash, new_email, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(purpose)
+        .bind(token_hash)
+        .bind(new_email)
+        .bind(Utc::now().to_rfc3339())
+        .bind(expires_at.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Latest token of this purpose sent to the user, used or not
+    pub async fn find_latest(pool: &DbPool, user_id: &str, purpose: &str) -> Result<Option<EmailToken>, anyhow::Error> {
+        let token = sqlx::query_as::<_, EmailToken>(
+            "SELECT * FROM email_tokens WHERE user_id = ? AND purpose = ? ORDER BY created_at DESC LIMIT 1"
+        )
+        .bind(user_id)
+        .bind(purpose)
+        .fetch_optional(pool)
+        .await?;
+        Ok(token)
+    }
+
+    /// Unused, unexpired token matching the hash, left as it is
+    pub async fn find_valid(pool: &DbPool, token_hash: &str, purpose: &str) -> Result<Option<EmailToken>, anyhow::Error> {
+        let token = sqlx::query_as::<_, EmailToken>(
+            "SELECT * FROM email_tokens WHERE token_hash = ? AND purpose = ? AND used_at IS NULL AND expires_at > ?"
+        )
+        .bind(token_hash)
+        .bind(purpose)
+        .bind(Utc::now().to_rfc3339())
+        .fetch_optional(pool)
+        .await?;
+        Ok(token)
+    }
+
+    /// Marks the token as used and returns it, unless it was already used or expired.
+    /// Done in one statement so the same token can't be consumed twice.
+    pub async fn consume(pool: &DbPool, token_hash: &str, purpose: &str) -> Result<Option<EmailToken>, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+
+        let token = sqlx::query_as::<_, EmailToken>(
+            r#"
+            UPDATE email_tokens SET used_at = ?
+            WHERE token_hash = ? AND purpose = ? AND used_at IS NULL AND expires_at > ?
+            RETURNING *
+            "#,
+        )
+        .bind(&now)
+        .bind(token_hash)
+        .bind(purpose)
+        .bind(&now)
+        .fetch_optional(pool)
+        .await?;
+        Ok(token)
+    }
+}
+
+pub struct WorkspaceRepository;
+
+impl WorkspaceRepository {
+    /// Creates a shared workspace with `owner_id` as its first owner
+    pub async fn create(pool: &DbPool, name: &str, owner_id: &str) -> Result<WorkspaceAccess, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        let workspace = sqlx::query_as::<_, Workspace>(
+            "INSERT INTO workspaces (id, name, created_at, updated_at) VALUES (?, ?, ?, ?) RETURNING *"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(name)
+        .bind(&now)
+        .bind(&now)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("INSERT INTO workspace_members (workspace_id, user_id, role, created_at) VALUES (?, ?, ?, ?)")
+            .bind(&workspace.id)
+            .bind(owner_id)
+            .bind(WorkspaceRole::Owner)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(WorkspaceAccess { workspace, role: WorkspaceRole::Owner })
+    }
+
+    pub async fn find_by_user(pool: &DbPool, user_id: &str) -> Result<Vec<WorkspaceAccess>, anyhow::Error> {
+        let workspaces = sqlx::query_as::<_, WorkspaceAccess>(
+            r#"
+            SELECT w.*, m.role FROM workspaces w
+            JOIN workspace_members m ON m.workspace_id = w.id
+            WHERE m.user_id = ?
+            ORDER BY w.personal_user_id IS NULL, w.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(workspaces)
+    }
+
+    /// The workspace and the user's role in it; None when they are not a member
+    pub async fn find_for_member(pool: &DbPool, id: &str, user_id: &str) -> Result<Option<WorkspaceAccess>, anyhow::Error> {
+        let workspace = sqlx::query_as::<_, WorkspaceAccess>(
+            r#"
+            SELECT w.*, m.role FROM workspaces w
+            JOIN workspace_members m ON m.workspace_id = w.id
+            WHERE w.id = ? AND m.user_id = ?
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(workspace)
+    }
+
+    pub async fn find_by_id(pool: &DbPool, id: &str) -> Result<Option<Workspace>, anyhow::Error> {
+        let workspace = sqlx::query_as::<_, Workspace>("SELECT * FROM workspaces WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(workspace)
+    }
+
+    pub async fn find_personal(pool: &DbPool, user_id: &str) -> Result<Option<Workspace>, anyhow::Error> {
+        let workspace = sqlx::query_as::<_, Workspace>("SELECT * FROM workspaces WHERE personal_user_id = ?")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(workspace)
+    }
+
+    pub async fn rename(pool: &DbPool, id: &str, name: &str) -> Result<(), anyhow::Error> {
+        sqlx::query("UPDATE workspaces SET name = ?, updated_at = ? WHERE id = ?")
+            .bind(name)
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes the workspace with its connections and scripts
+    pub async fn delete(pool: &DbPool, id: &str) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query("DELETE FROM workspaces WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Shared workspaces where the user is the only owner but not the only member
+    pub async fn find_sole_owned(pool: &DbPool, user_id: &str) -> Result<Vec<Workspace>, anyhow::Error> {
+        let workspaces = sqlx::query_as::<_, Workspace>(
+            r#"
+            SELECT w.* FROM workspaces w
+            JOIN workspace_members m ON m.workspace_id = w.id AND m.user_id = ? AND m.role = 'owner'
+            WHERE w.personal_user_id IS NULL
+              AND NOT EXISTS (SELECT 1 FROM workspace_members o WHERE o.workspace_id = w.id AND o.user_id <> m.user_id AND o.role = 'owner')
+              AND EXISTS (SELECT 1 FROM workspace_members o WHERE o.workspace_id = w.id AND o.user_id <> m.user_id)
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(workspaces)
+    }
+
+    pub async fn list_members(pool: &DbPool, workspace_id: &str) -> Result<Vec<WorkspaceMember>, anyhow::Error> {
+        let members = sqlx::query_as::<_, WorkspaceMember>(
+            r#"
+            SELECT u.id AS user_id, u.username, u.email, m.role, m.created_at AS joined_at
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.workspace_id = ?
+            ORDER BY m.created_at
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(members)
+    }
+
+    pub async fn member_role(pool: &DbPool, workspace_id: &str, user_id: &str) -> Result<Option<WorkspaceRole>, anyhow::Error> {
+        let role = sqlx::query_as::<_, (WorkspaceRole,)>(
+            "SELECT role FROM workspace_members WHERE workspace_id = ? AND user_id = ?"
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(role.map(|(role,)| role))
+    }
+
+    /// Adds the user; false when they already are a member
+    pub async fn add_member(pool: &DbPool, workspace_id: &str, user_id: &str, role: WorkspaceRole) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO workspace_members (workspace_id, user_id, role, created_at) VALUES (?, ?, ?, ?)"
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(role)
+        .bind(Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Changes a member's role. Demoting the last owner is refused: returns false.
+    pub async fn update_member_role(pool: &DbPool, workspace_id: &str, user_id: &str, role: WorkspaceRole) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE workspace_members SET role = ?
+            WHERE workspace_id = ? AND user_id = ?
+              AND (role <> 'owner' OR ? = 'owner'
+                   OR EXISTS (SELECT 1 FROM workspace_members o WHERE o.workspace_id = ? AND o.user_id <> ? AND o.role = 'owner'))
+            "#,
+        )
+        .bind(role)
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(role)
+        .bind(workspace_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Whether the account using this email address already belongs to the workspace
+    pub async fn has_member_with_email(pool: &DbPool, workspace_id: &str, email: &str) -> Result<bool, anyhow::Error> {
+        let (exists,): (bool,) = sqlx::query_as(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM workspace_members m JOIN users u ON u.id = m.user_id
+                WHERE m.workspace_id = ? AND u.email = ? COLLATE NOCASE
+            )
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(email)
+        .fetch_one(pool)
+        .await?;
+        Ok(exists)
+    }
+
+    /// Removes a member, unless they are the last owner: returns false then
+    pub async fn remove_member(pool: &DbPool, workspace_id: &str, user_id: &str) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM workspace_members
+            WHERE workspace_id = ? AND user_id = ?
+              AND (role <> 'owner'
+                   OR EXISTS (SELECT 1 FROM workspace_members o WHERE o.workspace_id = ? AND o.user_id <> ? AND o.role = 'owner'))
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(workspace_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Result of accepting an invitation
+pub enum InvitationAcceptance {
+    Accepted(WorkspaceInvitation),
+    AlreadyMember,
+    // Accepted, revoked or expired in the meantime
+    Unavailable,
+}
+
+pub struct InvitationRepository;
+
+impl InvitationRepository {
+    /// Creates an invitation. A new one for the same address replaces the
+    /// pending ones, so only the latest email works.
+    pub async fn create(
+        pool: &DbPool,
+        workspace_id: &str,
+        email: Option<&str>,
+        role: WorkspaceRole,
+        invited_by: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<WorkspaceInvitation, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        if let Some(email) = email {
+            sqlx::query(
+                r#"
+                UPDATE workspace_invitations SET revoked_at = ?
+                WHERE workspace_id = ? AND email = ? COLLATE NOCASE AND accepted_at IS NULL AND revoked_at IS NULL
+                "#,
+            )
+            .bind(&now)
+            .bind(workspace_id)
+            .bind(email)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let invitation = sqlx::query_as::<_, WorkspaceInvitation>(
+            r#"
+            INSERT INTO workspace_invitations (id, workspace_id, email, role, invited_by, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(workspace_id)
+        .bind(email)
+        .bind(role)
+        .bind(invited_by)
+        .bind(&now)
+        .bind(expires_at.to_rfc3339())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(invitation)
+    }
+
+    pub async fn find_by_id(pool: &DbPool, id: &str) -> Result<Option<WorkspaceInvitation>, anyhow::Error> {
+        let invitation = sqlx::query_as::<_, WorkspaceInvitation>("SELECT * FROM workspace_invitations WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(invitation)
+    }
+
+    pub async fn find_pending(pool: &DbPool, workspace_id: &str) -> Result<Vec<WorkspaceInvitation>, anyhow::Error> {
+        let invitations = sqlx::query_as::<_, WorkspaceInvitation>(
+            r#"
+            SELECT * FROM workspace_invitations
+            WHERE workspace_id = ? AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > ?
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(Utc::now().to_rfc3339())
+        .fetch_all(pool)
+        .await?;
+        Ok(invitations)
+    }
+
+    /// Revokes a pending invitation; None when there is no such pending one
+    pub async fn revoke(pool: &DbPool, workspace_id: &str, id: &str) -> Result<Option<WorkspaceInvitation>, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let invitation = sqlx::query_as::<_, WorkspaceInvitation>(
+            r#"
+            UPDATE workspace_invitations SET revoked_at = ?
+            WHERE id = ? AND workspace_id = ? AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > ?
+            RETURNING *
+            "#,
+        )
+        .bind(&now)
+        .bind(id)
+        .bind(workspace_id)
+        .bind(&now)
+        .fetch_optional(pool)
+        .await?;
+        Ok(invitation)
+    }
+
+    /// Uses the invitation and adds the user with its role, recording the
+    /// acceptance in the audit log; all or nothing
+    pub async fn accept(
+        pool: &DbPool,
+        id: &str,
+        user_id: &str,
+        ip_address: &str,
+    ) -> Result<InvitationAcceptance, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        let invitation = sqlx::query_as::<_, WorkspaceInvitation>(
+            r#"
+            UPDATE workspace_invitations SET accepted_at = ?, accepted_by = ?
+            WHERE id = ? AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > ?
+            RETURNING *
+            "#,
+        )
+        .bind(&now)
+        .bind(user_id)
+        .bind(id)
+        .bind(&now)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(invitation) = invitation else {
+            return Ok(InvitationAcceptance::Unavailable);
+        };
+
+        let added = sqlx::query(
+            "INSERT OR IGNORE INTO workspace_members (workspace_id, user_id, role, created_at) VALUES (?, ?, ?, ?)"
+        )
+        .bind(&invitation.workspace_id)
+        .bind(user_id)
+        .bind(invitation.role)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+        // Dropping the transaction leaves the invitation for someone else
+        if added.rows_affected() == 0 {
+            return Ok(InvitationAcceptance::AlreadyMember);
+        }
+
+        AuditRepository::record(
+            &mut *tx,
+            &invitation.workspace_id,
+            AUDIT_INVITATION_ACCEPTED,
+            &NewAuditEvent {
+                actor_id: Some(user_id),
+                target_user_id: Some(user_id),
+                role: Some(invitation.role),
+                invitation_id: Some(&invitation.id),
+                ip_address: Some(ip_address),
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok(InvitationAcceptance::Accepted(invitation))
+    }
+}
+
+pub struct AuditRepository;
+
+impl AuditRepository {
+    pub async fn record<'e, E>(
+        executor: E,
+        workspace_id: &str,
+        action: &str,
+        event: &NewAuditEvent<'_>,
+    ) -> Result<(), anyhow::Error>
+    where
+        E: sqlx::Executor<'e, Database = Sqlite>,
+    {
+        sqlx::query(
+            r#"
+            INSERT INTO workspace_audit
+            (id, workspace_id, actor_id, action, target_user_id, role, invitation_id, ip_address, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(workspace_id)
+        .bind(event.actor_id)
+        .bind(action)
+        .bind(event.target_user_id)
+        .bind(event.role)
+        .bind(event.invitation_id)
+        .bind(event.ip_address)
+        .bind(Utc::now().to_rfc3339())
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    /// Newest first, with the usernames of the accounts that still exist
+    pub async fn find_by_workspace(
+        pool: &DbPool,
+        workspace_id: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<AuditEvent>, i64), anyhow::Error> {
+        let events = sqlx::query_as::<_, AuditEvent>(
+            r#"
+            SELECT a.id, a.action, a.actor_id, actor.username AS actor_username,
+                   a.target_user_id, target.username AS target_username,
+                   a.role, a.invitation_id, a.ip_address, a.created_at
+            FROM workspace_audit a
+            LEFT JOIN users actor ON actor.id = a.actor_id
+            LEFT JOIN users target ON target.id = a.target_user_id
+            WHERE a.workspace_id = ?
+            ORDER BY a.created_at DESC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        let (total,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM workspace_audit WHERE workspace_id = ?")
+            .bind(workspace_id)
+            .fetch_one(pool)
+            .await?;
+        Ok((events, total))
+    }
+}
+
+pub enum ReviewOutcome {
+    Recorded(Box<ChangeRequest>),
+    AlreadyReviewed,
+    // Decided, cancelled or run in the meantime
+    NotPending,
+}
+
+pub struct ChangeRequestRepository;
+
+impl ChangeRequestRepository {
+    pub async fn create(
+        pool: &DbPool,
+        connection: &Connection,
+        requested_by: &str,
+        query: &str,
+        justification: &str,
+        summary: &str,
+        policy: &ExecutionPolicy,
+    ) -> Result<ChangeRequest, anyhow::Error> {
+        let request = sqlx::query_as::<_, ChangeRequest>(
+            r#"
+            INSERT INTO change_requests
+                (id, workspace_id, connection_id, requested_by, query, justification, summary, status,
+                 required_approvals, approver_role, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&connection.workspace_id)
+        .bind(&connection.id)
+        .bind(requested_by)
+        .bind(query)
+        .bind(justification)
+        .bind(summary)
+        .bind(ChangeRequestStatus::Pending)
+        .bind(policy.required_approvals)
+        .bind(policy.approver_role)
+        .bind(Utc::now().to_rfc3339())
+        .fetch_one(pool)
+        .await?;
+        Ok(request)
+    }
+
+    pub async fn find_by_id(
+        pool: &DbPool,
+        connection_id: &str,
+        id: &str,
+    ) -> Result<Option<ChangeRequest>, anyhow::Error> {
+        let request = sqlx::query_as::<_, ChangeRequest>(
+            "SELECT * FROM change_requests WHERE id = ? AND connection_id = ?"
+        )
+        .bind(id)
+        .bind(connection_id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(request)
+    }
+
+    /// Newest first
+    pub async fn find_by_connection(
+        pool: &DbPool,
+        connection_id: &str,
+        status: Option<ChangeRequestStatus>,
+        limit: i64,
+    ) -> Result<Vec<ChangeRequest>, anyhow::Error> {
+        let mut builder = QueryBuilder::<Sqlite>::new("SELECT * FROM change_requests WHERE connection_id = ");
+        builder.push_bind(connection_id);
+        if let Some(status) = status {
+            builder.push(" AND status = ").push_bind(status);
+        }
+        builder.push(" ORDER BY created_at DESC LIMIT ").push_bind(limit);
+
+        let requests = builder.build_query_as::<ChangeRequest>().fetch_all(pool).await?;
+        Ok(requests)
+    }
+
+    pub async fn find_reviews(pool: &DbPool, id: &str) -> Result<Vec<ChangeRequestReview>, anyhow::Error> {
+        let reviews = sqlx::query_as::<_, ChangeRequestReview>(
+            r#"
+            SELECT r.reviewer_id, u.username AS reviewer_username, r.decision, r.comment, r.created_at
+            FROM change_request_reviews r
+            LEFT JOIN users u ON u.id = r.reviewer_id
+            WHERE r.change_request_id = ?
+            ORDER BY r.created_at
+            "#,
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await?;
+        Ok(reviews)
+    }
+
+    /// Records a reviewer's decision on a pending request. One rejection
+    /// closes it; it's approved once it has the approvals it asked for.
+    pub async fn review(
+        pool: &DbPool,
+        id: &str,
+        reviewer_id: &str,
+        decision: ReviewDecision,
+        comment: Option<&str>,
+    ) -> Result<ReviewOutcome, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        // Writing first takes the database lock, so concurrent reviews of the
+        // same request are counted one after the other
+        let pending = sqlx::query_as::<_, ChangeRequest>(
+            "UPDATE change_requests SET status = status WHERE id = ? AND status = ? RETURNING *"
+        )
+        .bind(id)
+        .bind(ChangeRequestStatus::Pending)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(request) = pending else {
+            return Ok(ReviewOutcome::NotPending);
+        };
+
+        let added = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO change_request_reviews (id, change_request_id, reviewer_id, decision, comment, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(id)
+        .bind(reviewer_id)
+        .bind(decision)
+        .bind(comment)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+        if added.rows_affected() == 0 {
+            return Ok(ReviewOutcome::AlreadyReviewed);
+        }
+
+        let (approvals,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM change_request_reviews WHERE change_request_id = ? AND decision = ?"
+        )
+        .bind(id)
+        .bind(ReviewDecision::Approved)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let status = match decision {
+            ReviewDecision::Rejected => Some(ChangeRequestStatus::Rejected),
+            ReviewDecision::Approved if approvals >= request.required_approvals => Some(ChangeRequestStatus::Approved),
+            ReviewDecision::Approved => None,
+        };
+        let request = match status {
+            Some(status) => {
+                sqlx::query_as::<_, ChangeRequest>(
+                    "UPDATE change_requests SET status = ?, decided_at = ? WHERE id = ? RETURNING *"
+                )
+                .bind(status)
+                .bind(&now)
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?
+            }
+            None => request,
+        };
+
+        tx.commit().await?;
+        Ok(ReviewOutcome::Recorded(Box::new(request)))
+    }
+
+    /// Withdraws a request that hasn't run yet
+    pub async fn cancel(pool: &DbPool, id: &str) -> Result<Option<ChangeRequest>, anyhow::Error> {
+        let request = sqlx::query_as::<_, ChangeRequest>(
+            r#"
+            UPDATE change_requests SET status = ?, decided_at = COALESCE(decided_at, ?)
+            WHERE id = ? AND status IN (?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(ChangeRequestStatus::Cancelled)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .bind(ChangeRequestStatus::Pending)
+        .bind(ChangeRequestStatus::Approved)
+        .fetch_optional(pool)
+        .await?;
+        Ok(request)
+    }
+
+    /// Moves an approved request to executing. Only one caller ever gets it
+    /// back, which is what makes the run happen at most once.
+    pub async fn claim(pool: &DbPool, id: &str, user_id: &str) -> Result<Option<ChangeRequest>, anyhow::Error> {
+        let request = sqlx::query_as::<_, ChangeRequest>(
+            r#"
+            UPDATE change_requests SET status = ?, executed_by = ?, executed_at = ?
+            WHERE id = ? AND status = ?
+            RETURNING *
+            "#,
+        )
+        .bind(ChangeRequestStatus::Executing)
+        .bind(user_id)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .bind(ChangeRequestStatus::Approved)
+        .fetch_optional(pool)
+        .await?;
+        Ok(request)
+    }
+
+    /// Attaches the outcome of the run to a claimed request
+    pub async fn finish(
+        pool: &DbPool,
+        id: &str,
+        result: Result<&str, &str>,
+    ) -> Result<ChangeRequest, anyhow::Error> {
+        let (status, result, error) = match result {
+            Ok(result) => (ChangeRequestStatus::Executed, Some(result), None),
+            Err(error) => (ChangeRequestStatus::Failed, None, Some(error)),
+        };
+        let request = sqlx::query_as::<_, ChangeRequest>(
+            "UPDATE change_requests SET status = ?, result = ?, error = ? WHERE id = ? AND status = ? RETURNING *"
+        )
+        .bind(status)
+        .bind(result)
+        .bind(error)
+        .bind(id)
+        .bind(ChangeRequestStatus::Executing)
+        .fetch_one(pool)
+        .await?;
+        Ok(request)
+    }
+}
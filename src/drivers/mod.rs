@@ -0,0 +1,481 @@
+pub mod mongodb;
+pub mod mysql;
+pub mod postgres;
+pub mod redis;
+pub mod sqlite;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::models::Connection;
+
+// Maximum time allowed to open a connection against a target database
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Result of running a query against a target database, before timing and
+/// bookkeeping are added by the API layer.
+#[derive(Debug, Default)]
+pub struct QueryOutput {
+    pub columns: Vec<String>,
+    pub rows: Vec<serde_json::Value>,
+    pub rows_affected: Option<u64>,
+    // Original documents for document stores such as MongoDB
+    pub documents: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryLanguage {
+    Sql,
+    Redis,
+    Mongo,
+}
+
+/// Features a driver supports, so clients know what to offer per connection
+#[derive(Debug, Clone, Serialize)]
+pub struct DriverCapabilities {
+    pub transactions: bool,
+    pub explain: bool,
+    pub schemas: bool,
+    pub streaming: bool,
+    pub cancel: bool,
+    pub introspection: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DriverInfo {
+    pub name: &'static str,
+    pub display_name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub query_language: QueryLanguage,
+    pub default_port: Option<u16>,
+    // File based engines such as SQLite have no host or credentials
+    pub requires_host: bool,
+    pub capabilities: DriverCapabilities,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionTest {
+    pub latency_ms: i64,
+    pub server_version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+    pub ordinal: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignKeyInfo {
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    pub referenced_schema: Option<String>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexInfo {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstraintInfo {
+    pub name: Option<String>,
+    // primary key, foreign key, unique, check, exclusion
+    pub kind: String,
+    pub columns: Vec<String>,
+    pub definition: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableInfo {
+    pub schema: Option<String>,
+    pub name: String,
+    // table, view, materialized view, collection, ...
+    pub kind: String,
+    #[serde(default)]
+    pub columns: Vec<ColumnInfo>,
+    #[serde(default)]
+    pub primary_key: Vec<String>,
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKeyInfo>,
+    #[serde(default)]
+    pub indexes: Vec<IndexInfo>,
+    #[serde(default)]
+    pub constraints: Vec<ConstraintInfo>,
+}
+
+impl TableInfo {
+    pub fn new(schema: Option<String>, name: String, kind: &str) -> Self {
+        Self {
+            schema,
+            name,
+            kind: kind.to_string(),
+            columns: Vec::new(),
+            primary_key: Vec::new(),
+            foreign_keys: Vec::new(),
+            indexes: Vec::new(),
+            constraints: Vec::new(),
+        }
+    }
+
+    /// Adds a column to the named constraint, creating it on first sight.
+    /// Catalog queries return one row per constraint column, in order.
+    pub(crate) fn add_constraint_column(&mut self, name: &str, kind: &str, column: Option<String>) {
+        let position = match self.constraints.iter().position(|c| c.name.as_deref() == Some(name)) {
+            Some(position) => position,
+            None => {
+                self.constraints.push(ConstraintInfo {
+                    name: Some(name.to_string()),
+                    kind: kind.to_string(),
+                    columns: Vec::new(),
+                    definition: None,
+                });
+                self.constraints.len() - 1
+            }
+        };
+
+        if let Some(column) = column {
+            if kind == "primary key" {
+                self.primary_key.push(column.clone());
+            }
+            self.constraints[position].columns.push(column);
+        }
+    }
+
+    /// Same as `add_constraint_column` for one column pair of a foreign key
+    pub(crate) fn add_foreign_key_column(
+        &mut self,
+        name: &str,
+        column: String,
+        referenced_schema: Option<String>,
+        referenced_table: String,
+        referenced_column: Option<String>,
+    ) {
+        let position = match self.foreign_keys.iter().position(|fk| fk.name.as_deref() == Some(name)) {
+            Some(position) => position,
+            None => {
+                self.foreign_keys.push(ForeignKeyInfo {
+                    name: Some(name.to_string()),
+                    columns: Vec::new(),
+                    referenced_schema,
+                    referenced_table,
+                    referenced_columns: Vec::new(),
+                });
+                self.foreign_keys.len() - 1
+            }
+        };
+
+        let foreign_key = &mut self.foreign_keys[position];
+        foreign_key.columns.push(column);
+        foreign_key.referenced_columns.extend(referenced_column);
+    }
+
+    /// Same as `add_constraint_column` for one column of an index
+    pub(crate) fn add_index_column(&mut self, name: &str, unique: bool, primary: bool, column: Option<String>) {
+        let position = match self.indexes.iter().position(|i| i.name == name) {
+            Some(position) => position,
+            None => {
+                self.indexes.push(IndexInfo {
+                    name: name.to_string(),
+                    columns: Vec::new(),
+                    unique,
+                    primary,
+                });
+                self.indexes.len() - 1
+            }
+        };
+        self.indexes[position].columns.extend(column);
+    }
+}
+
+/// Structure of what a connection exposes: databases, schemas and the tables,
+/// views or collections of the database the connection points at
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Catalog {
+    pub databases: Vec<String>,
+    pub schemas: Vec<String>,
+    pub tables: Vec<TableInfo>,
+}
+
+pub(crate) type TablePositions = HashMap<(Option<String>, String), usize>;
+
+impl Catalog {
+    pub(crate) fn table_positions(&self) -> TablePositions {
+        self.tables
+            .iter()
+            .enumerate()
+            .map(|(i, table)| ((table.schema.clone(), table.name.clone()), i))
+            .collect()
+    }
+
+    /// Table a catalog query row belongs to, read from its `table_schema` and
+    /// `table_name` columns
+    pub(crate) fn table_for_row(&mut self, positions: &TablePositions, row: &serde_json::Value) -> Option<&mut TableInfo> {
+        let key = (row_str(row, "table_schema"), row_str(row, "table_name")?);
+        let position = *positions.get(&key)?;
+        self.tables.get_mut(position)
+    }
+}
+
+/// Cancels the statement currently running on a session from elsewhere
+#[axum::async_trait]
+pub trait QueryCanceller: Send + Sync {
+    async fn cancel(&self) -> Result<(), anyhow::Error>;
+}
+
+pub type CancelToken = Arc<dyn QueryCanceller>;
+
+/// Parts of a connection's execution policy the server can enforce itself
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SessionLimits {
+    pub read_only: bool,
+    pub statement_timeout: Option<Duration>,
+    // Rows a query may return; the driver keeps one row past it and drops the rest
+    pub max_rows: Option<usize>,
+}
+
+/// An open connection to a target database
+#[axum::async_trait]
+pub trait DriverSession: Send {
+    async fn server_version(&mut self) -> Result<String, anyhow::Error>;
+
+    async fn execute(&mut self, query: &str) -> Result<QueryOutput, anyhow::Error>;
+
+    async fn introspect(&mut self) -> Result<Catalog, anyhow::Error>;
+
+    /// Applies the limits to the session on the server, for engines that have
+    /// such settings. The checks made before a query is sent apply either way.
+    async fn restrict(&mut self, _limits: &SessionLimits) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    /// Handle that can abort a running `execute`, when the engine supports it
+    fn cancel_token(&self) -> Option<CancelToken> {
+        None
+    }
+
+    async fn close(self: Box<Self>);
+}
+
+#[axum::async_trait]
+pub trait DatabaseDriver: Send + Sync {
+    fn info(&self) -> DriverInfo;
+
+    async fn connect(&self, conn: &Connection, password: &str) -> Result<Box<dyn DriverSession>, anyhow::Error>;
+
+    /// Opens a connection, round-trips to the server and closes it again
+    async fn test(&self, conn: &Connection, password: &str) -> Result<ConnectionTest, anyhow::Error> {
+        let started = Instant::now();
+        let mut session = self.connect(conn, password).await?;
+        let version = session.server_version().await;
+        let latency_ms = started.elapsed().as_millis() as i64;
+        session.close().await;
+
+        Ok(ConnectionTest {
+            latency_ms,
+            server_version: version?,
+        })
+    }
+}
+
+/// Drivers keyed by `Connection.db_type`
+pub struct DriverRegistry {
+    drivers: Vec<Arc<dyn DatabaseDriver>>,
+}
+
+impl DriverRegistry {
+    pub fn new() -> Self {
+        Self { drivers: Vec::new() }
+    }
+
+    pub fn with_default_drivers() -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(postgres::PostgresDriver));
+        registry.register(Arc::new(mysql::MySqlDriver::mysql()));
+        registry.register(Arc::new(mysql::MySqlDriver::mariadb()));
+        registry.register(Arc::new(sqlite::SqliteDriver));
+        registry.register(Arc::new(redis::RedisDriver));
+        registry.register(Arc::new(mongodb::MongoDriver));
+        registry
+    }
+
+    pub fn register(&mut self, driver: Arc<dyn DatabaseDriver>) {
+        self.drivers.push(driver);
+    }
+
+    /// Looks a driver up by its name or one of its aliases
+    pub fn get(&self, db_type: &str) -> Option<Arc<dyn DatabaseDriver>> {
+        let db_type = db_type.to_lowercase();
+        self.drivers
+            .iter()
+            .find(|driver| {
+                let info = driver.info();
+                info.name == db_type || info.aliases.contains(&db_type.as_str())
+            })
+            .cloned()
+    }
+
+    pub fn list(&self) -> Vec<DriverInfo> {
+        self.drivers.iter().map(|driver| driver.info()).collect()
+    }
+}
+
+impl Default for DriverRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Statements currently running, keyed by their user and the client supplied
+/// execution id, so ids picked by different users never collide
+#[derive(Default)]
+pub struct RunningQueries {
+    queries: Mutex<HashMap<(String, String), CancelToken>>,
+}
+
+impl RunningQueries {
+    /// Keeps the token until the returned guard is dropped, which also happens
+    /// when the client goes away mid-query. None when the user already has a
+    /// query running under that id.
+    pub fn register(&self, execution_id: &str, user_id: &str, token: CancelToken) -> Option<RunningQuery<'_>> {
+        let key = (user_id.to_string(), execution_id.to_string());
+        let mut queries = self.queries.lock().unwrap();
+        if queries.contains_key(&key) {
+            return None;
+        }
+        queries.insert(key.clone(), token);
+        Some(RunningQuery { queries: self, key })
+    }
+
+    /// Returns the cancel token only to the user that started the query
+    pub fn get(&self, execution_id: &str, user_id: &str) -> Option<CancelToken> {
+        let queries = self.queries.lock().unwrap();
+        queries.get(&(user_id.to_string(), execution_id.to_string())).cloned()
+    }
+}
+
+/// Registration of a running query, undone on drop
+pub struct RunningQuery<'a> {
+    queries: &'a RunningQueries,
+    key: (String, String),
+}
+
+impl Drop for RunningQuery<'_> {
+    fn drop(&mut self) {
+        self.queries.queries.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// Reads a text column out of a QueryOutput row, used by catalog queries
+pub(crate) fn row_str(row: &serde_json::Value, column: &str) -> Option<String> {
+    match row.get(column)? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+pub(crate) fn row_i64(row: &serde_json::Value, column: &str) -> Option<i64> {
+    match row.get(column)? {
+        serde_json::Value::Number(n) => n.as_i64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Booleans come back as true/false, 0/1 or YES/NO depending on the engine
+pub(crate) fn row_bool(row: &serde_json::Value, column: &str) -> bool {
+    match row.get(column) {
+        Some(serde_json::Value::Bool(b)) => *b,
+        Some(serde_json::Value::Number(n)) => n.as_i64().unwrap_or(0) != 0,
+        Some(serde_json::Value::String(s)) => matches!(s.to_uppercase().as_str(), "YES" | "TRUE" | "T" | "1"),
+        _ => false,
+    }
+}
+
+/// Reads a JSON array of strings, as produced by `array_to_json`
+pub(crate) fn row_str_list(row: &serde_json::Value, column: &str) -> Vec<String> {
+    match row.get(column) {
+        Some(serde_json::Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_lookup() {
+        let registry = DriverRegistry::with_default_drivers();
+        assert_eq!(registry.get("postgres").unwrap().info().name, "postgres");
+        assert_eq!(registry.get("PostgreSQL").unwrap().info().name, "postgres");
+        assert_eq!(registry.get("mariadb").unwrap().info().display_name, "MariaDB");
+        assert!(registry.get("oracle").is_none());
+        assert_eq!(registry.list().len(), 6);
+    }
+
+    struct NoopCanceller;
+
+    #[axum::async_trait]
+    impl QueryCanceller for NoopCanceller {
+        async fn cancel(&self) -> Result<(), anyhow::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_running_queries() {
+        let running = RunningQueries::default();
+        let alice = running.register("q1", "alice", Arc::new(NoopCanceller)).unwrap();
+        // Another user picking the same id neither replaces nor sees alice's query
+        let bob = running.register("q1", "bob", Arc::new(NoopCanceller)).unwrap();
+        assert!(running.register("q1", "alice", Arc::new(NoopCanceller)).is_none());
+        assert!(running.get("q1", "alice").is_some());
+
+        drop(bob);
+        assert!(running.get("q1", "bob").is_none());
+        assert!(running.get("q1", "alice").is_some());
+        drop(alice);
+        assert!(running.get("q1", "alice").is_none());
+    }
+
+    #[test]
+    fn test_catalog_grouping() {
+        let mut catalog = Catalog {
+            tables: vec![TableInfo::new(Some("public".into()), "orders".into(), "table")],
+            ..Default::default()
+        };
+        let positions = catalog.table_positions();
+        let rows = [
+            serde_json::json!({"table_schema": "public", "table_name": "orders", "column_name": "tenant_id"}),
+            serde_json::json!({"table_schema": "public", "table_name": "orders", "column_name": "id"}),
+            serde_json::json!({"table_schema": "public", "table_name": "missing", "column_name": "id"}),
+        ];
+
+        for row in &rows {
+            if let Some(table) = catalog.table_for_row(&positions, row) {
+                table.add_constraint_column("orders_pkey", "primary key", row_str(row, "column_name"));
+            }
+        }
+
+        let table = &catalog.tables[0];
+        assert_eq!(table.primary_key, vec!["tenant_id", "id"]);
+        assert_eq!(table.constraints.len(), 1);
+        assert_eq!(table.constraints[0].columns, vec!["tenant_id", "id"]);
+    }
+}
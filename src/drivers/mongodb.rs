@@ -0,0 +1,676 @@
+use ::mongodb::bson::{self, Bson, Document};
+use ::mongodb::options::{AggregateOptions, ClientOptions, Credential, FindOptions, ServerAddress};
+use ::mongodb::Client;
+use futures::{StreamExt, TryStreamExt};
+use serde_json::{Map, Value};
+
+use crate::drivers::{
+    Catalog, ColumnInfo, DatabaseDriver, DriverCapabilities, DriverInfo, DriverSession, QueryLanguage,
+    QueryOutput, SessionLimits, TableInfo, CONNECT_TIMEOUT,
+};
+use crate::models::Connection;
+
+// Documents read per collection to guess its fields during introspection
+const SAMPLE_SIZE: i64 = 50;
+
+// Documents a find returns without .limit(), and the most it may ask for
+const DEFAULT_FIND_LIMIT: i64 = 1000;
+const MAX_FIND_LIMIT: i64 = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MongoCommand {
+    Find {
+        filter: Document,
+        projection: Option<Document>,
+        sort: Option<Document>,
+        limit: Option<i64>,
+        skip: Option<u64>,
+    },
+    Aggregate {
+        pipeline: Vec<Document>,
+    },
+    Count {
+        filter: Document,
+    },
+    Distinct {
+        field: String,
+        filter: Document,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MongoQuery {
+    pub database: Option<String>,
+    pub collection: String,
+    pub command: MongoCommand,
+}
+
+pub async fn connect(conn: &Connection, password: &str) -> Result<Client, anyhow::Error> {
+    // A full connection string in `host` allows mongodb+srv:// and replica sets
+    let mut options = if conn.host.starts_with("mongodb://") || conn.host.starts_with("mongodb+srv://") {
+        ClientOptions::parse(&conn.host).await?
+    } else {
+        let mut options = ClientOptions::default();
+        options.hosts = vec![ServerAddress::Tcp {
+            host: conn.host.clone(),
+            port: Some(conn.port as u16),
+        }];
+        options
+    };
+
+    if !conn.username.is_empty() {
+        let mut credential = Credential::default();
+        credential.username = Some(conn.username.clone());
+        credential.password = Some(password.to_string());
+        options.credential = Some(credential);
+    }
+
+    options.app_name = Some("nexusdb".to_string());
+    options.connect_timeout = Some(CONNECT_TIMEOUT);
+    options.server_selection_timeout = Some(CONNECT_TIMEOUT);
+
+    Ok(Client::with_options(options)?)
+}
+
+pub struct MongoDriver;
+
+#[axum::async_trait]
+impl DatabaseDriver for MongoDriver {
+    fn info(&self) -> DriverInfo {
+        DriverInfo {
+            name: "mongodb",
+            display_name: "MongoDB",
+            aliases: &["mongo"],
+            query_language: QueryLanguage::Mongo,
+            default_port: Some(27017),
+            requires_host: true,
+            capabilities: DriverCapabilities {
+                transactions: false,
+                explain: false,
+                schemas: false,
+                streaming: false,
+                cancel: false,
+                introspection: true,
+            },
+        }
+    }
+
+    async fn connect(&self, conn: &Connection, password: &str) -> Result<Box<dyn DriverSession>, anyhow::Error> {
+        Ok(Box::new(MongoSession {
+            client: connect(conn, password).await?,
+            database: conn.database_name.clone().filter(|d| !d.is_empty()),
+            max_rows: None,
+        }))
+    }
+}
+
+pub struct MongoSession {
+    client: Client,
+    database: Option<String>,
+    max_rows: Option<usize>,
+}
+
+#[axum::async_trait]
+impl DriverSession for MongoSession {
+    async fn server_version(&mut self) -> Result<String, anyhow::Error> {
+        let info = self
+            .client
+            .database("admin")
+            .run_command(bson::doc! { "buildInfo": 1 }, None)
+            .await?;
+        Ok(format!("MongoDB {}", info.get_str("version")?))
+    }
+
+    async fn execute(&mut self, query: &str) -> Result<QueryOutput, anyhow::Error> {
+        run(&self.client, self.database.as_deref(), query, self.max_rows).await
+    }
+
+    async fn restrict(&mut self, limits: &SessionLimits) -> Result<(), anyhow::Error> {
+        self.max_rows = limits.max_rows;
+        Ok(())
+    }
+
+    async fn introspect(&mut self) -> Result<Catalog, anyhow::Error> {
+        let mut catalog = Catalog {
+            databases: self.client.list_database_names(None, None).await?,
+            ..Default::default()
+        };
+        let Some(database) = &self.database else {
+            return Ok(catalog);
+        };
+
+        let db = self.client.database(database);
+        let specs: Vec<_> = db.list_collections(None, None).await?.try_collect().await?;
+        catalog.schemas.push(database.clone());
+
+        for spec in specs {
+            let is_view = matches!(spec.collection_type, ::mongodb::results::CollectionType::View);
+            let mut table = TableInfo::new(
+                Some(database.clone()),
+                spec.name.clone(),
+                if is_view { "view" } else { "collection" },
+            );
+            let collection = db.collection::<Document>(&spec.name);
+
+            let sample: Vec<Document> = collection
+                .find(None, FindOptions::builder().limit(SAMPLE_SIZE).build())
+                .await?
+                .try_collect()
+                .await?;
+            table.columns = sample_columns(&sample);
+
+            // Views have no indexes of their own
+            if !is_view {
+                table.primary_key.push("_id".to_string());
+                let indexes: Vec<_> = collection.list_indexes(None).await?.try_collect().await?;
+                for index in indexes {
+                    let options = index.options.unwrap_or_default();
+                    let name = options.name.unwrap_or_default();
+                    let unique = options.unique.unwrap_or(false) || name == "_id_";
+                    for key in index.keys.keys() {
+                        table.add_index_column(&name, unique, name == "_id_", Some(key.clone()));
+                    }
+                }
+            }
+
+            catalog.tables.push(table);
+        }
+
+        Ok(catalog)
+    }
+
+    async fn close(self: Box<Self>) {
+        self.client.shutdown().await;
+    }
+}
+
+/// Reads at most `max_rows + 1` documents, so the caller can tell the result
+/// was cut short
+async fn run(
+    client: &Client,
+    default_database: Option<&str>,
+    query: &str,
+    max_rows: Option<usize>,
+) -> Result<QueryOutput, anyhow::Error> {
+    let cap = max_rows.map(|max| max.saturating_add(1));
+    let parsed = parse_query(query)?;
+    let database = parsed
+        .database
+        .as_deref()
+        .or(default_database)
+        .ok_or_else(|| anyhow::anyhow!("No database selected for this MongoDB connection"))?;
+
+    let collection = client.database(database).collection::<Document>(&parsed.collection);
+
+    let output = match parsed.command {
+        MongoCommand::Find { filter, projection, sort, limit, skip } => {
+            let mut options = FindOptions::default();
+            options.projection = projection;
+            options.sort = sort;
+            options.limit = Some(match cap {
+                Some(cap) => find_limit(limit).min(cap as i64),
+                None => find_limit(limit),
+            });
+            options.skip = skip;
+            let documents: Vec<Document> = collection.find(filter, options).await?.try_collect().await?;
+            documents_to_output(documents)
+        }
+        MongoCommand::Aggregate { pipeline } => {
+            let options = AggregateOptions::builder().allow_disk_use(true).build();
+            let cursor = collection.aggregate(pipeline, options).await?;
+            let documents: Vec<Document> = cursor.take(cap.unwrap_or(usize::MAX)).try_collect().await?;
+            documents_to_output(documents)
+        }
+        MongoCommand::Count { filter } => {
+            let count = collection.count_documents(filter, None).await?;
+            QueryOutput {
+                columns: vec!["count".to_string()],
+                rows: vec![serde_json::json!({ "count": count })],
+                ..Default::default()
+            }
+        }
+        MongoCommand::Distinct { field, filter } => {
+            let values = collection.distinct(&field, filter, None).await?;
+            QueryOutput {
+                columns: vec!["value".to_string()],
+                rows: values
+                    .into_iter()
+                    .map(|v| serde_json::json!({ "value": v.into_relaxed_extjson() }))
+                    .collect(),
+                ..Default::default()
+            }
+        }
+    };
+
+    Ok(output)
+}
+
+/// A negative limit means the same number of documents in a single batch,
+/// and 0 means no limit, which gets the default
+fn find_limit(limit: Option<i64>) -> i64 {
+    match limit.map(i64::unsigned_abs) {
+        Some(0) | None => DEFAULT_FIND_LIMIT,
+        Some(limit) => limit.min(MAX_FIND_LIMIT as u64) as i64,
+    }
+}
+
+pub async fn list_databases(conn: &Connection, password: &str) -> Result<Vec<Value>, anyhow::Error> {
+    let client = connect(conn, password).await?;
+    let databases = client.list_databases(None, None).await?;
+
+    Ok(databases
+        .into_iter()
+        .map(|db| {
+            serde_json::json!({
+                "name": db.name,
+                "size_on_disk": db.size_on_disk,
+                "empty": db.empty,
+            })
+        })
+        .collect())
+}
+
+pub async fn list_collections(conn: &Connection, password: &str, database: &str) -> Result<Vec<Value>, anyhow::Error> {
+    let client = connect(conn, password).await?;
+    let specs: Vec<_> = client
+        .database(database)
+        .list_collections(None, None)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(specs
+        .into_iter()
+        .map(|spec| {
+            serde_json::json!({
+                "name": spec.name,
+                "type": bson::to_bson(&spec.collection_type).map(Bson::into_relaxed_extjson).unwrap_or(Value::Null),
+                "read_only": spec.info.read_only,
+            })
+        })
+        .collect())
+}
+
+pub async fn list_indexes(
+    conn: &Connection,
+    password: &str,
+    database: &str,
+    collection: &str,
+) -> Result<Vec<Value>, anyhow::Error> {
+    let client = connect(conn, password).await?;
+    let indexes: Vec<_> = client
+        .database(database)
+        .collection::<Document>(collection)
+        .list_indexes(None)
+        .await?
+        .try_collect()
+        .await?;
+
+    indexes
+        .iter()
+        .map(|index| Ok(bson::to_bson(index)?.into_relaxed_extjson()))
+        .collect()
+}
+
+/// Guesses the top level fields of a collection from a sample of its documents.
+/// A field is nullable when it is missing or null in any sampled document.
+pub fn sample_columns(documents: &[Document]) -> Vec<ColumnInfo> {
+    let mut columns: Vec<(ColumnInfo, Vec<String>, usize)> = Vec::new();
+
+    for document in documents {
+        for (key, value) in document {
+            let position = match columns.iter().position(|(c, _, _)| &c.name == key) {
+                Some(position) => position,
+                None => {
+                    let column = ColumnInfo {
+                        name: key.clone(),
+                        data_type: String::new(),
+                        nullable: false,
+                        default: None,
+                        ordinal: columns.len() as i64 + 1,
+                    };
+                    columns.push((column, Vec::new(), 0));
+                    columns.len() - 1
+                }
+            };
+
+            let (column, types, seen) = &mut columns[position];
+            *seen += 1;
+            if matches!(value, Bson::Null) {
+                column.nullable = true;
+                continue;
+            }
+            let type_name = format!("{:?}", value.element_type());
+            if !types.contains(&type_name) {
+                types.push(type_name);
+            }
+        }
+    }
+
+    columns
+        .into_iter()
+        .map(|(mut column, types, seen)| {
+            column.data_type = types.join(" | ");
+            column.nullable |= seen < documents.len();
+            column
+        })
+        .collect()
+}
+
+/// Builds a QueryOutput whose columns are the union of the (flattened) keys of
+/// every document, keeping the untouched documents alongside.
+pub fn documents_to_output(documents: Vec<Document>) -> QueryOutput {
+    let mut columns: Vec<String> = Vec::new();
+    let mut rows = Vec::with_capacity(documents.len());
+    let mut raw = Vec::with_capacity(documents.len());
+
+    for document in documents {
+        let json = Bson::Document(document).into_relaxed_extjson();
+        let mut row = Map::new();
+        if let Value::Object(object) = &json {
+            flatten_into(&mut row, None, object);
+        }
+        for key in row.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+        rows.push(Value::Object(row));
+        raw.push(json);
+    }
+
+    QueryOutput {
+        columns,
+        rows,
+        rows_affected: None,
+        documents: Some(raw),
+    }
+}
+
+fn flatten_into(row: &mut Map<String, Value>, prefix: Option<&str>, object: &Map<String, Value>) {
+    for (key, value) in object {
+        let path = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, key),
+            None => key.clone(),
+        };
+        match value {
+            // Extended JSON wrappers such as {"$oid": ...} stay a single cell
+            Value::Object(inner) if !inner.is_empty() && !inner.keys().all(|k| k.starts_with('$')) => {
+                flatten_into(row, Some(&path), inner)
+            }
+            _ => {
+                row.insert(path, value.clone());
+            }
+        }
+    }
+}
+
+/// Accepts either a JSON command such as
+/// `{"collection": "users", "find": {"age": {"$gt": 30}}, "limit": 10}`
+/// or shell syntax such as `db.users.find({"age": {"$gt": 30}}).limit(10)`.
+pub fn parse_query(query: &str) -> Result<MongoQuery, anyhow::Error> {
+    let query = query.trim().trim_end_matches(';').trim();
+    if query.starts_with('{') {
+        parse_json_query(query)
+    } else if query.starts_with("db.") {
+        parse_shell_query(query)
+    } else {
+        Err(anyhow::anyhow!(
+            "MongoDB queries must be a JSON command or use db.<collection>.<method>(...) syntax"
+        ))
+    }
+}
+
+fn parse_json_query(query: &str) -> Result<MongoQuery, anyhow::Error> {
+    let value: Value = serde_json::from_str(query)?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("MongoDB command must be a JSON object"))?;
+
+    let collection = object
+        .get("collection")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow::anyhow!("Missing \"collection\""))?
+        .to_string();
+    let database = object.get("database").and_then(Value::as_str).map(str::to_string);
+    let filter = |key: &str| object.get(key).map(to_document).transpose().map(Option::unwrap_or_default);
+
+    let command = if let Some(find) = object.get("find") {
+        MongoCommand::Find {
+            filter: to_document(find)?,
+            projection: object.get("projection").map(to_document).transpose()?,
+            sort: object.get("sort").map(to_document).transpose()?,
+            limit: object.get("limit").and_then(Value::as_i64),
+            skip: object.get("skip").and_then(Value::as_u64),
+        }
+    } else if let Some(pipeline) = object.get("aggregate") {
+        MongoCommand::Aggregate {
+            pipeline: to_pipeline(pipeline)?,
+        }
+    } else if let Some(count) = object.get("count") {
+        MongoCommand::Count {
+            filter: to_document(count)?,
+        }
+    } else if let Some(field) = object.get("distinct") {
+        MongoCommand::Distinct {
+            field: field
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("\"distinct\" must be a field name"))?
+                .to_string(),
+            filter: filter("filter")?,
+        }
+    } else {
+        return Err(anyhow::anyhow!(
+            "Unsupported MongoDB command, expected find, aggregate, count or distinct"
+        ));
+    };
+
+    Ok(MongoQuery {
+        database,
+        collection,
+        command,
+    })
+}
+
+fn parse_shell_query(query: &str) -> Result<MongoQuery, anyhow::Error> {
+    let rest = &query["db.".len()..];
+    let open = rest
+        .find('(')
+        .ok_or_else(|| anyhow::anyhow!("Expected a method call"))?;
+    let (collection, method) = rest[..open]
+        .rsplit_once('.')
+        .ok_or_else(|| anyhow::anyhow!("Expected db.<collection>.<method>(...)"))?;
+    let (args, mut rest) = split_call(&rest[open + 1..])?;
+    let args = parse_args(args)?;
+
+    let mut command = match method {
+        "find" | "findOne" => MongoCommand::Find {
+            filter: args.first().map(to_document).transpose()?.unwrap_or_default(),
+            projection: args.get(1).map(to_document).transpose()?,
+            sort: None,
+            limit: if method == "findOne" { Some(1) } else { None },
+            skip: None,
+        },
+        "aggregate" => MongoCommand::Aggregate {
+            pipeline: args.first().map(to_pipeline).transpose()?.unwrap_or_default(),
+        },
+        "count" | "countDocuments" => MongoCommand::Count {
+            filter: args.first().map(to_document).transpose()?.unwrap_or_default(),
+        },
+        "distinct" => MongoCommand::Distinct {
+            field: args
+                .first()
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow::anyhow!("distinct() expects a field name"))?
+                .to_string(),
+            filter: args.get(1).map(to_document).transpose()?.unwrap_or_default(),
+        },
+        other => return Err(anyhow::anyhow!("Unsupported MongoDB method: {}", other)),
+    };
+
+    // Cursor modifiers: .sort({...}).skip(n).limit(n)
+    while !rest.trim().is_empty() {
+        let trimmed = rest.trim_start();
+        let modifier = trimmed
+            .strip_prefix('.')
+            .ok_or_else(|| anyhow::anyhow!("Unexpected input after method call: {}", trimmed))?;
+        let open = modifier
+            .find('(')
+            .ok_or_else(|| anyhow::anyhow!("Expected a cursor method call"))?;
+        let name = &modifier[..open];
+        let (args, remaining) = split_call(&modifier[open + 1..])?;
+        let args = parse_args(args)?;
+        rest = remaining;
+
+        let MongoCommand::Find { sort, limit, skip, .. } = &mut command else {
+            return Err(anyhow::anyhow!("{}() can only follow find()", name));
+        };
+        match name {
+            "sort" => *sort = args.first().map(to_document).transpose()?,
+            "limit" => *limit = args.first().and_then(Value::as_i64),
+            "skip" => *skip = args.first().and_then(Value::as_u64),
+            other => return Err(anyhow::anyhow!("Unsupported cursor method: {}", other)),
+        }
+    }
+
+    Ok(MongoQuery {
+        database: None,
+        collection: collection.to_string(),
+        command,
+    })
+}
+
+/// Splits `args) rest` at the closing parenthesis, skipping nested brackets and strings
+fn split_call(input: &str) -> Result<(&str, &str), anyhow::Error> {
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (i, c) in input.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' if depth == 0 => return Ok((&input[..i], &input[i + 1..])),
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    Err(anyhow::anyhow!("Unbalanced parentheses in MongoDB query"))
+}
+
+fn parse_args(args: &str) -> Result<Vec<Value>, anyhow::Error> {
+    if args.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&format!("[{}]", args))
+        .map_err(|e| anyhow::anyhow!("Invalid JSON arguments: {}", e))
+}
+
+/// Converts (extended) JSON into a BSON document, so {"$oid": ...} and
+/// {"$date": ...} are understood.
+fn to_document(value: &Value) -> Result<Document, anyhow::Error> {
+    match Bson::try_from(value.clone())? {
+        Bson::Document(document) => Ok(document),
+        _ => Err(anyhow::anyhow!("Expected a JSON object, got {}", value)),
+    }
+}
+
+fn to_pipeline(value: &Value) -> Result<Vec<Document>, anyhow::Error> {
+    value
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Aggregation pipeline must be an array"))?
+        .iter()
+        .map(to_document)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::mongodb::bson::doc;
+
+    #[test]
+    fn test_parse_shell_query() {
+        let parsed = parse_query(r#"db.users.find({"age": {"$gt": 30}}, {"name": 1}).sort({"age": -1}).limit(5);"#).unwrap();
+        assert_eq!(parsed.collection, "users");
+        assert_eq!(
+            parsed.command,
+            MongoCommand::Find {
+                filter: doc! { "age": { "$gt": 30 } },
+                projection: Some(doc! { "name": 1 }),
+                sort: Some(doc! { "age": -1 }),
+                limit: Some(5),
+                skip: None,
+            }
+        );
+
+        let parsed = parse_query(r#"db.system.users.distinct("role", {"active": true})"#).unwrap();
+        assert_eq!(parsed.collection, "system.users");
+        assert_eq!(
+            parsed.command,
+            MongoCommand::Distinct {
+                field: "role".to_string(),
+                filter: doc! { "active": true },
+            }
+        );
+
+        assert!(parse_query("db.users.aggregate([{\"$match\": {}}]).limit(1)").is_err());
+        assert_eq!(find_limit(None), DEFAULT_FIND_LIMIT);
+        assert_eq!(find_limit(Some(0)), DEFAULT_FIND_LIMIT);
+        assert_eq!(find_limit(Some(-20)), 20);
+        assert_eq!(find_limit(Some(1_000_000)), MAX_FIND_LIMIT);
+        assert!(parse_query("db.users.find({").is_err());
+        assert!(parse_query("SELECT * FROM users").is_err());
+    }
+
+    #[test]
+    fn test_parse_json_query() {
+        let parsed = parse_query(
+            r#"{"database": "app", "collection": "orders", "aggregate": [{"$match": {"_id": {"$oid": "65a1b2c3d4e5f60718293a4b"}}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.database.as_deref(), Some("app"));
+        let MongoCommand::Aggregate { pipeline } = parsed.command else {
+            panic!("expected aggregate");
+        };
+        assert!(pipeline[0].get_document("$match").unwrap().get_object_id("_id").is_ok());
+
+        let parsed = parse_query(r#"{"collection": "orders", "count": {"status": "paid"}}"#).unwrap();
+        assert_eq!(parsed.command, MongoCommand::Count { filter: doc! { "status": "paid" } });
+    }
+
+    #[test]
+    fn test_documents_to_output() {
+        let output = documents_to_output(vec![
+            doc! { "_id": 1, "name": "alice", "address": { "city": "Lima" } },
+            doc! { "_id": 2, "tags": ["a"] },
+        ]);
+        assert_eq!(output.columns, vec!["_id", "name", "address.city", "tags"]);
+        assert_eq!(output.rows[0]["address.city"], "Lima");
+        assert_eq!(output.documents.unwrap()[0]["address"]["city"], "Lima");
+    }
+
+    #[test]
+    fn test_sample_columns() {
+        let columns = sample_columns(&[
+            doc! { "_id": 1, "name": "alice", "age": 30 },
+            doc! { "_id": 2, "name": null, "age": "unknown" },
+        ]);
+        assert_eq!(columns[1].name, "name");
+        assert_eq!(columns[1].data_type, "String");
+        assert!(columns[1].nullable);
+        assert_eq!(columns[2].data_type, "Int32 | String");
+        assert!(!columns[0].nullable);
+    }
+}
@@ -0,0 +1,419 @@
+use futures::TryStreamExt;
+use serde_json::{Map, Number, Value};
+use sqlx::mysql::{MySqlConnectOptions, MySqlConnection, MySqlRow};
+use sqlx::{Column, ConnectOptions, Connection as _, Either, Executor, Row, TypeInfo, ValueRef};
+use std::sync::Arc;
+
+use crate::drivers::{
+    row_bool, row_i64, row_str, CancelToken, Catalog, ColumnInfo, DatabaseDriver, DriverCapabilities,
+    DriverInfo, DriverSession, QueryCanceller, QueryLanguage, QueryOutput, SessionLimits, TableInfo,
+    CONNECT_TIMEOUT,
+};
+use crate::models::Connection;
+
+/// Serves both MySQL and MariaDB, which share the wire protocol
+pub struct MySqlDriver {
+    name: &'static str,
+    display_name: &'static str,
+}
+
+impl MySqlDriver {
+    pub fn mysql() -> Self {
+        Self {
+            name: "mysql",
+            display_name: "MySQL",
+        }
+    }
+
+    pub fn mariadb() -> Self {
+        Self {
+            name: "mariadb",
+            display_name: "MariaDB",
+        }
+    }
+}
+
+#[axum::async_trait]
+impl DatabaseDriver for MySqlDriver {
+    fn info(&self) -> DriverInfo {
+        DriverInfo {
+            name: self.name,
+            display_name: self.display_name,
+            aliases: &[],
+            query_language: QueryLanguage::Sql,
+            default_port: Some(3306),
+            requires_host: true,
+            capabilities: DriverCapabilities {
+                transactions: true,
+                explain: true,
+                schemas: false,
+                streaming: false,
+                cancel: true,
+                introspection: true,
+            },
+        }
+    }
+
+    async fn connect(&self, conn: &Connection, password: &str) -> Result<Box<dyn DriverSession>, anyhow::Error> {
+        let options = connect_options(conn, password);
+        let mut my = connect(conn, password).await?;
+        let connection_id: u64 = sqlx::query_scalar("SELECT CONNECTION_ID()").fetch_one(&mut my).await?;
+
+        Ok(Box::new(MySqlSession {
+            my,
+            mariadb: self.name == "mariadb",
+            canceller: Arc::new(MySqlCanceller { options, connection_id }),
+            max_rows: None,
+        }))
+    }
+}
+
+pub struct MySqlSession {
+    my: MySqlConnection,
+    mariadb: bool,
+    canceller: Arc<MySqlCanceller>,
+    max_rows: Option<usize>,
+}
+
+#[axum::async_trait]
+impl DriverSession for MySqlSession {
+    async fn server_version(&mut self) -> Result<String, anyhow::Error> {
+        let output = run(&mut self.my, "SELECT VERSION() AS version").await?;
+        output
+            .rows
+            .first()
+            .and_then(|r| row_str(r, "version"))
+            .ok_or_else(|| anyhow::anyhow!("Server did not report a version"))
+    }
+
+    async fn execute(&mut self, query: &str) -> Result<QueryOutput, anyhow::Error> {
+        run_limited(&mut self.my, query, self.max_rows).await
+    }
+
+    async fn restrict(&mut self, limits: &SessionLimits) -> Result<(), anyhow::Error> {
+        self.max_rows = limits.max_rows;
+        if limits.read_only {
+            self.my.execute("SET SESSION TRANSACTION READ ONLY").await?;
+        }
+        if let Some(timeout) = limits.statement_timeout {
+            // MySQL limits SELECTs in milliseconds, MariaDB any statement in seconds
+            let setting = if self.mariadb {
+                format!("SET SESSION max_statement_time = {}", timeout.as_secs_f64())
+            } else {
+                format!("SET SESSION max_execution_time = {}", timeout.as_millis())
+            };
+            self.my.execute(setting.as_str()).await?;
+        }
+        Ok(())
+    }
+
+    async fn introspect(&mut self) -> Result<Catalog, anyhow::Error> {
+        let databases = run(
+            &mut self.my,
+            "SELECT SCHEMA_NAME AS name FROM information_schema.SCHEMATA ORDER BY SCHEMA_NAME",
+        )
+        .await?;
+        let schemas = run(&mut self.my, SCHEMAS_SQL).await?;
+        let tables = run(&mut self.my, TABLES_SQL).await?;
+        let columns = run(&mut self.my, COLUMNS_SQL).await?;
+        let constraints = run(&mut self.my, CONSTRAINTS_SQL).await?;
+        let indexes = run(&mut self.my, INDEXES_SQL).await?;
+
+        let mut catalog = Catalog {
+            databases: databases.rows.iter().filter_map(|r| row_str(r, "name")).collect(),
+            schemas: schemas.rows.iter().filter_map(|r| row_str(r, "name")).collect(),
+            tables: tables
+                .rows
+                .iter()
+                .map(|r| {
+                    let kind = match row_str(r, "table_type").as_deref() {
+                        Some("VIEW") | Some("SYSTEM VIEW") => "view",
+                        _ => "table",
+                    };
+                    TableInfo::new(row_str(r, "table_schema"), row_str(r, "table_name").unwrap_or_default(), kind)
+                })
+                .collect(),
+        };
+        let positions = catalog.table_positions();
+
+        for row in &columns.rows {
+            if let Some(table) = catalog.table_for_row(&positions, row) {
+                table.columns.push(ColumnInfo {
+                    name: row_str(row, "column_name").unwrap_or_default(),
+                    data_type: row_str(row, "data_type").unwrap_or_default(),
+                    nullable: row_bool(row, "is_nullable"),
+                    default: row_str(row, "column_default"),
+                    ordinal: row_i64(row, "ordinal").unwrap_or_default(),
+                });
+            }
+        }
+
+        // One row per constraint column, ordered by position inside the constraint
+        for row in &constraints.rows {
+            let Some(table) = catalog.table_for_row(&positions, row) else {
+                continue;
+            };
+            let name = row_str(row, "constraint_name").unwrap_or_default();
+            let kind = match row_str(row, "constraint_type").as_deref() {
+                Some("PRIMARY KEY") => "primary key",
+                Some("FOREIGN KEY") => "foreign key",
+                Some("UNIQUE") => "unique",
+                _ => "check",
+            };
+            let column = row_str(row, "column_name");
+
+            if kind == "foreign key" {
+                if let (Some(column), Some(referenced_table)) = (column.clone(), row_str(row, "referenced_table")) {
+                    table.add_foreign_key_column(
+                        &name,
+                        column,
+                        row_str(row, "referenced_schema"),
+                        referenced_table,
+                        row_str(row, "referenced_column"),
+                    );
+                }
+            }
+            table.add_constraint_column(&name, kind, column);
+        }
+
+        for row in &indexes.rows {
+            if let Some(table) = catalog.table_for_row(&positions, row) {
+                let name = row_str(row, "index_name").unwrap_or_default();
+                table.add_index_column(
+                    &name,
+                    !row_bool(row, "non_unique"),
+                    name == "PRIMARY",
+                    row_str(row, "column_name"),
+                );
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    fn cancel_token(&self) -> Option<CancelToken> {
+        Some(self.canceller.clone())
+    }
+
+    async fn close(self: Box<Self>) {
+        let _ = self.my.close().await;
+    }
+}
+
+/// Cancels a running statement with KILL QUERY from a second connection
+pub struct MySqlCanceller {
+    options: MySqlConnectOptions,
+    connection_id: u64,
+}
+
+#[axum::async_trait]
+impl QueryCanceller for MySqlCanceller {
+    async fn cancel(&self) -> Result<(), anyhow::Error> {
+        let mut my = tokio::time::timeout(CONNECT_TIMEOUT, self.options.connect())
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out connecting to cancel the query"))??;
+        // KILL does not accept placeholders; the id comes from CONNECTION_ID()
+        my.execute(format!("KILL QUERY {}", self.connection_id).as_str()).await?;
+        let _ = my.close().await;
+        Ok(())
+    }
+}
+
+// Catalog queries cover the selected database, or every non-system schema when
+// the connection has none
+const SCHEMAS_SQL: &str = "SELECT SCHEMA_NAME AS name FROM information_schema.SCHEMATA \
+     WHERE (DATABASE() IS NULL AND SCHEMA_NAME NOT IN ('mysql', 'information_schema', 'performance_schema', 'sys')) \
+        OR SCHEMA_NAME = DATABASE() \
+     ORDER BY SCHEMA_NAME";
+
+const TABLES_SQL: &str = "SELECT TABLE_SCHEMA AS table_schema, TABLE_NAME AS table_name, TABLE_TYPE AS table_type \
+     FROM information_schema.TABLES \
+     WHERE (DATABASE() IS NULL AND TABLE_SCHEMA NOT IN ('mysql', 'information_schema', 'performance_schema', 'sys')) \
+        OR TABLE_SCHEMA = DATABASE() \
+     ORDER BY TABLE_SCHEMA, TABLE_NAME";
+
+const COLUMNS_SQL: &str = "SELECT TABLE_SCHEMA AS table_schema, TABLE_NAME AS table_name, COLUMN_NAME AS column_name, \
+            COLUMN_TYPE AS data_type, IS_NULLABLE AS is_nullable, COLUMN_DEFAULT AS column_default, \
+            ORDINAL_POSITION AS ordinal \
+     FROM information_schema.COLUMNS \
+     WHERE (DATABASE() IS NULL AND TABLE_SCHEMA NOT IN ('mysql', 'information_schema', 'performance_schema', 'sys')) \
+        OR TABLE_SCHEMA = DATABASE() \
+     ORDER BY TABLE_SCHEMA, TABLE_NAME, ORDINAL_POSITION";
+
+const CONSTRAINTS_SQL: &str = "SELECT tc.TABLE_SCHEMA AS table_schema, tc.TABLE_NAME AS table_name, \
+            tc.CONSTRAINT_NAME AS constraint_name, tc.CONSTRAINT_TYPE AS constraint_type, \
+            kcu.COLUMN_NAME AS column_name, kcu.REFERENCED_TABLE_SCHEMA AS referenced_schema, \
+            kcu.REFERENCED_TABLE_NAME AS referenced_table, kcu.REFERENCED_COLUMN_NAME AS referenced_column \
+     FROM information_schema.TABLE_CONSTRAINTS tc \
+     LEFT JOIN information_schema.KEY_COLUMN_USAGE kcu \
+       ON kcu.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA \
+      AND kcu.CONSTRAINT_NAME = tc.CONSTRAINT_NAME \
+      AND kcu.TABLE_SCHEMA = tc.TABLE_SCHEMA \
+      AND kcu.TABLE_NAME = tc.TABLE_NAME \
+     WHERE (DATABASE() IS NULL AND tc.TABLE_SCHEMA NOT IN ('mysql', 'information_schema', 'performance_schema', 'sys')) \
+        OR tc.TABLE_SCHEMA = DATABASE() \
+     ORDER BY tc.TABLE_SCHEMA, tc.TABLE_NAME, tc.CONSTRAINT_NAME, kcu.ORDINAL_POSITION";
+
+const INDEXES_SQL: &str = "SELECT TABLE_SCHEMA AS table_schema, TABLE_NAME AS table_name, INDEX_NAME AS index_name, \
+            NON_UNIQUE AS non_unique, COLUMN_NAME AS column_name \
+     FROM information_schema.STATISTICS \
+     WHERE (DATABASE() IS NULL AND TABLE_SCHEMA NOT IN ('mysql', 'information_schema', 'performance_schema', 'sys')) \
+        OR TABLE_SCHEMA = DATABASE() \
+     ORDER BY TABLE_SCHEMA, TABLE_NAME, INDEX_NAME, SEQ_IN_INDEX";
+
+pub fn connect_options(conn: &Connection, password: &str) -> MySqlConnectOptions {
+    let mut options = MySqlConnectOptions::new()
+        .host(&conn.host)
+        .port(conn.port as u16)
+        .username(&conn.username)
+        .password(password)
+        .charset("utf8mb4");
+
+    if let Some(database) = conn.database_name.as_deref().filter(|d| !d.is_empty()) {
+        options = options.database(database);
+    }
+
+    options
+}
+
+pub async fn connect(conn: &Connection, password: &str) -> Result<MySqlConnection, anyhow::Error> {
+    let options = connect_options(conn, password);
+    tokio::time::timeout(CONNECT_TIMEOUT, options.connect())
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out connecting to {}:{}", conn.host, conn.port))?
+        .map_err(|e| anyhow::anyhow!("Failed to connect: {}", e))
+}
+
+async fn run(my: &mut MySqlConnection, query: &str) -> Result<QueryOutput, anyhow::Error> {
+    run_limited(my, query, None).await
+}
+
+/// Runs the query, keeping at most `max_rows + 1` rows so the caller can tell
+/// the result was cut short without holding the whole of it
+async fn run_limited(
+    my: &mut MySqlConnection,
+    query: &str,
+    max_rows: Option<usize>,
+) -> Result<QueryOutput, anyhow::Error> {
+    let mut output = QueryOutput::default();
+    let mut rows_affected = 0u64;
+
+    {
+        // Without arguments the query is sent through COM_QUERY (text protocol),
+        // which accepts several statements in one call.
+        let mut stream = my.fetch_many(query);
+        while let Some(item) = stream.try_next().await? {
+            match item {
+                Either::Left(result) => rows_affected += result.rows_affected(),
+                Either::Right(row) => {
+                    if output.columns.is_empty() {
+                        output.columns = column_names(&row);
+                    }
+                    // Later statements of the batch still run and count, so
+                    // the stream is drained; only their rows are left out
+                    if max_rows.is_none_or(|max| output.rows.len() <= max) {
+                        output.rows.push(row_to_json(&row));
+                    }
+                }
+            }
+        }
+    }
+
+    // Empty result sets carry no column definitions, so ask the server for them
+    if output.rows.is_empty() {
+        if let Ok(describe) = my.describe(query).await {
+            output.columns = describe.columns().iter().map(|c| c.name().to_string()).collect();
+        }
+    }
+
+    output.rows_affected = Some(rows_affected);
+    Ok(output)
+}
+
+fn column_names(row: &MySqlRow) -> Vec<String> {
+    row.columns().iter().map(|c| c.name().to_string()).collect()
+}
+
+fn row_to_json(row: &MySqlRow) -> Value {
+    let mut object = Map::new();
+    for column in row.columns() {
+        let value = value_to_json(row, column.ordinal());
+        object.insert(column.name().to_string(), value);
+    }
+    Value::Object(object)
+}
+
+fn value_to_json(row: &MySqlRow, index: usize) -> Value {
+    let type_name = match row.try_get_raw(index) {
+        Ok(raw) if raw.is_null() => return Value::Null,
+        Ok(raw) => raw.type_info().name().to_string(),
+        Err(_) => return Value::Null,
+    };
+
+    match row.try_get_unchecked::<Vec<u8>, _>(index) {
+        Ok(bytes) => bytes_to_json(&type_name, bytes),
+        Err(_) => Value::Null,
+    }
+}
+
+/// Maps a MySQL/MariaDB value, as sent by the text protocol, into JSON
+pub fn bytes_to_json(type_name: &str, bytes: Vec<u8>) -> Value {
+    // BIT columns are sent as raw big-endian bytes even in the text protocol
+    if type_name == "BIT" {
+        return Value::from(bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64));
+    }
+
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => return Value::String(format!("0x{}", hex::encode(e.into_bytes()))),
+    };
+
+    match type_name {
+        "BOOLEAN" => match text.as_str() {
+            "0" => Value::Bool(false),
+            "1" => Value::Bool(true),
+            _ => text.parse::<i64>().map(Value::from).unwrap_or(Value::String(text)),
+        },
+        "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" | "YEAR" => text
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or(Value::String(text)),
+        "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "MEDIUMINT UNSIGNED" | "INT UNSIGNED"
+        | "BIGINT UNSIGNED" => text
+            .parse::<u64>()
+            .map(Value::from)
+            .unwrap_or(Value::String(text)),
+        "FLOAT" | "DOUBLE" => text
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .unwrap_or(Value::String(text)),
+        "JSON" => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        // DECIMAL is kept as text so no precision is lost
+        _ => Value::String(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_to_json() {
+        assert_eq!(bytes_to_json("BOOLEAN", b"1".to_vec()), Value::Bool(true));
+        assert_eq!(bytes_to_json("INT", b"-7".to_vec()), Value::from(-7));
+        assert_eq!(
+            bytes_to_json("BIGINT UNSIGNED", b"18446744073709551615".to_vec()),
+            Value::from(u64::MAX)
+        );
+        assert_eq!(bytes_to_json("DECIMAL", b"12.50".to_vec()), Value::String("12.50".into()));
+        assert_eq!(
+            bytes_to_json("DATETIME", b"2024-01-31 10:00:00".to_vec()),
+            Value::String("2024-01-31 10:00:00".into())
+        );
+        assert_eq!(bytes_to_json("BIT", vec![0x01, 0x02]), Value::from(258));
+        assert_eq!(bytes_to_json("JSON", br#"[1,2]"#.to_vec()), serde_json::json!([1, 2]));
+        assert_eq!(bytes_to_json("BLOB", vec![0xff, 0x00]), Value::String("0xff00".into()));
+    }
+}
@@ -0,0 +1,401 @@
+use futures::TryStreamExt;
+use serde_json::{Map, Number, Value};
+use sqlx::postgres::{PgConnectOptions, PgConnection, PgRow};
+use sqlx::{Column, ConnectOptions, Connection as _, Either, Executor, Row, TypeInfo, ValueRef};
+use std::sync::Arc;
+
+use crate::drivers::{
+    row_bool, row_i64, row_str, row_str_list, CancelToken, Catalog, ColumnInfo, DatabaseDriver,
+    DriverCapabilities, DriverInfo, DriverSession, QueryCanceller, QueryLanguage, QueryOutput, SessionLimits,
+    TableInfo, CONNECT_TIMEOUT,
+};
+use crate::models::Connection;
+
+pub struct PostgresDriver;
+
+#[axum::async_trait]
+impl DatabaseDriver for PostgresDriver {
+    fn info(&self) -> DriverInfo {
+        DriverInfo {
+            name: "postgres",
+            display_name: "PostgreSQL",
+            aliases: &["postgresql", "pg"],
+            query_language: QueryLanguage::Sql,
+            default_port: Some(5432),
+            requires_host: true,
+            capabilities: DriverCapabilities {
+                transactions: true,
+                explain: true,
+                schemas: true,
+                streaming: false,
+                cancel: true,
+                introspection: true,
+            },
+        }
+    }
+
+    async fn connect(&self, conn: &Connection, password: &str) -> Result<Box<dyn DriverSession>, anyhow::Error> {
+        let options = connect_options(conn, password);
+        let mut pg = connect(conn, password).await?;
+        let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()").fetch_one(&mut pg).await?;
+
+        Ok(Box::new(PgSession {
+            pg,
+            canceller: Arc::new(PgCanceller { options, pid }),
+            max_rows: None,
+        }))
+    }
+}
+
+pub struct PgSession {
+    pg: PgConnection,
+    canceller: Arc<PgCanceller>,
+    max_rows: Option<usize>,
+}
+
+#[axum::async_trait]
+impl DriverSession for PgSession {
+    async fn server_version(&mut self) -> Result<String, anyhow::Error> {
+        let version: String = sqlx::query_scalar("SELECT version()").fetch_one(&mut self.pg).await?;
+        Ok(version)
+    }
+
+    async fn execute(&mut self, query: &str) -> Result<QueryOutput, anyhow::Error> {
+        run_limited(&mut self.pg, query, self.max_rows).await
+    }
+
+    async fn restrict(&mut self, limits: &SessionLimits) -> Result<(), anyhow::Error> {
+        self.max_rows = limits.max_rows;
+        if limits.read_only {
+            self.pg
+                .execute("SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY")
+                .await?;
+        }
+        if let Some(timeout) = limits.statement_timeout {
+            self.pg
+                .execute(format!("SET statement_timeout = {}", timeout.as_millis()).as_str())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn introspect(&mut self) -> Result<Catalog, anyhow::Error> {
+        let databases = run(&mut self.pg, DATABASES_SQL).await?;
+        let schemas = run(&mut self.pg, SCHEMAS_SQL).await?;
+        let tables = run(&mut self.pg, TABLES_SQL).await?;
+        let columns = run(&mut self.pg, COLUMNS_SQL).await?;
+        let constraints = run(&mut self.pg, CONSTRAINTS_SQL).await?;
+        let indexes = run(&mut self.pg, INDEXES_SQL).await?;
+
+        let mut catalog = Catalog {
+            databases: databases.rows.iter().filter_map(|r| row_str(r, "datname")).collect(),
+            schemas: schemas.rows.iter().filter_map(|r| row_str(r, "schema_name")).collect(),
+            tables: tables
+                .rows
+                .iter()
+                .map(|r| {
+                    TableInfo::new(
+                        row_str(r, "table_schema"),
+                        row_str(r, "table_name").unwrap_or_default(),
+                        relkind_name(row_str(r, "relkind").as_deref()),
+                    )
+                })
+                .collect(),
+        };
+        let positions = catalog.table_positions();
+
+        for row in &columns.rows {
+            if let Some(table) = catalog.table_for_row(&positions, row) {
+                table.columns.push(ColumnInfo {
+                    name: row_str(row, "column_name").unwrap_or_default(),
+                    data_type: row_str(row, "data_type").unwrap_or_default(),
+                    nullable: row_bool(row, "is_nullable"),
+                    default: row_str(row, "column_default"),
+                    ordinal: row_i64(row, "ordinal").unwrap_or_default(),
+                });
+            }
+        }
+
+        for row in &constraints.rows {
+            let Some(table) = catalog.table_for_row(&positions, row) else {
+                continue;
+            };
+            let name = row_str(row, "constraint_name").unwrap_or_default();
+            let kind = constraint_kind(row_str(row, "contype").as_deref());
+            let constraint_columns = row_str_list(row, "columns");
+
+            if kind == "foreign key" {
+                let referenced_columns = row_str_list(row, "referenced_columns");
+                for (i, column) in constraint_columns.iter().enumerate() {
+                    table.add_foreign_key_column(
+                        &name,
+                        column.clone(),
+                        row_str(row, "referenced_schema"),
+                        row_str(row, "referenced_table").unwrap_or_default(),
+                        referenced_columns.get(i).cloned(),
+                    );
+                }
+            }
+
+            table.add_constraint_column(&name, kind, None);
+            for column in constraint_columns {
+                table.add_constraint_column(&name, kind, Some(column));
+            }
+            if let Some(constraint) = table.constraints.last_mut() {
+                constraint.definition = row_str(row, "definition");
+            }
+        }
+
+        for row in &indexes.rows {
+            if let Some(table) = catalog.table_for_row(&positions, row) {
+                let name = row_str(row, "index_name").unwrap_or_default();
+                let unique = row_bool(row, "is_unique");
+                let primary = row_bool(row, "is_primary");
+                table.add_index_column(&name, unique, primary, None);
+                for column in row_str_list(row, "columns") {
+                    table.add_index_column(&name, unique, primary, Some(column));
+                }
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    fn cancel_token(&self) -> Option<CancelToken> {
+        Some(self.canceller.clone())
+    }
+
+    async fn close(self: Box<Self>) {
+        let _ = self.pg.close().await;
+    }
+}
+
+/// Cancels a running statement through pg_cancel_backend on a second connection
+pub struct PgCanceller {
+    options: PgConnectOptions,
+    pid: i32,
+}
+
+#[axum::async_trait]
+impl QueryCanceller for PgCanceller {
+    async fn cancel(&self) -> Result<(), anyhow::Error> {
+        let mut pg = tokio::time::timeout(CONNECT_TIMEOUT, self.options.connect())
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out connecting to cancel the query"))??;
+        sqlx::query("SELECT pg_cancel_backend($1)")
+            .bind(self.pid)
+            .execute(&mut pg)
+            .await?;
+        let _ = pg.close().await;
+        Ok(())
+    }
+}
+
+// Catalog queries skip information_schema and every pg_* schema (pg_catalog, pg_toast, ...)
+const DATABASES_SQL: &str = "SELECT datname FROM pg_database WHERE NOT datistemplate ORDER BY datname";
+
+const SCHEMAS_SQL: &str = "SELECT nspname AS schema_name FROM pg_namespace \
+     WHERE nspname <> 'information_schema' AND nspname !~ '^pg_' ORDER BY nspname";
+
+const TABLES_SQL: &str = "SELECT n.nspname AS table_schema, c.relname AS table_name, c.relkind::text AS relkind \
+     FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+     WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f') \
+       AND n.nspname <> 'information_schema' AND n.nspname !~ '^pg_' \
+     ORDER BY 1, 2";
+
+const COLUMNS_SQL: &str = "SELECT n.nspname AS table_schema, c.relname AS table_name, a.attname AS column_name, \
+            format_type(a.atttypid, a.atttypmod) AS data_type, NOT a.attnotnull AS is_nullable, \
+            pg_get_expr(d.adbin, d.adrelid) AS column_default, a.attnum AS ordinal \
+     FROM pg_attribute a \
+     JOIN pg_class c ON c.oid = a.attrelid \
+     JOIN pg_namespace n ON n.oid = c.relnamespace \
+     LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+     WHERE a.attnum > 0 AND NOT a.attisdropped AND c.relkind IN ('r', 'p', 'v', 'm', 'f') \
+       AND n.nspname <> 'information_schema' AND n.nspname !~ '^pg_' \
+     ORDER BY 1, 2, a.attnum";
+
+const CONSTRAINTS_SQL: &str = "SELECT n.nspname AS table_schema, c.relname AS table_name, con.conname AS constraint_name, \
+            con.contype::text AS contype, pg_get_constraintdef(con.oid) AS definition, \
+            array_to_json(ARRAY( \
+                SELECT a.attname FROM unnest(con.conkey) WITH ORDINALITY k(attnum, ord) \
+                JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum ORDER BY k.ord \
+            )) AS columns, \
+            rn.nspname AS referenced_schema, rc.relname AS referenced_table, \
+            array_to_json(ARRAY( \
+                SELECT a.attname FROM unnest(con.confkey) WITH ORDINALITY k(attnum, ord) \
+                JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum ORDER BY k.ord \
+            )) AS referenced_columns \
+     FROM pg_constraint con \
+     JOIN pg_class c ON c.oid = con.conrelid \
+     JOIN pg_namespace n ON n.oid = c.relnamespace \
+     LEFT JOIN pg_class rc ON rc.oid = con.confrelid \
+     LEFT JOIN pg_namespace rn ON rn.oid = rc.relnamespace \
+     WHERE con.contype IN ('p', 'f', 'u', 'c', 'x') \
+       AND n.nspname <> 'information_schema' AND n.nspname !~ '^pg_' \
+     ORDER BY 1, 2, 3";
+
+// pg_get_indexdef with a column number returns the column name or the indexed expression
+const INDEXES_SQL: &str = "SELECT n.nspname AS table_schema, t.relname AS table_name, i.relname AS index_name, \
+            ix.indisunique AS is_unique, ix.indisprimary AS is_primary, \
+            array_to_json(ARRAY( \
+                SELECT pg_get_indexdef(ix.indexrelid, k.ord, true) \
+                FROM generate_series(1, ix.indnkeyatts::int) AS k(ord) \
+            )) AS columns \
+     FROM pg_index ix \
+     JOIN pg_class i ON i.oid = ix.indexrelid \
+     JOIN pg_class t ON t.oid = ix.indrelid \
+     JOIN pg_namespace n ON n.oid = t.relnamespace \
+     WHERE n.nspname <> 'information_schema' AND n.nspname !~ '^pg_' \
+     ORDER BY 1, 2, 3";
+
+fn relkind_name(relkind: Option<&str>) -> &'static str {
+    match relkind {
+        Some("v") => "view",
+        Some("m") => "materialized view",
+        Some("f") => "foreign table",
+        _ => "table",
+    }
+}
+
+fn constraint_kind(contype: Option<&str>) -> &'static str {
+    match contype {
+        Some("p") => "primary key",
+        Some("f") => "foreign key",
+        Some("u") => "unique",
+        Some("x") => "exclusion",
+        _ => "check",
+    }
+}
+
+pub fn connect_options(conn: &Connection, password: &str) -> PgConnectOptions {
+    let mut options = PgConnectOptions::new()
+        .host(&conn.host)
+        .port(conn.port as u16)
+        .username(&conn.username)
+        .password(password)
+        .application_name("nexusdb");
+
+    if let Some(database) = conn.database_name.as_deref().filter(|d| !d.is_empty()) {
+        options = options.database(database);
+    }
+
+    options
+}
+
+pub async fn connect(conn: &Connection, password: &str) -> Result<PgConnection, anyhow::Error> {
+    let options = connect_options(conn, password);
+    tokio::time::timeout(CONNECT_TIMEOUT, options.connect())
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out connecting to {}:{}", conn.host, conn.port))?
+        .map_err(|e| anyhow::anyhow!("Failed to connect: {}", e))
+}
+
+async fn run(pg: &mut PgConnection, query: &str) -> Result<QueryOutput, anyhow::Error> {
+    run_limited(pg, query, None).await
+}
+
+/// Runs the query, keeping at most `max_rows + 1` rows so the caller can tell
+/// the result was cut short without holding the whole of it
+async fn run_limited(
+    pg: &mut PgConnection,
+    query: &str,
+    max_rows: Option<usize>,
+) -> Result<QueryOutput, anyhow::Error> {
+    let mut output = QueryOutput::default();
+    let mut rows_affected = 0u64;
+
+    {
+        // A bare &str without arguments goes through the simple query protocol,
+        // which allows several statements and returns every value as text.
+        let mut stream = pg.fetch_many(query);
+        while let Some(item) = stream.try_next().await? {
+            match item {
+                Either::Left(result) => rows_affected += result.rows_affected(),
+                Either::Right(row) => {
+                    if output.columns.is_empty() {
+                        output.columns = column_names(&row);
+                    }
+                    // Later statements of the batch still run and count, so
+                    // the stream is drained; only their rows are left out
+                    if max_rows.is_none_or(|max| output.rows.len() <= max) {
+                        output.rows.push(row_to_json(&row));
+                    }
+                }
+            }
+        }
+    }
+
+    // Empty result sets carry no row description, so ask the server for it
+    if output.rows.is_empty() {
+        if let Ok(describe) = pg.describe(query).await {
+            output.columns = describe.columns().iter().map(|c| c.name().to_string()).collect();
+        }
+    }
+
+    output.rows_affected = Some(rows_affected);
+    Ok(output)
+}
+
+fn column_names(row: &PgRow) -> Vec<String> {
+    row.columns().iter().map(|c| c.name().to_string()).collect()
+}
+
+fn row_to_json(row: &PgRow) -> Value {
+    let mut object = Map::new();
+    for column in row.columns() {
+        let value = value_to_json(row, column.ordinal());
+        object.insert(column.name().to_string(), value);
+    }
+    Value::Object(object)
+}
+
+fn value_to_json(row: &PgRow, index: usize) -> Value {
+    let type_name = match row.try_get_raw(index) {
+        Ok(raw) if raw.is_null() => return Value::Null,
+        Ok(raw) => raw.type_info().name().to_string(),
+        Err(_) => return Value::Null,
+    };
+
+    match row.try_get_unchecked::<String, _>(index) {
+        Ok(text) => text_to_json(&type_name, text),
+        Err(_) => Value::Null,
+    }
+}
+
+/// Maps the text representation of a PostgreSQL value into JSON
+pub fn text_to_json(type_name: &str, text: String) -> Value {
+    match type_name {
+        "BOOL" => Value::Bool(text == "t" || text == "true"),
+        "INT2" | "INT4" | "INT8" | "OID" => text
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or(Value::String(text)),
+        "FLOAT4" | "FLOAT8" => text
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .unwrap_or(Value::String(text)),
+        "JSON" | "JSONB" => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        // NUMERIC is kept as text so no precision is lost
+        _ => Value::String(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_to_json() {
+        assert_eq!(text_to_json("BOOL", "t".into()), Value::Bool(true));
+        assert_eq!(text_to_json("INT8", "42".into()), Value::from(42));
+        assert_eq!(text_to_json("FLOAT8", "1.5".into()), Value::from(1.5));
+        assert_eq!(text_to_json("FLOAT8", "NaN".into()), Value::String("NaN".into()));
+        assert_eq!(text_to_json("NUMERIC", "10.10".into()), Value::String("10.10".into()));
+        assert_eq!(
+            text_to_json("JSONB", r#"{"a": 1}"#.into()),
+            serde_json::json!({"a": 1})
+        );
+    }
+}
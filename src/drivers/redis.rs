@@ -0,0 +1,567 @@
+use ::redis::aio::MultiplexedConnection;
+use ::redis::{ConnectionAddr, ConnectionInfo, RedisConnectionInfo, Value as RedisValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+use crate::drivers::{
+    Catalog, DatabaseDriver, DriverCapabilities, DriverInfo, DriverSession, QueryLanguage, QueryOutput,
+    CONNECT_TIMEOUT,
+};
+use crate::models::Connection;
+
+// Page size used when walking collections with the *SCAN family
+const SCAN_COUNT: usize = 100;
+
+// Longest a single command may take when the connection's policy sets no
+// statement timeout, so BLPOP k 0 and the like don't hold the request forever
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Commands that turn the connection into a stream of messages instead of
+// answering once
+const STREAMING_COMMANDS: &[&str] = &[
+    "SUBSCRIBE", "PSUBSCRIBE", "SSUBSCRIBE", "UNSUBSCRIBE", "PUNSUBSCRIBE", "SUNSUBSCRIBE", "MONITOR", "SYNC",
+    "PSYNC",
+];
+
+#[derive(Debug, Serialize)]
+pub struct KeyInfo {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub key_type: String,
+    pub ttl: i64,
+    pub memory_usage: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyValue {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub key_type: String,
+    pub ttl: i64,
+    pub length: i64,
+    pub value: Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KeyWrite {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub key_type: String,
+    pub value: Value,
+    pub ttl: Option<i64>,
+}
+
+pub fn connection_info(conn: &Connection, password: &str) -> Result<ConnectionInfo, anyhow::Error> {
+    // database_name holds the logical database index for Redis
+    let db = match conn.database_name.as_deref().filter(|d| !d.is_empty()) {
+        Some(db) => db
+            .parse::<i64>()
+            .map_err(|_| anyhow::anyhow!("Redis database must be a number, got '{}'", db))?,
+        None => 0,
+    };
+
+    Ok(ConnectionInfo {
+        addr: ConnectionAddr::Tcp(conn.host.clone(), conn.port as u16),
+        redis: RedisConnectionInfo {
+            db,
+            username: Some(conn.username.clone()).filter(|u| !u.is_empty()),
+            password: Some(password.to_string()).filter(|p| !p.is_empty()),
+        },
+    })
+}
+
+pub async fn connect(conn: &Connection, password: &str) -> Result<MultiplexedConnection, anyhow::Error> {
+    let client = ::redis::Client::open(connection_info(conn, password)?)?;
+    tokio::time::timeout(CONNECT_TIMEOUT, client.get_multiplexed_tokio_connection())
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out connecting to {}:{}", conn.host, conn.port))?
+        .map_err(|e| anyhow::anyhow!("Failed to connect: {}", e))
+}
+
+pub struct RedisDriver;
+
+#[axum::async_trait]
+impl DatabaseDriver for RedisDriver {
+    fn info(&self) -> DriverInfo {
+        DriverInfo {
+            name: "redis",
+            display_name: "Redis",
+            aliases: &[],
+            query_language: QueryLanguage::Redis,
+            default_port: Some(6379),
+            requires_host: true,
+            capabilities: DriverCapabilities {
+                // MULTI/EXEC blocks
+                transactions: true,
+                explain: false,
+                schemas: false,
+                streaming: false,
+                cancel: false,
+                introspection: true,
+            },
+        }
+    }
+
+    async fn connect(&self, conn: &Connection, password: &str) -> Result<Box<dyn DriverSession>, anyhow::Error> {
+        Ok(Box::new(RedisSession {
+            redis: connect(conn, password).await?,
+        }))
+    }
+}
+
+pub struct RedisSession {
+    redis: MultiplexedConnection,
+}
+
+#[axum::async_trait]
+impl DriverSession for RedisSession {
+    async fn server_version(&mut self) -> Result<String, anyhow::Error> {
+        let info: String = ::redis::cmd("INFO").arg("server").query_async(&mut self.redis).await?;
+        info.lines()
+            .find_map(|line| line.strip_prefix("redis_version:"))
+            .map(|version| format!("Redis {}", version.trim()))
+            .ok_or_else(|| anyhow::anyhow!("Server did not report a version"))
+    }
+
+    async fn execute(&mut self, query: &str) -> Result<QueryOutput, anyhow::Error> {
+        run(&mut self.redis, query).await
+    }
+
+    async fn introspect(&mut self) -> Result<Catalog, anyhow::Error> {
+        // Lines look like "db0:keys=12,expires=0,avg_ttl=0"
+        let info: String = ::redis::cmd("INFO").arg("keyspace").query_async(&mut self.redis).await?;
+        let databases = info
+            .lines()
+            .filter_map(|line| line.split_once(':').map(|(db, _)| db))
+            .filter(|db| db.starts_with("db"))
+            .map(str::to_string)
+            .collect();
+
+        Ok(Catalog {
+            databases,
+            ..Default::default()
+        })
+    }
+
+    async fn close(self: Box<Self>) {}
+}
+
+/// Runs one command per line and returns a row per command with its reply
+async fn run(redis: &mut MultiplexedConnection, query: &str) -> Result<QueryOutput, anyhow::Error> {
+    let mut output = QueryOutput {
+        columns: vec!["command".to_string(), "reply".to_string()],
+        ..Default::default()
+    };
+
+    for (line, args) in parse_script(query)? {
+        let mut cmd = ::redis::cmd(&args[0]);
+        for arg in &args[1..] {
+            cmd.arg(arg);
+        }
+
+        let reply: RedisValue = tokio::time::timeout(COMMAND_TIMEOUT, cmd.query_async(redis))
+            .await
+            .map_err(|_| anyhow::anyhow!("{} took longer than {} seconds", args[0], COMMAND_TIMEOUT.as_secs()))??;
+        output.rows.push(serde_json::json!({
+            "command": line,
+            "reply": value_to_json(&reply),
+        }));
+    }
+
+    Ok(output)
+}
+
+/// Parses every line of a script before the first one runs, refusing
+/// commands that never give a single reply
+fn parse_script(query: &str) -> Result<Vec<(&str, Vec<String>)>, anyhow::Error> {
+    let mut commands = Vec::new();
+    for line in query.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let args = parse_command_line(line)?;
+        let name = args[0].to_uppercase();
+        if STREAMING_COMMANDS.contains(&name.as_str()) {
+            return Err(anyhow::anyhow!("{} streams replies and can't be run as a query", name));
+        }
+        commands.push((line, args));
+    }
+    Ok(commands)
+}
+
+/// Splits a command line into arguments, honouring single and double quotes
+pub fn parse_command_line(line: &str) -> Result<Vec<String>, anyhow::Error> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some('"') if c == '\\' => match chars.next() {
+                Some('n') => current.push('\n'),
+                Some('t') => current.push('\t'),
+                Some(other) => current.push(other),
+                None => return Err(anyhow::anyhow!("Unterminated escape in command")),
+            },
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(anyhow::anyhow!("Unterminated quote in command"));
+    }
+    if in_arg {
+        args.push(current);
+    }
+    if args.is_empty() {
+        return Err(anyhow::anyhow!("Empty command"));
+    }
+
+    Ok(args)
+}
+
+pub fn value_to_json(value: &RedisValue) -> Value {
+    match value {
+        RedisValue::Nil => Value::Null,
+        RedisValue::Int(i) => Value::from(*i),
+        RedisValue::Data(bytes) => bytes_to_json(bytes),
+        RedisValue::Bulk(items) => Value::Array(items.iter().map(value_to_json).collect()),
+        RedisValue::Status(status) => Value::String(status.clone()),
+        RedisValue::Okay => Value::String("OK".to_string()),
+    }
+}
+
+fn bytes_to_json(bytes: &[u8]) -> Value {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Value::String(text.to_string()),
+        Err(_) => Value::String(format!("0x{}", hex::encode(bytes))),
+    }
+}
+
+fn value_to_key(value: &RedisValue) -> String {
+    match value {
+        RedisValue::Data(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        RedisValue::Status(status) => status.clone(),
+        other => value_to_json(other).to_string(),
+    }
+}
+
+/// Turns a flat [k1, v1, k2, v2, ...] reply into a JSON object
+fn pairs_to_object(items: &[RedisValue]) -> Map<String, Value> {
+    items
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (value_to_key(&pair[0]), value_to_json(&pair[1])))
+        .collect()
+}
+
+fn json_to_arg(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+pub async fn scan_keys(
+    redis: &mut MultiplexedConnection,
+    cursor: u64,
+    pattern: &str,
+    count: usize,
+) -> Result<(u64, Vec<KeyInfo>), anyhow::Error> {
+    let (next_cursor, keys): (u64, Vec<Vec<u8>>) = ::redis::cmd("SCAN")
+        .arg(cursor)
+        .arg("MATCH")
+        .arg(pattern)
+        .arg("COUNT")
+        .arg(count)
+        .query_async(redis)
+        .await?;
+
+    if keys.is_empty() {
+        return Ok((next_cursor, Vec::new()));
+    }
+
+    let mut pipe = ::redis::pipe();
+    for key in &keys {
+        pipe.cmd("TYPE").arg(key).cmd("TTL").arg(key);
+    }
+    let details: Vec<RedisValue> = pipe.query_async(redis).await?;
+
+    // MEMORY USAGE is not available everywhere (older or managed servers)
+    let mut memory_pipe = ::redis::pipe();
+    for key in &keys {
+        memory_pipe.cmd("MEMORY").arg("USAGE").arg(key);
+    }
+    let memory: Vec<Option<i64>> = memory_pipe
+        .query_async(redis)
+        .await
+        .unwrap_or_else(|_| vec![None; keys.len()]);
+
+    let infos = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| KeyInfo {
+            key: String::from_utf8_lossy(key).into_owned(),
+            key_type: details
+                .get(i * 2)
+                .map(value_to_key)
+                .unwrap_or_else(|| "none".to_string()),
+            ttl: match details.get(i * 2 + 1) {
+                Some(RedisValue::Int(ttl)) => *ttl,
+                _ => -1,
+            },
+            memory_usage: memory.get(i).copied().flatten(),
+        })
+        .collect();
+
+    Ok((next_cursor, infos))
+}
+
+async fn scan_collection(
+    redis: &mut MultiplexedConnection,
+    command: &str,
+    key: &str,
+    limit: usize,
+) -> Result<Vec<RedisValue>, anyhow::Error> {
+    let mut cursor = 0u64;
+    let mut items = Vec::new();
+
+    loop {
+        let (next, batch): (u64, Vec<RedisValue>) = ::redis::cmd(command)
+            .arg(key)
+            .arg(cursor)
+            .arg("COUNT")
+            .arg(SCAN_COUNT)
+            .query_async(redis)
+            .await?;
+        items.extend(batch);
+        cursor = next;
+
+        if cursor == 0 || items.len() >= limit {
+            break;
+        }
+    }
+
+    Ok(items)
+}
+
+/// Reads a key according to its type. Collections are capped at `limit` items.
+pub async fn read_key(
+    redis: &mut MultiplexedConnection,
+    key: &str,
+    limit: usize,
+) -> Result<Option<KeyValue>, anyhow::Error> {
+    let key_type: String = ::redis::cmd("TYPE").arg(key).query_async(redis).await?;
+    if key_type == "none" {
+        return Ok(None);
+    }
+
+    let ttl: i64 = ::redis::cmd("TTL").arg(key).query_async(redis).await?;
+    let stop = limit as isize - 1;
+
+    let (length_cmd, value) = match key_type.as_str() {
+        "string" => {
+            let raw: RedisValue = ::redis::cmd("GET").arg(key).query_async(redis).await?;
+            ("STRLEN", value_to_json(&raw))
+        }
+        "hash" => {
+            let items = scan_collection(redis, "HSCAN", key, limit * 2).await?;
+            ("HLEN", Value::Object(pairs_to_object(&items)))
+        }
+        "list" => {
+            let raw: RedisValue = ::redis::cmd("LRANGE").arg(key).arg(0).arg(stop).query_async(redis).await?;
+            ("LLEN", value_to_json(&raw))
+        }
+        "set" => {
+            let items = scan_collection(redis, "SSCAN", key, limit).await?;
+            ("SCARD", Value::Array(items.iter().take(limit).map(value_to_json).collect()))
+        }
+        "zset" => {
+            let raw: Vec<RedisValue> = ::redis::cmd("ZRANGE")
+                .arg(key)
+                .arg(0)
+                .arg(stop)
+                .arg("WITHSCORES")
+                .query_async(redis)
+                .await?;
+            let members = raw
+                .chunks(2)
+                .filter(|pair| pair.len() == 2)
+                .map(|pair| {
+                    let score = value_to_key(&pair[1]).parse::<f64>().unwrap_or_default();
+                    serde_json::json!({ "member": value_to_json(&pair[0]), "score": score })
+                })
+                .collect();
+            ("ZCARD", Value::Array(members))
+        }
+        "stream" => {
+            let raw: Vec<RedisValue> = ::redis::cmd("XRANGE")
+                .arg(key)
+                .arg("-")
+                .arg("+")
+                .arg("COUNT")
+                .arg(limit)
+                .query_async(redis)
+                .await?;
+            let entries = raw
+                .iter()
+                .filter_map(|entry| match entry {
+                    RedisValue::Bulk(parts) if parts.len() == 2 => {
+                        let fields = match &parts[1] {
+                            RedisValue::Bulk(fields) => pairs_to_object(fields),
+                            _ => Map::new(),
+                        };
+                        Some(serde_json::json!({ "id": value_to_key(&parts[0]), "fields": fields }))
+                    }
+                    _ => None,
+                })
+                .collect();
+            ("XLEN", Value::Array(entries))
+        }
+        other => return Err(anyhow::anyhow!("Unsupported key type: {}", other)),
+    };
+
+    let length: i64 = ::redis::cmd(length_cmd).arg(key).query_async(redis).await?;
+
+    Ok(Some(KeyValue {
+        key: key.to_string(),
+        key_type,
+        ttl,
+        length,
+        value,
+    }))
+}
+
+/// Replaces the whole value of a key atomically, keeping the requested TTL
+pub async fn write_key(redis: &mut MultiplexedConnection, write: &KeyWrite) -> Result<(), anyhow::Error> {
+    let key = write.key.as_str();
+    let mut pipe = ::redis::pipe();
+    pipe.atomic().cmd("DEL").arg(key).ignore();
+
+    let invalid = || anyhow::anyhow!("Invalid value for a {} key", write.key_type);
+
+    match write.key_type.as_str() {
+        "string" => {
+            pipe.cmd("SET").arg(key).arg(json_to_arg(&write.value)).ignore();
+        }
+        "hash" => {
+            let fields = write.value.as_object().ok_or_else(invalid)?;
+            if !fields.is_empty() {
+                let cmd = pipe.cmd("HSET").arg(key);
+                for (field, value) in fields {
+                    cmd.arg(field).arg(json_to_arg(value));
+                }
+                cmd.ignore();
+            }
+        }
+        "list" | "set" => {
+            let items = write.value.as_array().ok_or_else(invalid)?;
+            if !items.is_empty() {
+                let command = if write.key_type == "list" { "RPUSH" } else { "SADD" };
+                let cmd = pipe.cmd(command).arg(key);
+                for item in items {
+                    cmd.arg(json_to_arg(item));
+                }
+                cmd.ignore();
+            }
+        }
+        "zset" => {
+            let members = write.value.as_array().ok_or_else(invalid)?;
+            if !members.is_empty() {
+                let cmd = pipe.cmd("ZADD").arg(key);
+                for member in members {
+                    let score = member.get("score").and_then(Value::as_f64).ok_or_else(invalid)?;
+                    let name = member.get("member").ok_or_else(invalid)?;
+                    cmd.arg(score).arg(json_to_arg(name));
+                }
+                cmd.ignore();
+            }
+        }
+        "stream" => {
+            let entries = write.value.as_array().ok_or_else(invalid)?;
+            for entry in entries {
+                let id = entry.get("id").and_then(Value::as_str).unwrap_or("*");
+                let fields = entry.get("fields").and_then(Value::as_object).ok_or_else(invalid)?;
+                if fields.is_empty() {
+                    return Err(invalid());
+                }
+                let cmd = pipe.cmd("XADD").arg(key).arg(id);
+                for (field, value) in fields {
+                    cmd.arg(field).arg(json_to_arg(value));
+                }
+                cmd.ignore();
+            }
+        }
+        other => return Err(anyhow::anyhow!("Unsupported key type: {}", other)),
+    }
+
+    if let Some(ttl) = write.ttl.filter(|ttl| *ttl > 0) {
+        pipe.cmd("EXPIRE").arg(key).arg(ttl).ignore();
+    }
+
+    pipe.query_async::<_, ()>(redis).await?;
+    Ok(())
+}
+
+pub async fn delete_key(redis: &mut MultiplexedConnection, key: &str) -> Result<bool, anyhow::Error> {
+    let deleted: i64 = ::redis::cmd("DEL").arg(key).query_async(redis).await?;
+    Ok(deleted > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command_line() {
+        assert_eq!(parse_command_line("GET user:1").unwrap(), vec!["GET", "user:1"]);
+        assert_eq!(
+            parse_command_line(r#"SET greeting "hello world" EX 10"#).unwrap(),
+            vec!["SET", "greeting", "hello world", "EX", "10"]
+        );
+        assert_eq!(
+            parse_command_line(r#"SET k 'it"s' """#).unwrap(),
+            vec!["SET", "k", "it\"s", ""]
+        );
+        assert!(parse_command_line(r#"SET k "open"#).is_err());
+        assert!(parse_command_line("   ").is_err());
+    }
+
+    #[test]
+    fn test_streaming_commands_are_refused() {
+        assert_eq!(parse_script("GET a\n# note\nBLPOP q 0").unwrap().len(), 2);
+        assert!(parse_script("GET a\nsubscribe news").is_err());
+        assert!(parse_script("MONITOR").is_err());
+    }
+
+    #[test]
+    fn test_value_to_json() {
+        let reply = RedisValue::Bulk(vec![
+            RedisValue::Data(b"a".to_vec()),
+            RedisValue::Int(1),
+            RedisValue::Nil,
+            RedisValue::Data(vec![0xff]),
+        ]);
+        assert_eq!(value_to_json(&reply), serde_json::json!(["a", 1, null, "0xff"]));
+        assert_eq!(value_to_json(&RedisValue::Okay), Value::String("OK".into()));
+    }
+}
@@ -0,0 +1,522 @@
+use futures::TryStreamExt;
+use serde_json::{Map, Number, Value};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqliteRow};
+use sqlx::{Column, ConnectOptions, Connection as _, Either, Executor, Row, TypeInfo, ValueRef};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::drivers::{
+    row_bool, row_i64, row_str, CancelToken, Catalog, ColumnInfo, ConstraintInfo, DatabaseDriver,
+    DriverCapabilities, DriverInfo, DriverSession, ForeignKeyInfo, QueryCanceller, QueryLanguage, QueryOutput,
+    SessionLimits, TableInfo, CONNECT_TIMEOUT,
+};
+use crate::models::Connection;
+use crate::security::validation;
+
+// Every SQLite database file starts with this header
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+pub const ALLOWED_EXTENSIONS: &[&str] = &["sqlite", "sqlite3", "db"];
+
+pub fn connect_options(path: &str) -> SqliteConnectOptions {
+    SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(false)
+}
+
+pub async fn connect(conn: &Connection) -> Result<SqliteConnection, anyhow::Error> {
+    let path = conn
+        .database_name
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("SQLite connection has no file path"))?;
+
+    let options = connect_options(path);
+    let mut lite = tokio::time::timeout(CONNECT_TIMEOUT, options.connect())
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out opening {}", path))?
+        .map_err(|e| anyhow::anyhow!("Failed to open database: {}", e))?;
+
+    // ATTACH (and VACUUM INTO, which attaches its target) would open any file
+    // the server can read or write, the metadata database included, whatever
+    // resolve_path allowed
+    let mut handle = lite.lock_handle().await?;
+    // SAFETY: the handle is locked and its connection open for the whole call
+    unsafe {
+        libsqlite3_sys::sqlite3_limit(handle.as_raw_handle().as_ptr(), libsqlite3_sys::SQLITE_LIMIT_ATTACHED, 0);
+    }
+    drop(handle);
+    Ok(lite)
+}
+
+pub struct SqliteDriver;
+
+#[axum::async_trait]
+impl DatabaseDriver for SqliteDriver {
+    fn info(&self) -> DriverInfo {
+        DriverInfo {
+            name: "sqlite",
+            display_name: "SQLite",
+            aliases: &["sqlite3"],
+            query_language: QueryLanguage::Sql,
+            default_port: None,
+            requires_host: false,
+            capabilities: DriverCapabilities {
+                transactions: true,
+                explain: true,
+                schemas: false,
+                streaming: false,
+                cancel: true,
+                introspection: true,
+            },
+        }
+    }
+
+    async fn connect(&self, conn: &Connection, _password: &str) -> Result<Box<dyn DriverSession>, anyhow::Error> {
+        let mut lite = connect(conn).await?;
+
+        // There is no server to send a cancel to; a progress handler polls the
+        // flag and interrupts the running statement once it is set
+        let canceller = Arc::new(SqliteCanceller::default());
+        let cancelled = canceller.cancelled.clone();
+        lite.lock_handle()
+            .await?
+            .set_progress_handler(1000, move || !cancelled.load(Ordering::Relaxed));
+
+        Ok(Box::new(SqliteSession {
+            lite,
+            canceller,
+            max_rows: None,
+        }))
+    }
+}
+
+pub struct SqliteSession {
+    lite: SqliteConnection,
+    canceller: Arc<SqliteCanceller>,
+    max_rows: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct SqliteCanceller {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[axum::async_trait]
+impl QueryCanceller for SqliteCanceller {
+    async fn cancel(&self) -> Result<(), anyhow::Error> {
+        self.cancelled.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[axum::async_trait]
+impl DriverSession for SqliteSession {
+    async fn server_version(&mut self) -> Result<String, anyhow::Error> {
+        let output = run(&mut self.lite, "SELECT sqlite_version() AS version").await?;
+        output
+            .rows
+            .first()
+            .and_then(|r| row_str(r, "version"))
+            .map(|v| format!("SQLite {}", v))
+            .ok_or_else(|| anyhow::anyhow!("SQLite did not report a version"))
+    }
+
+    async fn execute(&mut self, query: &str) -> Result<QueryOutput, anyhow::Error> {
+        if let Some(keyword) = validation::sqlite_file_access(query) {
+            return Err(anyhow::anyhow!("{} is not allowed on SQLite connections", keyword));
+        }
+        run_limited(&mut self.lite, query, self.max_rows).await
+    }
+
+    async fn restrict(&mut self, limits: &SessionLimits) -> Result<(), anyhow::Error> {
+        self.max_rows = limits.max_rows;
+        if limits.read_only {
+            self.lite.execute("PRAGMA query_only = ON").await?;
+        }
+        Ok(())
+    }
+
+    fn cancel_token(&self) -> Option<CancelToken> {
+        Some(self.canceller.clone())
+    }
+
+    async fn introspect(&mut self) -> Result<Catalog, anyhow::Error> {
+        let databases = run(&mut self.lite, "PRAGMA database_list").await?;
+        let schemas: Vec<String> = databases.rows.iter().filter_map(|r| row_str(r, "name")).collect();
+        let mut catalog = Catalog {
+            databases: schemas.clone(),
+            schemas: schemas.clone(),
+            tables: Vec::new(),
+        };
+
+        // Every attached database is a schema with its own sqlite_master
+        for schema in &schemas {
+            let ident = quote_identifier(schema);
+            let literal = quote_literal(schema);
+
+            let tables = run(
+                &mut self.lite,
+                &format!(
+                    "SELECT name, type FROM {ident}.sqlite_master \
+                     WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%' ORDER BY name"
+                ),
+            )
+            .await?;
+            for row in &tables.rows {
+                catalog.tables.push(TableInfo::new(
+                    Some(schema.clone()),
+                    row_str(row, "name").unwrap_or_default(),
+                    &row_str(row, "type").unwrap_or_else(|| "table".to_string()),
+                ));
+            }
+
+            let columns = run(
+                &mut self.lite,
+                &format!(
+                    "SELECT {literal} AS table_schema, m.name AS table_name, p.name AS column_name, \
+                            p.type AS data_type, p.\"notnull\" AS not_null, p.dflt_value AS column_default, \
+                            p.pk AS pk, p.cid + 1 AS ordinal \
+                     FROM {ident}.sqlite_master m JOIN pragma_table_info(m.name, {literal}) p \
+                     WHERE m.type IN ('table', 'view') AND m.name NOT LIKE 'sqlite_%' \
+                     ORDER BY m.name, p.cid"
+                ),
+            )
+            .await?;
+            let foreign_keys = run(
+                &mut self.lite,
+                &format!(
+                    "SELECT {literal} AS table_schema, m.name AS table_name, f.id AS id, f.seq AS seq, \
+                            f.\"from\" AS column_name, f.\"table\" AS referenced_table, f.\"to\" AS referenced_column \
+                     FROM {ident}.sqlite_master m JOIN pragma_foreign_key_list(m.name, {literal}) f \
+                     WHERE m.type = 'table' \
+                     ORDER BY m.name, f.id, f.seq"
+                ),
+            )
+            .await?;
+            let indexes = run(
+                &mut self.lite,
+                &format!(
+                    "SELECT {literal} AS table_schema, m.name AS table_name, il.name AS index_name, \
+                            il.\"unique\" AS is_unique, il.origin AS origin, ii.name AS column_name \
+                     FROM {ident}.sqlite_master m \
+                     JOIN pragma_index_list(m.name, {literal}) il \
+                     JOIN pragma_index_info(il.name, {literal}) ii \
+                     WHERE m.type = 'table' \
+                     ORDER BY m.name, il.name, ii.seqno"
+                ),
+            )
+            .await?;
+
+            let positions = catalog.table_positions();
+            let mut primary_keys: Vec<(usize, i64, String)> = Vec::new();
+
+            for row in &columns.rows {
+                let key = (row_str(row, "table_schema"), row_str(row, "table_name").unwrap_or_default());
+                let Some(&position) = positions.get(&key) else {
+                    continue;
+                };
+                let name = row_str(row, "column_name").unwrap_or_default();
+                // pk is the position of the column inside the primary key, 0 when not part of it
+                let pk = row_i64(row, "pk").unwrap_or_default();
+                if pk > 0 {
+                    primary_keys.push((position, pk, name.clone()));
+                }
+                catalog.tables[position].columns.push(ColumnInfo {
+                    name,
+                    data_type: row_str(row, "data_type").unwrap_or_default(),
+                    nullable: !row_bool(row, "not_null") && pk == 0,
+                    default: row_str(row, "column_default"),
+                    ordinal: row_i64(row, "ordinal").unwrap_or_default(),
+                });
+            }
+
+            primary_keys.sort();
+            for (position, _, column) in primary_keys {
+                catalog.tables[position].primary_key.push(column);
+            }
+
+            // SQLite foreign keys have no name, rows of one key share an id
+            for row in &foreign_keys.rows {
+                let Some(table) = catalog.table_for_row(&positions, row) else {
+                    continue;
+                };
+                if row_i64(row, "seq") == Some(0) {
+                    table.foreign_keys.push(ForeignKeyInfo {
+                        name: None,
+                        columns: Vec::new(),
+                        referenced_schema: Some(schema.clone()),
+                        referenced_table: row_str(row, "referenced_table").unwrap_or_default(),
+                        referenced_columns: Vec::new(),
+                    });
+                }
+                if let Some(foreign_key) = table.foreign_keys.last_mut() {
+                    foreign_key.columns.push(row_str(row, "column_name").unwrap_or_default());
+                    foreign_key.referenced_columns.extend(row_str(row, "referenced_column"));
+                }
+            }
+
+            // origin is "pk" for the primary key, "u" for UNIQUE constraints and "c" for CREATE INDEX
+            for row in &indexes.rows {
+                let Some(table) = catalog.table_for_row(&positions, row) else {
+                    continue;
+                };
+                let name = row_str(row, "index_name").unwrap_or_default();
+                let origin = row_str(row, "origin");
+                let column = row_str(row, "column_name");
+                if origin.as_deref() == Some("u") {
+                    table.add_constraint_column(&name, "unique", column.clone());
+                }
+                table.add_index_column(&name, row_bool(row, "is_unique"), origin.as_deref() == Some("pk"), column);
+            }
+
+            // Primary and foreign keys are unnamed, list them as constraints from the keys
+            for table in catalog.tables.iter_mut().filter(|t| t.schema.as_deref() == Some(schema.as_str())) {
+                let mut keys = Vec::new();
+                if !table.primary_key.is_empty() {
+                    keys.push(("primary key", table.primary_key.clone()));
+                }
+                keys.extend(table.foreign_keys.iter().map(|fk| ("foreign key", fk.columns.clone())));
+
+                for (kind, columns) in keys.into_iter().rev() {
+                    table.constraints.insert(
+                        0,
+                        ConstraintInfo {
+                            name: None,
+                            kind: kind.to_string(),
+                            columns,
+                            definition: None,
+                        },
+                    );
+                }
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    async fn close(self: Box<Self>) {
+        let _ = self.lite.close().await;
+    }
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+async fn run(lite: &mut SqliteConnection, query: &str) -> Result<QueryOutput, anyhow::Error> {
+    run_limited(lite, query, None).await
+}
+
+/// Runs the query, keeping at most `max_rows + 1` rows so the caller can tell
+/// the result was cut short without holding the whole of it
+async fn run_limited(
+    lite: &mut SqliteConnection,
+    query: &str,
+    max_rows: Option<usize>,
+) -> Result<QueryOutput, anyhow::Error> {
+    let mut output = QueryOutput::default();
+    let mut rows_affected = 0u64;
+
+    {
+        let mut stream = lite.fetch_many(query);
+        while let Some(item) = stream.try_next().await? {
+            match item {
+                Either::Left(result) => rows_affected += result.rows_affected(),
+                Either::Right(row) => {
+                    if output.columns.is_empty() {
+                        output.columns = column_names(&row);
+                    }
+                    // Later statements of the batch still run and count, so
+                    // the stream is drained; only their rows are left out
+                    if max_rows.is_none_or(|max| output.rows.len() <= max) {
+                        output.rows.push(row_to_json(&row));
+                    }
+                }
+            }
+        }
+    }
+
+    if output.rows.is_empty() {
+        if let Ok(describe) = lite.describe(query).await {
+            output.columns = describe.columns().iter().map(|c| c.name().to_string()).collect();
+        }
+    }
+
+    output.rows_affected = Some(rows_affected);
+    Ok(output)
+}
+
+fn column_names(row: &SqliteRow) -> Vec<String> {
+    row.columns().iter().map(|c| c.name().to_string()).collect()
+}
+
+fn row_to_json(row: &SqliteRow) -> Value {
+    let mut object = Map::new();
+    for column in row.columns() {
+        let value = value_to_json(row, column.ordinal());
+        object.insert(column.name().to_string(), value);
+    }
+    Value::Object(object)
+}
+
+fn value_to_json(row: &SqliteRow, index: usize) -> Value {
+    // SQLite is dynamically typed, so dispatch on the storage class of the value
+    let type_name = match row.try_get_raw(index) {
+        Ok(raw) if raw.is_null() => return Value::Null,
+        Ok(raw) => raw.type_info().name().to_string(),
+        Err(_) => return Value::Null,
+    };
+
+    let value = match type_name.as_str() {
+        "INTEGER" => row.try_get_unchecked::<i64, _>(index).map(Value::from),
+        "REAL" => row.try_get_unchecked::<f64, _>(index).map(|f| {
+            Number::from_f64(f).map(Value::Number).unwrap_or(Value::String(f.to_string()))
+        }),
+        "BLOB" => row
+            .try_get_unchecked::<Vec<u8>, _>(index)
+            .map(|b| Value::String(format!("0x{}", hex::encode(b)))),
+        _ => row.try_get_unchecked::<String, _>(index).map(Value::String),
+    };
+
+    value.unwrap_or(Value::Null)
+}
+
+pub fn has_sqlite_header(bytes: &[u8]) -> bool {
+    bytes.starts_with(SQLITE_HEADER)
+}
+
+pub fn has_allowed_extension(file_name: &str) -> bool {
+    Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| ALLOWED_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Resolves a server-side database path and checks that it lives inside one
+/// of the allowed directories.
+pub fn resolve_path(path: &str, allowed_dirs: &[PathBuf]) -> Result<PathBuf, anyhow::Error> {
+    let resolved = Path::new(path)
+        .canonicalize()
+        .map_err(|_| anyhow::anyhow!("SQLite file not found: {}", path))?;
+
+    if !resolved.is_file() {
+        return Err(anyhow::anyhow!("SQLite path is not a file: {}", path));
+    }
+
+    let allowed = allowed_dirs
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| resolved.starts_with(dir));
+
+    if !allowed {
+        return Err(anyhow::anyhow!("SQLite path is outside the allowed directories"));
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_checks() {
+        assert!(has_sqlite_header(b"SQLite format 3\0rest-of-page"));
+        assert!(!has_sqlite_header(b"PK\x03\x04"));
+        assert!(has_allowed_extension("app.DB"));
+        assert!(has_allowed_extension("data.sqlite3"));
+        assert!(!has_allowed_extension("dump.sql"));
+    }
+
+    #[test]
+    fn test_resolve_path_outside_allowed_dirs() {
+        let dir = std::env::temp_dir().join(format!("nexusdb-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("app.db");
+        std::fs::write(&file, SQLITE_HEADER).unwrap();
+
+        let allowed = vec![dir.clone()];
+        assert!(resolve_path(file.to_str().unwrap(), &allowed).is_ok());
+        assert!(resolve_path(dir.join("../app.db").to_str().unwrap(), &allowed).is_err());
+        assert!(resolve_path(file.to_str().unwrap(), &[dir.join("other")]).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_metadata_db_cannot_be_attached() {
+        let dir = std::env::temp_dir().join(format!("nexusdb-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let metadata = dir.join("nexusdb.db");
+        let target = dir.join("app.db");
+        for file in [&metadata, &target] {
+            let mut lite = connect_options(file.to_str().unwrap())
+                .create_if_missing(true)
+                .connect()
+                .await
+                .unwrap();
+            lite.execute("CREATE TABLE users (password_hash TEXT)").await.unwrap();
+            lite.close().await.unwrap();
+        }
+
+        let conn = Connection {
+            id: "c".into(),
+            user_id: "u".into(),
+            workspace_id: "w".into(),
+            name: "app".into(),
+            db_type: "sqlite".into(),
+            host: String::new(),
+            port: 0,
+            username: String::new(),
+            encrypted_password: String::new(),
+            database_name: Some(target.to_str().unwrap().to_string()),
+            status: "unknown".into(),
+            last_checked_at: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+
+        // Below the driver's own check, the connection itself refuses to attach
+        let mut lite = connect(&conn).await.unwrap();
+        let attach = format!("ATTACH DATABASE '{}' AS m", metadata.display());
+        assert!(lite.execute(attach.as_str()).await.is_err());
+        let vacuum = format!("VACUUM INTO '{}'", dir.join("copy.db").display());
+        assert!(lite.execute(vacuum.as_str()).await.is_err());
+        assert!(!dir.join("copy.db").exists());
+        lite.close().await.unwrap();
+
+        let mut session = SqliteDriver.connect(&conn, "").await.unwrap();
+        let error = session
+            .execute(&format!("SELECT 1; {}; SELECT * FROM m.users", attach))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("ATTACH is not allowed"));
+        session.close().await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_rows_stops_reading() {
+        let mut lite = connect_options(":memory:").connect().await.unwrap();
+        let query = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000) SELECT i FROM n";
+
+        let output = run_limited(&mut lite, query, Some(10)).await.unwrap();
+        assert_eq!(output.rows.len(), 11);
+        assert_eq!(output.rows[10]["i"], 11);
+        assert_eq!(run(&mut lite, query).await.unwrap().rows.len(), 1000);
+
+        // The statements after a long result still run and count
+        let batch = format!("CREATE TABLE t (x); {}; INSERT INTO t VALUES (1), (2)", query);
+        let output = run_limited(&mut lite, &batch, Some(10)).await.unwrap();
+        assert_eq!(output.rows.len(), 11);
+        assert_eq!(output.rows_affected, Some(2));
+        lite.close().await.unwrap();
+    }
+}
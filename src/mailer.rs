@@ -0,0 +1,263 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use chrono::Utc;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::transport::smtp::extension::ClientId;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use uuid::Uuid;
+
+use crate::config::Config;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[axum::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), anyhow::Error>;
+}
+
+/// Builds the mailer selected with MAILER ("log" or "smtp")
+pub fn from_config(config: &Config) -> Result<Arc<dyn Mailer>, anyhow::Error> {
+    let from: Mailbox = config
+        .mail_from
+        .parse()
+        .with_context(|| format!("Invalid MAIL_FROM '{}'", config.mail_from))?;
+
+    match config.mailer.as_str() {
+        "log" => Ok(Arc::new(FileMailer {
+            dir: PathBuf::from(&config.mail_dir),
+            from,
+        })),
+        "smtp" => Ok(Arc::new(SmtpMailer::new(
+            &config.smtp_host,
+            config.smtp_port,
+            config.smtp_username.clone().zip(config.smtp_password.clone()),
+            config.smtp_starttls,
+            config.smtp_allow_plaintext_auth,
+            from,
+        )?)),
+        other => bail!("Unknown MAILER '{}', expected log or smtp", other),
+    }
+}
+
+/// Development mailer: logs each email and writes it as an .eml file to MAIL_DIR
+pub struct FileMailer {
+    dir: PathBuf,
+    from: Mailbox,
+}
+
+#[axum::async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<(), anyhow::Error> {
+        let message = build_message(&self.from, email)?;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self
+            .dir
+            .join(format!("{}-{}.eml", Utc::now().format("%Y%m%dT%H%M%S"), Uuid::new_v4()));
+        tokio::fs::write(&path, message.formatted()).await?;
+
+        tracing::info!("Email to {} ({}) written to {}", email.to, email.subject, path.display());
+        Ok(())
+    }
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// With `starttls` the connection must be upgraded before anything else is
+    /// sent. Credentials without it would go out in the clear, so they need
+    /// `allow_plaintext_auth`.
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, String)>,
+        starttls: bool,
+        allow_plaintext_auth: bool,
+        from: Mailbox,
+    ) -> Result<Self, anyhow::Error> {
+        let tls = if starttls {
+            Tls::Required(TlsParameters::new(host.to_string())?)
+        } else {
+            Tls::None
+        };
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+            .port(port)
+            .tls(tls)
+            .timeout(Some(SMTP_TIMEOUT))
+            .hello_name(ClientId::Domain("nexusdb".to_string()));
+
+        if let Some((username, password)) = credentials {
+            if !starttls && !allow_plaintext_auth {
+                bail!("SMTP credentials need SMTP_STARTTLS=true (or SMTP_ALLOW_PLAINTEXT_AUTH=true for a trusted relay)");
+            }
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[axum::async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<(), anyhow::Error> {
+        let message = build_message(&self.from, email)?;
+        self.transport
+            .send(message)
+            .await
+            .with_context(|| format!("SMTP delivery to {} failed", email.to))?;
+        Ok(())
+    }
+}
+
+/// The recipient comes from user input; parsing it as a mailbox keeps it
+/// from adding headers of its own
+fn build_message(from: &Mailbox, email: &Email) -> Result<Message, anyhow::Error> {
+    let to: Mailbox = email
+        .to
+        .parse()
+        .with_context(|| format!("Invalid recipient '{}'", email.to))?;
+    if email.subject.contains(['\r', '\n']) {
+        bail!("Line breaks are not allowed in email headers");
+    }
+    // lettre normalises LF but passes a bare CR through to the wire
+    if email.body.replace("\r\n", "\n").contains('\r') {
+        bail!("Bare carriage returns are not allowed in email bodies");
+    }
+
+    Ok(Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(email.subject.as_str())
+        .message_id(Some(format!("<{}@nexusdb>", Uuid::new_v4())))
+        .header(ContentType::TEXT_PLAIN)
+        .body(email.body.clone())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufStream};
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn from() -> Mailbox {
+        "NexusDB <no-reply@example.com>".parse().unwrap()
+    }
+
+    #[test]
+    fn test_message_formatting() {
+        let email = Email {
+            to: "ana@example.com".to_string(),
+            subject: "Verificación".to_string(),
+            body: "Hola\n.\nAdiós".to_string(),
+        };
+        let message = String::from_utf8(build_message(&from(), &email).unwrap().formatted()).unwrap();
+        assert!(message.contains("Subject: =?utf-8?"));
+        assert!(message.contains("To: ana@example.com\r\n"));
+
+        let injected = Email { to: "a@example.com\r\nBcc: x@example.com".to_string(), ..email.clone() };
+        assert!(build_message(&from(), &injected).is_err());
+        let injected = Email { subject: "Hi\r\nBcc: x@example.com".to_string(), ..email.clone() };
+        assert!(build_message(&from(), &injected).is_err());
+
+        let bare_cr = Email { body: "one\rtwo".to_string(), ..email.clone() };
+        assert!(build_message(&from(), &bare_cr).is_err());
+        let crlf = Email { body: "one\r\ntwo".to_string(), ..email };
+        assert!(build_message(&from(), &crlf).is_ok());
+    }
+
+    /// Minimal SMTP sink: accepts one message and returns the commands it saw and the data
+    async fn smtp_sink(listener: TcpListener) -> (Vec<String>, String) {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut stream = BufStream::new(tcp);
+        let mut commands = Vec::new();
+        let mut data = String::new();
+
+        async fn reply(stream: &mut BufStream<TcpStream>, line: &str) {
+            stream.write_all(format!("{}\r\n", line).as_bytes()).await.unwrap();
+            stream.flush().await.unwrap();
+        }
+        reply(&mut stream, "220 sink ready").await;
+
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end().to_string();
+            commands.push(line.clone());
+            match line.split_whitespace().next().unwrap_or_default() {
+                "EHLO" => reply(&mut stream, "250-sink\r\n250 8BITMIME").await,
+                "MAIL" | "RCPT" => reply(&mut stream, "250 OK").await,
+                "DATA" => {
+                    reply(&mut stream, "354 go ahead").await;
+                    loop {
+                        let mut chunk = String::new();
+                        stream.read_line(&mut chunk).await.unwrap();
+                        if chunk == ".\r\n" {
+                            break;
+                        }
+                        data.push_str(&chunk);
+                    }
+                    reply(&mut stream, "250 queued").await;
+                }
+                "QUIT" => {
+                    reply(&mut stream, "221 bye").await;
+                    break;
+                }
+                _ => reply(&mut stream, "502 not implemented").await,
+            }
+        }
+        (commands, data)
+    }
+
+    #[tokio::test]
+    async fn test_smtp_delivery_to_local_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        let email = Email {
+            to: "ana@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "Line one\n.hidden dot".to_string(),
+        };
+        let mailer = SmtpMailer::new("127.0.0.1", port, None, false, false, from()).unwrap();
+        mailer.send(&email).await.unwrap();
+
+        let (commands, data) = sink.await.unwrap();
+        assert_eq!(commands[0], "EHLO nexusdb");
+        assert!(commands.iter().any(|c| c.starts_with("MAIL FROM:<no-reply@example.com>")));
+        assert!(commands.contains(&"RCPT TO:<ana@example.com>".to_string()));
+        assert!(!commands.iter().any(|c| c.starts_with("AUTH")));
+        assert_eq!(commands.last().unwrap(), "QUIT");
+        assert!(data.contains("Subject: Hello\r\n"));
+        assert!(data.ends_with("Line one\r\n..hidden dot\r\n"));
+    }
+
+    #[test]
+    fn test_smtp_credentials_need_tls() {
+        let credentials = || Some(("mailer".to_string(), "secret".to_string()));
+        assert!(SmtpMailer::new("127.0.0.1", 2525, credentials(), false, false, from()).is_err());
+        assert!(SmtpMailer::new("127.0.0.1", 2525, credentials(), false, true, from()).is_ok());
+        assert!(SmtpMailer::new("smtp.example.com", 587, credentials(), true, false, from()).is_ok());
+    }
+}
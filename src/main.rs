@@ -1,22 +1,32 @@
 mod api;
 mod config;
 mod db;
+mod drivers;
+mod mailer;
 mod models;
+mod monitor;
 mod security;
 
 use axum::{
     Router,
+    extract::DefaultBodyLimit,
+    http::HeaderValue,
     middleware,
 };
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::cors::{CorsLayer, Any};
 use tower::ServiceBuilder;
 
-use crate::api::{AppState, create_router};
+use crate::api::AppState;
 use crate::config::Config;
 use crate::db::create_pool;
+use crate::db::repository::UserRepository;
+use crate::drivers::{DriverRegistry, RunningQueries};
+use crate::models::ROLE_ADMIN;
 use crate::security::auth::AuthService;
 use crate::security::encryption::EncryptionService;
+use crate::security::password_policy::PasswordPolicy;
 use crate::security::rate_limit::create_rate_limiter;
 
 #[tokio::main]
@@ -42,50 +52,205 @@ async fn main() -> Result<(), anyhow::Error> {
     // Initialize services
     let auth_service = Arc::new(AuthService::new(
         &config.jwt_secret,
-        config.jwt_expiration_hours,
+        config.access_token_minutes,
     ));
     let encryption_service = Arc::new(EncryptionService::new(&config.encryption_key)?);
 
     // Create shared state
     let state = Arc::new(AppState {
+        config: Arc::new(config.clone()),
         db: db_pool,
         auth_service: auth_service.clone(),
         encryption_service,
+        drivers: Arc::new(DriverRegistry::with_default_drivers()),
+        running_queries: RunningQueries::default(),
+        mailer: mailer::from_config(&config)?,
+        password_policy: PasswordPolicy::from_config(&config)?,
     });
 
+    for user_id in &config.admin_user_ids {
+        if !UserRepository::grant_role(&state.db, user_id, ROLE_ADMIN).await? {
+            tracing::warn!("ADMIN_USER_IDS: no user with id {}", user_id);
+        }
+    }
+
+    monitor::spawn(state.clone(), config.connection_check_interval_secs);
+
     // Configure CORS
-    let cors = CorsLayer::permissive(); // En producción, configurar de manera más restrictiva
+    let cors_origin = config
+        .cors_origin
+        .parse::<HeaderValue>()
+        .map_err(|_| anyhow::anyhow!("Invalid CORS_ORIGIN"))?;
+    let cors = CorsLayer::new()
+        .allow_origin(cors_origin)
+        .allow_methods(Any)
+        .allow_headers(Any);
 
-    // Build protected routes (require authentication)
-    let protected_routes = Router::new()
-        .route("/api/auth/me", axum::routing::get(api::auth::get_current_user))
+    // Build admin routes (require the admin role, checked after authentication)
+    let admin_routes = Router::new()
+        .route("/api/admin/users", axum::routing::get(api::admin::list_users))
+        .route("/api/admin/users/:id/disable", axum::routing::post(api::admin::disable_user))
+        .route("/api/admin/users/:id/enable", axum::routing::post(api::admin::enable_user))
+        .route("/api/admin/bans", axum::routing::get(api::admin::list_bans))
+        .route("/api/admin/bans", axum::routing::post(api::admin::create_ban))
+        .route("/api/admin/bans/:id", axum::routing::delete(api::admin::lift_ban))
+        .route("/api/admin/failed-attempts", axum::routing::get(api::admin::list_failed_attempts))
+        .layer(middleware::from_fn(security::middleware::admin_middleware));
+
+    // Routes that use connection credentials; REQUIRE_2FA applies to them
+    let connection_routes = Router::new()
         .route("/api/connections", axum::routing::post(api::connections::create_connection))
         .route("/api/connections", axum::routing::get(api::connections::list_connections))
+        .route(
+            "/api/connections/sqlite/upload",
+            axum::routing::post(api::connections::upload_sqlite_database)
+                .layer(DefaultBodyLimit::max(config.max_upload_mb * 1024 * 1024)),
+        )
         .route("/api/connections/:id", axum::routing::get(api::connections::get_connection))
+        .route("/api/connections/:id", axum::routing::patch(api::connections::update_connection))
         .route("/api/connections/:id", axum::routing::delete(api::connections::delete_connection))
+        .route("/api/connections/:id/test", axum::routing::post(api::connections::test_connection))
+        .route("/api/connections/:id/policy", axum::routing::get(api::policies::get_policy))
+        .route("/api/connections/:id/policy", axum::routing::put(api::policies::update_policy))
+        .route(
+            "/api/connections/:id/change-requests",
+            axum::routing::get(api::change_requests::list_change_requests),
+        )
+        .route(
+            "/api/connections/:id/change-requests",
+            axum::routing::post(api::change_requests::create_change_request),
+        )
+        .route(
+            "/api/connections/:id/change-requests/:request_id",
+            axum::routing::get(api::change_requests::get_change_request),
+        )
+        .route(
+            "/api/connections/:id/change-requests/:request_id/approve",
+            axum::routing::post(api::change_requests::approve_change_request),
+        )
+        .route(
+            "/api/connections/:id/change-requests/:request_id/reject",
+            axum::routing::post(api::change_requests::reject_change_request),
+        )
+        .route(
+            "/api/connections/:id/change-requests/:request_id/cancel",
+            axum::routing::post(api::change_requests::cancel_change_request),
+        )
+        .route(
+            "/api/connections/:id/change-requests/:request_id/execute",
+            axum::routing::post(api::change_requests::execute_change_request),
+        )
+        .route("/api/connections/:id/schema", axum::routing::get(api::schema::get_schema))
+        .route("/api/connections/:id/schema/refresh", axum::routing::post(api::schema::refresh_schema))
+        .route("/api/connections/:id/schema/databases", axum::routing::get(api::schema::list_databases))
+        .route("/api/connections/:id/schema/schemas", axum::routing::get(api::schema::list_schemas))
+        .route("/api/connections/:id/schema/tables", axum::routing::get(api::schema::list_tables))
+        .route("/api/connections/:id/schema/tables/:table", axum::routing::get(api::schema::get_table))
+        .route("/api/connections/:id/redis/keys", axum::routing::get(api::redis_keys::scan_keys))
+        .route("/api/connections/:id/redis/key", axum::routing::get(api::redis_keys::get_key))
+        .route("/api/connections/:id/redis/key", axum::routing::put(api::redis_keys::put_key))
+        .route("/api/connections/:id/redis/key", axum::routing::delete(api::redis_keys::delete_key))
+        .route("/api/connections/:id/mongo/databases", axum::routing::get(api::mongo::list_databases))
+        .route(
+            "/api/connections/:id/mongo/databases/:database/collections",
+            axum::routing::get(api::mongo::list_collections),
+        )
+        .route(
+            "/api/connections/:id/mongo/databases/:database/collections/:collection/indexes",
+            axum::routing::get(api::mongo::list_indexes),
+        )
+        .route("/api/query/execute", axum::routing::post(api::execution::execute_query))
+        .route("/api/query/:execution_id/cancel", axum::routing::post(api::execution::cancel_query))
+        .route("/api/history/:id/rerun", axum::routing::post(api::history::rerun_history_entry))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            security::middleware::connection_access_middleware,
+        ));
+
+    // Account and workspace routes, only for login sessions (not personal access tokens)
+    let account_routes = Router::new()
+        .route("/api/auth/logout", axum::routing::post(api::auth::logout))
+        .route("/api/auth/verify-email/resend", axum::routing::post(api::email::resend_verification))
+        .route("/api/auth/me", axum::routing::delete(api::account::delete_account))
+        .route("/api/auth/me/password", axum::routing::post(api::account::change_password))
+        .route("/api/auth/me/email", axum::routing::post(api::email::request_email_change))
+        .route("/api/auth/me/export", axum::routing::get(api::account::export_account))
+        .route("/api/auth/me/sessions", axum::routing::get(api::auth::list_sessions))
+        .route("/api/auth/me/sessions", axum::routing::delete(api::auth::revoke_other_sessions))
+        .route("/api/auth/me/sessions/:id", axum::routing::delete(api::auth::revoke_session))
+        .route("/api/auth/2fa/enroll", axum::routing::post(api::auth::enroll_two_factor))
+        .route("/api/auth/2fa/confirm", axum::routing::post(api::auth::confirm_two_factor))
+        .route("/api/auth/2fa/recovery-codes", axum::routing::post(api::auth::regenerate_recovery_codes))
+        .route("/api/auth/2fa/disable", axum::routing::post(api::auth::disable_two_factor))
+        .route("/api/auth/tokens", axum::routing::get(api::tokens::list_tokens))
+        .route("/api/auth/tokens", axum::routing::post(api::tokens::create_token))
+        .route("/api/auth/tokens/:id", axum::routing::delete(api::tokens::delete_token))
+        .route("/api/workspaces", axum::routing::get(api::workspaces::list_workspaces))
+        .route("/api/workspaces", axum::routing::post(api::workspaces::create_workspace))
+        .route("/api/workspaces/:id", axum::routing::get(api::workspaces::get_workspace))
+        .route("/api/workspaces/:id", axum::routing::patch(api::workspaces::update_workspace))
+        .route("/api/workspaces/:id", axum::routing::delete(api::workspaces::delete_workspace))
+        .route("/api/workspaces/:id/members", axum::routing::get(api::workspaces::list_members))
+        .route("/api/workspaces/:id/members", axum::routing::post(api::workspaces::add_member))
+        .route("/api/workspaces/:id/members/:user_id", axum::routing::patch(api::workspaces::update_member))
+        .route("/api/workspaces/:id/members/:user_id", axum::routing::delete(api::workspaces::remove_member))
+        .route("/api/workspaces/:id/invitations", axum::routing::get(api::invitations::list_invitations))
+        .route("/api/workspaces/:id/invitations", axum::routing::post(api::invitations::create_invitation))
+        .route(
+            "/api/workspaces/:id/invitations/:invitation_id",
+            axum::routing::delete(api::invitations::revoke_invitation),
+        )
+        .route("/api/workspaces/:id/audit", axum::routing::get(api::workspaces::list_audit))
+        .route("/api/invitations/accept", axum::routing::post(api::invitations::accept_invitation))
+        .layer(middleware::from_fn(security::middleware::session_middleware));
+
+    // Build protected routes (require authentication)
+    let protected_routes = Router::new()
+        .route("/api/auth/me", axum::routing::get(api::auth::get_current_user))
         .route("/api/scripts", axum::routing::post(api::scripts::create_script))
         .route("/api/scripts", axum::routing::get(api::scripts::list_scripts))
+        .route("/api/scripts/:id", axum::routing::patch(api::scripts::update_script))
         .route("/api/scripts/:id", axum::routing::delete(api::scripts::delete_script))
+        .route("/api/history", axum::routing::get(api::history::list_history))
+        .route("/api/history/:id", axum::routing::get(api::history::get_history_entry))
+        .route("/api/drivers", axum::routing::get(api::drivers::list_drivers))
+        .merge(account_routes)
+        .merge(connection_routes)
+        .merge(admin_routes)
         .layer(middleware::from_fn_with_state(
-            auth_service.clone(),
+            state.clone(),
             security::auth::auth_middleware,
         ));
 
     // Build public routes
     let public_routes = Router::new()
         .route("/health", axum::routing::get(api::health::health_check))
+        .route("/api/pow/challenge", axum::routing::get(api::pow::get_challenge))
         .route("/api/auth/register", axum::routing::post(api::auth::register))
-        .route("/api/auth/login", axum::routing::post(api::auth::login));
+        .route("/api/auth/login", axum::routing::post(api::auth::login))
+        .route("/api/auth/login/2fa", axum::routing::post(api::auth::login_two_factor))
+        .route("/api/auth/refresh", axum::routing::post(api::auth::refresh))
+        .route("/api/auth/verify-email", axum::routing::post(api::email::verify_email))
+        .route("/api/auth/email/confirm", axum::routing::post(api::email::confirm_email_change))
+        .route("/api/auth/password/forgot", axum::routing::post(api::email::forgot_password))
+        .route("/api/auth/password/reset", axum::routing::post(api::email::reset_password))
+        .route("/api/invitations/preview", axum::routing::post(api::invitations::preview_invitation));
 
     // Combine routes
     let app = Router::new()
         .merge(public_routes)
         .merge(protected_routes)
+        // Outermost first: CORS wraps the rate limiter and the ban check, so
+        // browsers can read their 429 and 403 responses too
         .layer(
             ServiceBuilder::new()
+                .layer(tower_http::trace::TraceLayer::new_for_http())
                 .layer(cors)
                 .layer(create_rate_limiter())
-                .layer(tower_http::trace::TraceLayer::new_for_http()),
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    security::middleware::security_middleware,
+                )),
         )
         .with_state(state);
 
@@ -94,7 +259,7 @@ async fn main() -> Result<(), anyhow::Error> {
     tracing::info!("Server listening on {}", addr);
     
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use uuid::Uuid;
 use validator::Validate;
 
+use crate::security::pow::PowSolution;
+use crate::security::validation::{Finding, StatementAnalysis};
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct User {
     pub id: String,
@@ -10,14 +12,34 @@ pub struct User {
     pub email: String,
     #[serde(skip_serializing)]
     pub password_hash: String,
+    // "user" or "admin"
+    pub role: String,
+    pub disabled_at: Option<DateTime<Utc>>,
+    // Encrypted with EncryptionService; set during enrollment, before confirmation
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<DateTime<Utc>>,
+    // Last accepted TOTP time step, to reject replayed codes
+    #[serde(skip_serializing)]
+    pub totp_last_step: Option<i64>,
+    pub email_verified_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+pub const ROLE_ADMIN: &str = "admin";
+
+// Purposes of the single-use tokens sent by email
+pub const EMAIL_TOKEN_VERIFY: &str = "verify_email";
+pub const EMAIL_TOKEN_RESET: &str = "reset_password";
+pub const EMAIL_TOKEN_CHANGE: &str = "change_email";
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Connection {
     pub id: String,
+    // Creator of the connection
     pub user_id: String,
+    pub workspace_id: String,
     pub name: String,
     pub db_type: String,
     pub host: String,
@@ -27,6 +49,7 @@ pub struct Connection {
     pub encrypted_password: String,
     pub database_name: Option<String>,
     pub status: String,
+    pub last_checked_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -34,7 +57,9 @@ pub struct Connection {
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Script {
     pub id: String,
+    // Creator of the script
     pub user_id: String,
+    pub workspace_id: String,
     pub name: String,
     pub query: String,
     pub db_type: String,
@@ -55,6 +80,240 @@ pub struct QueryExecution {
     pub executed_at: DateTime<Utc>,
 }
 
+/// Role of a workspace member, from least to most privileged:
+/// - viewer: sees connections (never their passwords), schemas and scripts
+/// - runner: also runs queries
+/// - editor: also manages scripts
+/// - admin: also manages connections and members
+/// - owner: also manages owners and renames or deletes the workspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum WorkspaceRole {
+    Viewer,
+    Runner,
+    Editor,
+    Admin,
+    Owner,
+}
+
+impl WorkspaceRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkspaceRole::Viewer => "viewer",
+            WorkspaceRole::Runner => "runner",
+            WorkspaceRole::Editor => "editor",
+            WorkspaceRole::Admin => "admin",
+            WorkspaceRole::Owner => "owner",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    // Set for the private workspace every user gets, which can't be shared
+    #[serde(skip_serializing)]
+    pub personal_user_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Workspace with the role the requesting user has in it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WorkspaceAccess {
+    #[sqlx(flatten)]
+    pub workspace: Workspace,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ConnectionAccess {
+    #[sqlx(flatten)]
+    pub connection: Connection,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionResponse {
+    pub id: String,
+    pub workspace_id: String,
+    pub name: String,
+    pub db_type: String,
+    pub host: String,
+    pub port: i32,
+    pub username: String,
+    pub database_name: Option<String>,
+    pub status: String,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Caller's role in the connection's workspace, in listings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<WorkspaceRole>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ScriptAccess {
+    #[sqlx(flatten)]
+    pub script: Script,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct WorkspaceMember {
+    pub user_id: String,
+    pub username: String,
+    pub email: String,
+    pub role: WorkspaceRole,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceResponse {
+    pub id: String,
+    pub name: String,
+    pub personal: bool,
+    pub role: WorkspaceRole,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<WorkspaceAccess> for WorkspaceResponse {
+    fn from(access: WorkspaceAccess) -> Self {
+        Self {
+            personal: access.workspace.personal_user_id.is_some(),
+            id: access.workspace.id,
+            name: access.workspace.name,
+            role: access.role,
+            created_at: access.workspace.created_at,
+            updated_at: access.workspace.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct WorkspaceRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddMemberRequest {
+    pub username: String,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateMemberRequest {
+    pub role: WorkspaceRole,
+}
+
+/// Optional `?workspace_id=` on listings
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceFilter {
+    pub workspace_id: Option<String>,
+}
+
+// Actions recorded in the workspace audit log
+pub const AUDIT_MEMBER_ADDED: &str = "member.added";
+pub const AUDIT_MEMBER_ROLE_CHANGED: &str = "member.role_changed";
+pub const AUDIT_MEMBER_REMOVED: &str = "member.removed";
+pub const AUDIT_INVITATION_CREATED: &str = "invitation.created";
+pub const AUDIT_INVITATION_REVOKED: &str = "invitation.revoked";
+pub const AUDIT_INVITATION_ACCEPTED: &str = "invitation.accepted";
+
+/// Invitation to join a workspace with a preset role, accepted once
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct WorkspaceInvitation {
+    pub id: String,
+    pub workspace_id: String,
+    // Only this address may accept; None for invite links anyone can use
+    pub email: Option<String>,
+    pub role: WorkspaceRole,
+    pub invited_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub accepted_by: Option<String>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateInvitationRequest {
+    // Sends the link to this address; without it the link is only returned
+    #[validate(email)]
+    pub email: Option<String>,
+    pub role: WorkspaceRole,
+    pub expires_in_hours: Option<i64>,
+}
+
+/// A new invitation with its link, returned only once
+#[derive(Debug, Serialize)]
+pub struct InvitationCreated {
+    #[serde(flatten)]
+    pub invitation: WorkspaceInvitation,
+    pub token: String,
+    pub link: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvitationTokenRequest {
+    pub token: String,
+}
+
+/// What an invite link offers, shown before signing in
+#[derive(Debug, Serialize)]
+pub struct InvitationPreview {
+    pub workspace_name: String,
+    pub role: WorkspaceRole,
+    pub email: Option<String>,
+    pub invited_by: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AuditEvent {
+    pub id: String,
+    pub action: String,
+    pub actor_id: Option<String>,
+    pub actor_username: Option<String>,
+    pub target_user_id: Option<String>,
+    pub target_username: Option<String>,
+    pub role: Option<WorkspaceRole>,
+    pub invitation_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Audit entry about to be written
+#[derive(Debug, Default)]
+pub struct NewAuditEvent<'a> {
+    pub actor_id: Option<&'a str>,
+    pub target_user_id: Option<&'a str>,
+    pub role: Option<WorkspaceRole>,
+    pub invitation_id: Option<&'a str>,
+    pub ip_address: Option<&'a str>,
+}
+
+/// History row with the name of its connection, for listings
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct HistoryEntry {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub execution: QueryExecution,
+    pub connection_name: Option<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SchemaCache {
+    pub connection_id: String,
+    // Serialized drivers::Catalog
+    pub catalog: String,
+    pub refreshed_at: DateTime<Utc>,
+}
+
 // Request/Response DTOs
 #[derive(Debug, Deserialize, Validate)]
 pub struct RegisterRequest {
@@ -62,27 +321,233 @@ pub struct RegisterRequest {
     pub username: String,
     #[validate(email)]
     pub email: String,
-    #[validate(length(min = 8))]
+    // Checked against the password policy
     pub password: String,
+    pub pow: Option<PowSolution>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    // Only required after recent failed logins from the same IP
+    pub pow: Option<PowSolution>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
+    // Short-lived access token for the Authorization header
     pub token: String,
+    pub expires_in: i64,
+    pub refresh_token: String,
     pub user: UserResponse,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Session {
+    pub id: String,
+    pub user_id: String,
+    pub user_agent: Option<String>,
+    // Last IP the session was used from
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    // The session the request was made with
+    pub current: bool,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RefreshToken {
+    pub id: String,
+    pub session_id: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserResponse {
     pub id: String,
     pub username: String,
     pub email: String,
+    pub role: String,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            email_verified: user.email_verified_at.is_some(),
+            two_factor_enabled: user.totp_enabled_at.is_some(),
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            role: user.role,
+        }
+    }
+}
+
+/// Login answers with tokens, or with an MFA challenge when 2FA is enabled
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    MfaRequired(MfaChallengeResponse),
+}
+
+#[derive(Debug, Serialize)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    // Proves the password step; exchanged with a code at /api/auth/login/2fa
+    pub mfa_token: String,
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginTwoFactorRequest {
+    pub mfa_token: String,
+    // TOTP code or recovery code
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisableTwoFactorRequest {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorSetupResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PersonalAccessToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    // Comma separated
+    pub scopes: String,
+    // Comma separated; NULL means every connection of the user
+    pub connection_ids: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateAccessTokenRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1))]
+    pub scopes: Vec<String>,
+    pub connection_ids: Option<Vec<String>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccessTokenResponse {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub connection_ids: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    // Plain token, only present in the response that creates it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RecoveryCode {
+    pub id: String,
+    pub code_hash: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct EmailToken {
+    pub user_id: String,
+    pub new_email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Token from a verification or email change link
+#[derive(Debug, Deserialize)]
+pub struct EmailTokenRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangeEmailRequest {
+    #[validate(email)]
+    pub new_email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+    // TOTP or recovery code, required when 2FA is enabled
+    pub code: Option<String>,
+}
+
+/// Everything stored about a user, downloaded before deleting the account
+#[derive(Debug, Serialize)]
+pub struct AccountExport {
+    pub exported_at: DateTime<Utc>,
+    pub user: User,
+    pub workspaces: Vec<WorkspaceResponse>,
+    pub sessions: Vec<Session>,
+    pub access_tokens: Vec<AccessTokenResponse>,
+    pub connections: Vec<Connection>,
+    pub scripts: Vec<Script>,
+    pub history: Vec<QueryExecution>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -90,17 +555,61 @@ pub struct CreateConnectionRequest {
     #[validate(length(min = 1, max = 100))]
     pub name: String,
     pub db_type: String,
+    // Not used by file based connections such as SQLite
+    #[serde(default)]
     pub host: String,
+    #[serde(default)]
     pub port: i32,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub password: String,
     pub database_name: Option<String>,
+    // The user's personal workspace when missing
+    pub workspace_id: Option<String>,
+}
+
+/// Partial update, fields left out keep their current value
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateConnectionRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<i32>,
+    pub username: Option<String>,
+    // Re-encrypted before it is stored
+    pub password: Option<String>,
+    pub database_name: Option<String>,
+    // Version precondition when If-Match is not sent
+    pub updated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ExecuteQueryRequest {
     pub connection_id: String,
     pub query: String,
+    // Client generated id used to cancel the query while it runs
+    pub execution_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub connection_id: Option<String>,
+    pub success: Option<bool>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    // Free text matched against the query and the error message
+    pub q: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryPage {
+    pub items: Vec<HistoryEntry>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -109,4 +618,286 @@ pub struct QueryResponse {
     pub rows: Vec<serde_json::Value>,
     pub execution_time_ms: i64,
     pub rows_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documents: Option<Vec<serde_json::Value>>,
+    // Rows beyond the connection's max_rows were dropped
+    pub truncated: bool,
+    // What the SQL analyzer noticed, such as an UPDATE without WHERE
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<Finding>,
+}
+
+/// Kinds of statement an execution policy allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementClass {
+    Select,
+    Dml,
+    Ddl,
+    Admin,
+}
+
+impl StatementClass {
+    pub const ALL: [StatementClass; 4] = [
+        StatementClass::Select,
+        StatementClass::Dml,
+        StatementClass::Ddl,
+        StatementClass::Admin,
+    ];
+
+    pub fn all() -> Vec<StatementClass> {
+        Self::ALL.to_vec()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatementClass::Select => "select",
+            StatementClass::Dml => "dml",
+            StatementClass::Ddl => "ddl",
+            StatementClass::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|class| class.as_str() == value)
+    }
+}
+
+/// Time of day, as HH:MM at a fixed UTC offset, in which a connection accepts
+/// queries. A window whose `from` is after its `until` spans midnight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AllowedHours {
+    pub from: String,
+    pub until: String,
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+/// Limits every query on a connection goes through before it reaches the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionPolicy {
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default = "StatementClass::all")]
+    pub allowed_statements: Vec<StatementClass>,
+    pub max_rows: Option<i64>,
+    pub statement_timeout_secs: Option<i64>,
+    pub allowed_hours: Option<AllowedHours>,
+    // Statements (or Redis commands) a single request may carry
+    pub max_statements: Option<i64>,
+    // UPDATE and DELETE with no WHERE clause
+    #[serde(default = "ExecutionPolicy::allow_by_default")]
+    pub allow_unfiltered_writes: bool,
+    // Protected connections only run statements other than select through
+    // an approved change request
+    #[serde(default)]
+    pub require_approval: bool,
+    #[serde(default = "ExecutionPolicy::one_approval")]
+    pub required_approvals: i64,
+    // Least workspace role an approver needs
+    #[serde(default = "ExecutionPolicy::admin_approves")]
+    pub approver_role: WorkspaceRole,
+}
+
+impl ExecutionPolicy {
+    fn allow_by_default() -> bool {
+        true
+    }
+
+    fn one_approval() -> i64 {
+        1
+    }
+
+    fn admin_approves() -> WorkspaceRole {
+        WorkspaceRole::Admin
+    }
+}
+
+impl Default for ExecutionPolicy {
+    fn default() -> Self {
+        Self {
+            read_only: false,
+            allowed_statements: StatementClass::all(),
+            max_rows: None,
+            statement_timeout_secs: None,
+            allowed_hours: None,
+            max_statements: None,
+            allow_unfiltered_writes: true,
+            require_approval: false,
+            required_approvals: 1,
+            approver_role: WorkspaceRole::Admin,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ConnectionPolicy {
+    pub connection_id: String,
+    pub read_only: bool,
+    // Comma separated statement classes
+    pub allowed_statements: String,
+    pub max_rows: Option<i64>,
+    pub statement_timeout_secs: Option<i64>,
+    pub allowed_from: Option<String>,
+    pub allowed_until: Option<String>,
+    pub utc_offset_minutes: i64,
+    pub max_statements: Option<i64>,
+    pub allow_unfiltered_writes: bool,
+    pub require_approval: bool,
+    pub required_approvals: i64,
+    pub approver_role: WorkspaceRole,
+    pub updated_by: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<ConnectionPolicy> for ExecutionPolicy {
+    fn from(row: ConnectionPolicy) -> Self {
+        let allowed_hours = match (row.allowed_from, row.allowed_until) {
+            (Some(from), Some(until)) => Some(AllowedHours {
+                from,
+                until,
+                utc_offset_minutes: row.utc_offset_minutes as i32,
+            }),
+            _ => None,
+        };
+
+        Self {
+            read_only: row.read_only,
+            allowed_statements: row
+                .allowed_statements
+                .split(',')
+                .filter_map(StatementClass::parse)
+                .collect(),
+            max_rows: row.max_rows,
+            statement_timeout_secs: row.statement_timeout_secs,
+            allowed_hours,
+            max_statements: row.max_statements,
+            allow_unfiltered_writes: row.allow_unfiltered_writes,
+            require_approval: row.require_approval,
+            required_approvals: row.required_approvals,
+            approver_role: row.approver_role,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionPolicyResponse {
+    pub connection_id: String,
+    #[serde(flatten)]
+    pub policy: ExecutionPolicy,
+    // Both unset while the connection runs on the default policy
+    pub updated_by: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// How a query reads to the connection's policy: the class of each statement
+/// and, for SQL, what the analyzer made of it
+#[derive(Debug, Clone, Serialize)]
+pub struct QuerySummary {
+    pub classes: Vec<StatementClass>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub statements: Vec<StatementAnalysis>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<Finding>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ChangeRequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Cancelled,
+    // Claimed for its single run; stays here if the server stops midway
+    Executing,
+    Executed,
+    Failed,
+}
+
+impl ChangeRequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeRequestStatus::Pending => "pending",
+            ChangeRequestStatus::Approved => "approved",
+            ChangeRequestStatus::Rejected => "rejected",
+            ChangeRequestStatus::Cancelled => "cancelled",
+            ChangeRequestStatus::Executing => "executing",
+            ChangeRequestStatus::Executed => "executed",
+            ChangeRequestStatus::Failed => "failed",
+        }
+    }
+}
+
+/// A write on a protected connection waiting for, or done after, its approvals
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ChangeRequest {
+    pub id: String,
+    pub workspace_id: String,
+    pub connection_id: String,
+    pub requested_by: Option<String>,
+    pub query: String,
+    pub justification: String,
+    // Serialized QuerySummary
+    #[serde(skip_serializing)]
+    pub summary: String,
+    pub status: ChangeRequestStatus,
+    // Taken from the connection's policy when the request was made
+    pub required_approvals: i64,
+    pub approver_role: WorkspaceRole,
+    pub created_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub executed_by: Option<String>,
+    pub executed_at: Option<DateTime<Utc>>,
+    // Serialized QueryResponse of the run
+    #[serde(skip_serializing)]
+    pub result: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ReviewDecision {
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ChangeRequestReview {
+    pub reviewer_id: Option<String>,
+    pub reviewer_username: Option<String>,
+    pub decision: ReviewDecision,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangeRequestResponse {
+    #[serde(flatten)]
+    pub request: ChangeRequest,
+    pub summary: serde_json::Value,
+    pub result: Option<serde_json::Value>,
+    pub approvals: usize,
+    pub reviews: Vec<ChangeRequestReview>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateChangeRequest {
+    #[validate(length(min = 1))]
+    pub query: String,
+    #[validate(length(min = 1, max = 2000))]
+    pub justification: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReviewRequest {
+    #[validate(length(max = 2000))]
+    pub comment: Option<String>,
+}
+
+/// Optional `?status=` on change request listings
+#[derive(Debug, Deserialize)]
+pub struct ChangeRequestFilter {
+    pub status: Option<ChangeRequestStatus>,
 }
@@ -0,0 +1,75 @@
+use chrono::Utc;
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::AppState;
+use crate::db::repository::ConnectionRepository;
+use crate::drivers::{ConnectionTest, CONNECT_TIMEOUT};
+use crate::models::Connection;
+
+pub const STATUS_CONNECTED: &str = "connected";
+pub const STATUS_ERROR: &str = "error";
+
+// Upper bound for a whole check: connecting plus the version round-trip
+const CHECK_TIMEOUT: Duration = Duration::from_secs(CONNECT_TIMEOUT.as_secs() * 2);
+
+// Connections checked at the same time by the background monitor
+const MONITOR_CONCURRENCY: usize = 8;
+
+/// Opens a real connection, then stores the resulting status and check time
+pub async fn check_connection(state: &AppState, conn: &Connection) -> Result<ConnectionTest, anyhow::Error> {
+    let result = run_check(state, conn).await;
+    let status = if result.is_ok() { STATUS_CONNECTED } else { STATUS_ERROR };
+
+    ConnectionRepository::update_status(&state.db, &conn.id, status, Utc::now()).await?;
+    result
+}
+
+async fn run_check(state: &AppState, conn: &Connection) -> Result<ConnectionTest, anyhow::Error> {
+    let driver = state
+        .drivers
+        .get(&conn.db_type)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported database type: {}", conn.db_type))?;
+    let password = state.encryption_service.decrypt_credentials(&conn.encrypted_password)?;
+
+    tokio::time::timeout(CHECK_TIMEOUT, driver.test(conn, &password))
+        .await
+        .map_err(|_| anyhow::anyhow!("Connection check timed out"))?
+}
+
+/// Re-checks every connection periodically so listings show real health
+pub fn spawn(state: Arc<AppState>, interval_secs: u64) {
+    if interval_secs == 0 {
+        tracing::info!("Connection monitor disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let connections = match ConnectionRepository::find_all(&state.db).await {
+                Ok(connections) => connections,
+                Err(e) => {
+                    tracing::error!("Connection monitor failed to load connections: {}", e);
+                    continue;
+                }
+            };
+
+            futures::stream::iter(connections)
+                .for_each_concurrent(MONITOR_CONCURRENCY, |conn| {
+                    let state = state.clone();
+                    async move {
+                        if let Err(e) = check_connection(&state, &conn).await {
+                            tracing::debug!("Connection {} is unhealthy: {}", conn.id, e);
+                        }
+                    }
+                })
+                .await;
+        }
+    });
+}
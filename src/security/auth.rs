@@ -1,5 +1,5 @@
 use axum::{
-    extract::State,
+    extract::{ConnectInfo, State},
     http::{Request, StatusCode},
     middleware::Next,
     response::Response,
@@ -7,38 +7,71 @@ use axum::{
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::api::AppState;
+use crate::db::repository::{AccessTokenRepository, SessionRepository, UserRepository};
+use crate::db::DbPool;
+use crate::models::{PersonalAccessToken, User, ROLE_ADMIN};
+use crate::security::repository::{SecurityRepository, ENTITY_USER};
+use crate::security::tokens::{split_list, PAT_PREFIX};
+
+// How often last_used_at of a session or access token is written at most
+const SESSION_TOUCH_INTERVAL_SECS: i64 = 60;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,  // user_id
     pub username: String,
+    pub sid: String,  // session the token was issued for
+    pub jti: String,  // token id, used for revocation
     pub exp: i64,     // expiration timestamp
     pub iat: i64,     // issued at timestamp
 }
 
+// Issued after the password step of a login with 2FA, never accepted as an access token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MfaClaims {
+    sub: String,
+    mfa: bool,
+    exp: i64,
+    iat: i64,
+}
+
+const MFA_TOKEN_MINUTES: i64 = 5;
+
 pub struct AuthService {
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
-    expiration_hours: i64,
+    expiration_minutes: i64,
 }
 
 impl AuthService {
-    pub fn new(secret: &str, expiration_hours: i64) -> Self {
+    pub fn new(secret: &str, expiration_minutes: i64) -> Self {
         Self {
             encoding_key: EncodingKey::from_secret(secret.as_bytes()),
             decoding_key: DecodingKey::from_secret(secret.as_bytes()),
-            expiration_hours,
+            expiration_minutes,
         }
     }
 
-    pub fn create_token(&self, user_id: &str, username: &str) -> Result<String, anyhow::Error> {
+    /// Lifetime of access tokens in seconds
+    pub fn expires_in(&self) -> i64 {
+        self.expiration_minutes * 60
+    }
+
+    pub fn create_token(&self, user_id: &str, username: &str, session_id: &str) -> Result<String, anyhow::Error> {
         let now = Utc::now();
-        let exp = now + Duration::hours(self.expiration_hours);
+        let exp = now + Duration::minutes(self.expiration_minutes);
 
         let claims = Claims {
             sub: user_id.to_string(),
             username: username.to_string(),
+            sid: session_id.to_string(),
+            jti: Uuid::new_v4().to_string(),
             exp: exp.timestamp(),
             iat: now.timestamp(),
         };
@@ -47,6 +80,30 @@ impl AuthService {
         Ok(token)
     }
 
+    pub fn create_mfa_token(&self, user_id: &str) -> Result<String, anyhow::Error> {
+        let now = Utc::now();
+        let claims = MfaClaims {
+            sub: user_id.to_string(),
+            mfa: true,
+            exp: (now + Duration::minutes(MFA_TOKEN_MINUTES)).timestamp(),
+            iat: now.timestamp(),
+        };
+        Ok(encode(&Header::default(), &claims, &self.encoding_key)?)
+    }
+
+    pub fn mfa_token_expires_in(&self) -> i64 {
+        MFA_TOKEN_MINUTES * 60
+    }
+
+    /// Returns the user id the MFA token was issued for
+    pub fn verify_mfa_token(&self, token: &str) -> Result<String, anyhow::Error> {
+        let claims = decode::<MfaClaims>(token, &self.decoding_key, &Validation::default())?.claims;
+        if !claims.mfa {
+            return Err(anyhow::anyhow!("Not an MFA token"));
+        }
+        Ok(claims.sub)
+    }
+
     pub fn verify_token(&self, token: &str) -> Result<Claims, anyhow::Error> {
         let token_data = decode::<Claims>(
             token,
@@ -62,15 +119,83 @@ impl AuthService {
 pub struct AuthUser {
     pub user_id: String,
     pub username: String,
+    pub role: String,
+    // Session (device) the request was made from; None for personal access tokens
+    pub session_id: Option<String>,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
+    // Granted scopes; None means a login session with full access
+    pub scopes: Option<Vec<String>>,
+    // Connections a personal access token is limited to, if any
+    pub connection_ids: Option<Vec<String>>,
 }
 
 impl AuthUser {
-    pub fn from_claims(claims: Claims) -> Self {
+    pub fn from_claims(claims: Claims, user: &User) -> Self {
         Self {
             user_id: claims.sub,
             username: claims.username,
+            role: user.role.clone(),
+            session_id: Some(claims.sid),
+            email_verified: user.email_verified_at.is_some(),
+            two_factor_enabled: user.totp_enabled_at.is_some(),
+            scopes: None,
+            connection_ids: None,
+        }
+    }
+
+    pub fn from_access_token(token: &PersonalAccessToken, user: &User) -> Self {
+        Self {
+            user_id: user.id.clone(),
+            username: user.username.clone(),
+            role: user.role.clone(),
+            session_id: None,
+            email_verified: user.email_verified_at.is_some(),
+            two_factor_enabled: user.totp_enabled_at.is_some(),
+            scopes: Some(split_list(&token.scopes)),
+            connection_ids: token.connection_ids.as_deref().map(split_list),
         }
     }
+
+    // Personal access tokens never carry admin rights
+    pub fn is_admin(&self) -> bool {
+        self.role == ROLE_ADMIN && self.scopes.is_none()
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes.iter().any(|s| s == scope),
+            None => true,
+        }
+    }
+
+    pub fn require_scope(&self, scope: &str) -> Result<(), (StatusCode, String)> {
+        if !self.has_scope(scope) {
+            return Err((StatusCode::FORBIDDEN, format!("Token lacks the '{}' scope", scope)));
+        }
+        Ok(())
+    }
+
+    pub fn can_access_connection(&self, connection_id: &str) -> bool {
+        match &self.connection_ids {
+            Some(ids) => ids.iter().any(|id| id == connection_id),
+            None => true,
+        }
+    }
+
+    /// Answers like a missing connection, so restricted tokens can't probe ids
+    pub fn require_connection(&self, connection_id: &str) -> Result<(), (StatusCode, String)> {
+        if !self.can_access_connection(connection_id) {
+            return Err((StatusCode::NOT_FOUND, "Connection not found".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn require_session(&self) -> Result<&str, (StatusCode, String)> {
+        self.session_id.as_deref().ok_or_else(|| {
+            (StatusCode::FORBIDDEN, "Not available with personal access tokens".to_string())
+        })
+    }
 }
 
 // Implementación de FromRequestParts para hacer AuthUser un extractor de Axum
@@ -94,9 +219,10 @@ where
     }
 }
 
-// Middleware de autenticación
+// Middleware de autenticación: acepta JWTs de sesión y personal access tokens
 pub async fn auth_middleware(
-    State(auth_service): State<Arc<AuthService>>,
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     mut request: Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, StatusCode> {
@@ -112,14 +238,95 @@ pub async fn auth_middleware(
         _ => return Err(StatusCode::UNAUTHORIZED),
     };
 
-    match auth_service.verify_token(token) {
-        Ok(claims) => {
-            let auth_user = AuthUser::from_claims(claims);
-            request.extensions_mut().insert(auth_user);
-            Ok(next.run(request).await)
-        }
-        Err(_) => Err(StatusCode::UNAUTHORIZED),
+    let auth_user = if token.starts_with(PAT_PREFIX) {
+        authenticate_access_token(&state, token).await?
+    } else {
+        let claims = authenticate_jwt(&state, token, &addr.ip().to_string()).await?;
+        let user = load_active_user(&state.db, &claims.sub).await?;
+
+        // Logout needs the raw claims to revoke this token
+        request.extensions_mut().insert(claims.clone());
+        AuthUser::from_claims(claims, &user)
+    };
+
+    request.extensions_mut().insert(auth_user);
+    Ok(next.run(request).await)
+}
+
+async fn authenticate_jwt(state: &AppState, token: &str, ip: &str) -> Result<Claims, StatusCode> {
+    let claims = state
+        .auth_service
+        .verify_token(token)
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let revoked = SessionRepository::is_access_revoked(&state.db, &claims.jti, &claims.sid)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if revoked {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    SessionRepository::touch(
+        &state.db,
+        &claims.sid,
+        ip,
+        Duration::seconds(SESSION_TOUCH_INTERVAL_SECS),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(claims)
+}
+
+async fn authenticate_access_token(state: &AppState, token: &str) -> Result<AuthUser, StatusCode> {
+    let access_token = AccessTokenRepository::find_valid_by_hash(&state.db, &hash_token(token))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let user = load_active_user(&state.db, &access_token.user_id).await?;
+
+    AccessTokenRepository::touch(
+        &state.db,
+        &access_token.id,
+        Duration::seconds(SESSION_TOUCH_INTERVAL_SECS),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(AuthUser::from_access_token(&access_token, &user))
+}
+
+// Tokens outlive role changes, account suspensions and bans, so check the user on every request
+async fn load_active_user(pool: &DbPool, user_id: &str) -> Result<User, StatusCode> {
+    let user = UserRepository::find_by_id(pool, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    if user.disabled_at.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let banned = SecurityRepository::is_banned_by_admin(pool, ENTITY_USER, &user.username)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if banned {
+        return Err(StatusCode::FORBIDDEN);
     }
+    Ok(user)
+}
+
+// Refresh tokens y tokens enviados por email: 256 bits aleatorios;
+// en la base de datos solo se guarda su SHA-256
+pub fn generate_opaque_token() -> String {
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
 }
 
 // Helper para hashear passwords con argon2
@@ -151,3 +358,44 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool, anyhow::Error
         .verify_password(password.as_bytes(), &parsed_hash)
         .is_ok())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_pool;
+    use crate::security::repository::BANNED_BY_SYSTEM;
+
+    #[tokio::test]
+    async fn test_banned_user_tokens_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("nexusdb-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pool = create_pool(&format!("sqlite:{}?mode=rwc", dir.join("nexusdb.db").display()))
+            .await
+            .unwrap();
+        let user = UserRepository::create(&pool, "mallory", "mallory@example.com", "hash")
+            .await
+            .unwrap();
+        assert!(load_active_user(&pool, &user.id).await.is_ok());
+
+        // A lockout after failed logins only stops new logins
+        SecurityRepository::ban_entity(
+            &pool,
+            ENTITY_USER,
+            "mallory",
+            None,
+            Some(Duration::minutes(15)),
+            Some(BANNED_BY_SYSTEM),
+        )
+        .await
+        .unwrap();
+        assert!(load_active_user(&pool, &user.id).await.is_ok());
+
+        SecurityRepository::ban_entity(&pool, ENTITY_USER, "mallory", Some("Abuse"), None, Some("admin"))
+            .await
+            .unwrap();
+        assert_eq!(load_active_user(&pool, &user.id).await.unwrap_err(), StatusCode::FORBIDDEN);
+
+        pool.close().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,63 +2,136 @@ use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 use once_cell::sync::Lazy;
+use serde::Serialize;
 use crate::db::DbPool;
-use crate::security::repository::SecurityRepository;
+use crate::security::repository::{SecurityRepository, BANNED_BY_SYSTEM, ENTITY_IP, ENTITY_USER};
 
 // Configuration
 const MAX_ATTEMPTS: u32 = 5;
 const LOCKOUT_DURATION_MINUTES: i64 = 15;
 const ATTEMPT_WINDOW_SECONDS: u64 = 300; // 5 minutes to accumulate failures
 
-// In-memory store: Map<IP, (Count, FirstAttemptTime)>
-static FAILED_ATTEMPTS: Lazy<Arc<Mutex<HashMap<String, (u32, Instant)>>>> = Lazy::new(|| {
+// Map<(EntityType, Value), (Count, FirstAttemptTime)>
+type AttemptMap = HashMap<(String, String), (u32, Instant)>;
+
+// In-memory store
+static FAILED_ATTEMPTS: Lazy<Arc<Mutex<AttemptMap>>> = Lazy::new(|| {
     Arc::new(Mutex::new(HashMap::new()))
 });
 
+/// Failures counted so far for an IP or username that is not banned yet
+#[derive(Debug, Serialize)]
+pub struct FailedAttempts {
+    pub entity_type: String,
+    pub value: String,
+    pub count: u32,
+    pub max_attempts: u32,
+    pub window_remaining_secs: u64,
+}
+
 pub struct BruteForceProtection;
 
 impl BruteForceProtection {
-    pub async fn check_and_record_failure(pool: &DbPool, ip: String) -> Result<(), anyhow::Error> {
-        let mut attempts = FAILED_ATTEMPTS.lock().unwrap();
-        
-        // Clean up expired entries (naive implementation)
-        // In production, use a proper cache with TTL like Redis
-        
-        let (count, first_attempt) = attempts.entry(ip.clone())
-            .or_insert((0, Instant::now()));
-
-        // Reset if window passed
-        if first_attempt.elapsed() > Duration::from_secs(ATTEMPT_WINDOW_SECONDS) {
-            *count = 0;
-            *first_attempt = Instant::now();
+    /// Records a failed login for the client IP and for the username it tried.
+    /// Returns an error once either of them reaches MAX_ATTEMPTS and is banned.
+    pub async fn record_login_failure(pool: &DbPool, ip: &str, username: &str) -> Result<(), anyhow::Error> {
+        let ip_banned = Self::check_and_record_failure(pool, ENTITY_IP, ip).await?;
+        let user_banned = Self::check_and_record_failure(pool, ENTITY_USER, username).await?;
+
+        if ip_banned || user_banned {
+            return Err(anyhow::anyhow!("Too many failed attempts. You have been temporarily banned."));
         }
+        Ok(())
+    }
+
+    /// Counts one failure for the entity and bans it when the limit is reached.
+    /// Returns whether the entity got banned.
+    pub async fn check_and_record_failure(pool: &DbPool, entity_type: &str, value: &str) -> Result<bool, anyhow::Error> {
+        let reached_limit = {
+            let mut attempts = FAILED_ATTEMPTS.lock().unwrap();
+
+            // Drop counters whose window already passed
+            let window = Duration::from_secs(ATTEMPT_WINDOW_SECONDS);
+            attempts.retain(|_, (_, first_attempt)| first_attempt.elapsed() <= window);
+
+            let key = (entity_type.to_string(), value.to_string());
+            let (count, _) = attempts.entry(key.clone()).or_insert((0, Instant::now()));
+            *count += 1;
+
+            // Reset counter once the ban is issued
+            if *count >= MAX_ATTEMPTS {
+                attempts.remove(&key);
+                true
+            } else {
+                false
+            }
+        };
 
-        *count += 1;
+        if reached_limit {
+            tracing::warn!(
+                "Brute force detected from {} {}. Banning for {} minutes.",
+                entity_type, value, LOCKOUT_DURATION_MINUTES
+            );
 
-        if *count >= MAX_ATTEMPTS {
-            // Ban the IP
-            tracing::warn!("Brute force detected from IP: {}. Banning for {} minutes.", ip, LOCKOUT_DURATION_MINUTES);
-            
             SecurityRepository::ban_entity(
                 pool,
-                "IP",
-                &ip,
+                entity_type,
+                value,
                 Some("Brute Force Protection: Too many failed login attempts"),
-                Some(LOCKOUT_DURATION_MINUTES),
-                Some("SYSTEM"),
+                Some(chrono::Duration::minutes(LOCKOUT_DURATION_MINUTES)),
+                Some(BANNED_BY_SYSTEM),
             ).await?;
+        }
 
-            // Reset counter after ban
-            attempts.remove(&ip);
-            
-            return Err(anyhow::anyhow!("Too many failed attempts. You have been temporarily banned."));
+        Ok(reached_limit)
+    }
+
+    /// Failures counted for the entity within the current window
+    pub fn recent_failures(entity_type: &str, value: &str) -> u32 {
+        let attempts = FAILED_ATTEMPTS.lock().unwrap();
+        let window = Duration::from_secs(ATTEMPT_WINDOW_SECONDS);
+
+        match attempts.get(&(entity_type.to_string(), value.to_string())) {
+            Some((count, first_attempt)) if first_attempt.elapsed() <= window => *count,
+            _ => 0,
         }
+    }
 
-        Ok(())
+    /// Failed logins from all IPs within the window, as a measure of current abuse
+    pub fn total_recent_failures() -> u32 {
+        let attempts = FAILED_ATTEMPTS.lock().unwrap();
+        let window = Duration::from_secs(ATTEMPT_WINDOW_SECONDS);
+
+        attempts
+            .iter()
+            .filter(|((entity_type, _), (_, first_attempt))| {
+                entity_type == ENTITY_IP && first_attempt.elapsed() <= window
+            })
+            .map(|(_, (count, _))| *count)
+            .sum()
+    }
+
+    pub fn snapshot() -> Vec<FailedAttempts> {
+        let attempts = FAILED_ATTEMPTS.lock().unwrap();
+        let window = Duration::from_secs(ATTEMPT_WINDOW_SECONDS);
+
+        let mut counters: Vec<FailedAttempts> = attempts
+            .iter()
+            .filter(|(_, (_, first_attempt))| first_attempt.elapsed() <= window)
+            .map(|((entity_type, value), (count, first_attempt))| FailedAttempts {
+                entity_type: entity_type.clone(),
+                value: value.clone(),
+                count: *count,
+                max_attempts: MAX_ATTEMPTS,
+                window_remaining_secs: window.saturating_sub(first_attempt.elapsed()).as_secs(),
+            })
+            .collect();
+        counters.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        counters
     }
 
-    pub fn clear_attempts(ip: &str) {
+    pub fn clear_attempts(entity_type: &str, value: &str) {
         let mut attempts = FAILED_ATTEMPTS.lock().unwrap();
-        attempts.remove(ip);
+        attempts.remove(&(entity_type.to_string(), value.to_string()));
     }
 }
@@ -1,13 +1,13 @@
 use axum::{
-    extract::{Request, State},
+    extract::{ConnectInfo, Request, State},
     http::{StatusCode, HeaderValue},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use std::sync::Arc;
 use crate::api::AppState;
-use crate::security::repository::SecurityRepository;
-use axum_extra::extract::ConnectInfo;
+use crate::security::auth::AuthUser;
+use crate::security::repository::{SecurityRepository, ENTITY_IP};
 use std::net::SocketAddr;
 
 pub async fn security_middleware(
@@ -15,20 +15,19 @@ pub async fn security_middleware(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     req: Request,
     next: Next,
-) -> Result<Response, (StatusCode, String)> {
+) -> Response {
     // 1. Check IP Ban
     let ip = addr.ip().to_string();
-    let is_banned = SecurityRepository::is_banned(&state.db, "IP", &ip)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    if is_banned {
-        tracing::warn!("Blocked request from banned IP: {}", ip);
-        return Err((StatusCode::FORBIDDEN, "Access Denied".to_string()));
-    }
+    let mut response = match SecurityRepository::is_banned(&state.db, ENTITY_IP, &ip).await {
+        Ok(true) => {
+            tracing::warn!("Blocked request from banned IP: {}", ip);
+            (StatusCode::FORBIDDEN, "Access Denied").into_response()
+        }
+        Ok(false) => next.run(req).await,
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
 
-    // 2. Add Security Headers
-    let mut response = next.run(req).await;
+    // 2. Add Security Headers, to refusals as well
     let headers = response.headers_mut();
 
     headers.insert("X-Frame-Options", HeaderValue::from_static("DENY"));
@@ -37,5 +36,63 @@ pub async fn security_middleware(
     headers.insert("Strict-Transport-Security", HeaderValue::from_static("max-age=31536000; includeSubDomains"));
     headers.insert("Content-Security-Policy", HeaderValue::from_static("default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'"));
 
-    Ok(response)
+    response
+}
+
+/// Goes after auth_middleware; only lets admins through
+pub async fn admin_middleware(req: Request, next: Next) -> Result<Response, (StatusCode, String)> {
+    let is_admin = req
+        .extensions()
+        .get::<AuthUser>()
+        .map(AuthUser::is_admin)
+        .unwrap_or(false);
+
+    if !is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin role required".to_string()));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Goes after auth_middleware on routes that use connection credentials.
+/// Unverified email addresses (with REQUIRE_EMAIL_VERIFICATION) and accounts
+/// without 2FA (with REQUIRE_2FA) are turned away.
+pub async fn connection_access_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let (email_verified, has_2fa) = req
+        .extensions()
+        .get::<AuthUser>()
+        .map(|user| (user.email_verified, user.two_factor_enabled))
+        .unwrap_or((false, false));
+
+    if state.config.require_email_verification && !email_verified {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Verify your email address to use connections".to_string(),
+        ));
+    }
+    if state.config.require_2fa && !has_2fa {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Two-factor authentication must be enabled to use connections".to_string(),
+        ));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Goes after auth_middleware on account routes (sessions, 2FA, tokens) that
+/// need an interactive login rather than a personal access token
+pub async fn session_middleware(req: Request, next: Next) -> Result<Response, (StatusCode, String)> {
+    match req.extensions().get::<AuthUser>() {
+        Some(user) => {
+            user.require_session()?;
+        }
+        None => return Err((StatusCode::UNAUTHORIZED, "Unauthorized".to_string())),
+    }
+
+    Ok(next.run(req).await)
 }
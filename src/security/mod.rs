@@ -1,4 +1,13 @@
 pub mod auth;
+pub mod brute_force;
 pub mod encryption;
+pub mod invitations;
+pub mod middleware;
+pub mod password_policy;
+pub mod policy;
+pub mod pow;
 pub mod rate_limit;
+pub mod repository;
+pub mod tokens;
+pub mod totp;
 pub mod validation;
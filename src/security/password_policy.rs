@@ -0,0 +1,207 @@
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+// Shorter usernames or email parts would match too many unrelated passwords
+const MIN_PERSONAL_INFO_LEN: usize = 3;
+
+/// Known breached passwords, as SHA-1 hashes in the Have I Been Pwned format
+enum BreachedPasswords {
+    /// Directory of k-anonymity range files: one file per 5 hex char prefix
+    /// (`ABCDE` or `ABCDE.txt`) with `SUFFIX:COUNT` lines, as in the range API
+    Ranges(PathBuf),
+    /// Small list kept in memory: `HASH` or `HASH:COUNT` lines
+    Hashes(HashMap<String, u64>),
+}
+
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    require_lowercase: bool,
+    require_uppercase: bool,
+    require_digit: bool,
+    require_symbol: bool,
+    breached: Option<BreachedPasswords>,
+    // Times a password must appear in the breach list to be rejected
+    breached_min_count: u64,
+}
+
+impl PasswordPolicy {
+    pub fn from_config(config: &Config) -> Result<Self, anyhow::Error> {
+        let breached = match &config.breached_passwords_path {
+            Some(path) => Some(load_breached(Path::new(path))?),
+            None => None,
+        };
+
+        Ok(Self {
+            min_length: config.password_min_length,
+            max_length: config.password_max_length,
+            require_lowercase: config.password_require_lowercase,
+            require_uppercase: config.password_require_uppercase,
+            require_digit: config.password_require_digit,
+            require_symbol: config.password_require_symbol,
+            breached,
+            breached_min_count: config.breached_passwords_min_count,
+        })
+    }
+
+    /// Returns every rule the password breaks; empty when it is acceptable
+    pub async fn check(&self, password: &str, username: &str, email: &str) -> Result<Vec<String>, anyhow::Error> {
+        let mut failures = Vec::new();
+
+        let length = password.chars().count();
+        if length < self.min_length {
+            failures.push(format!("must be at least {} characters long", self.min_length));
+        }
+        if length > self.max_length {
+            failures.push(format!("must be at most {} characters long", self.max_length));
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            failures.push("must contain a lowercase letter".to_string());
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            failures.push("must contain an uppercase letter".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            failures.push("must contain a digit".to_string());
+        }
+        if self.require_symbol && !password.chars().any(|c| !c.is_alphanumeric() && !c.is_whitespace()) {
+            failures.push("must contain a symbol".to_string());
+        }
+
+        let lowered = password.to_lowercase();
+        let contains = |part: &str| {
+            part.chars().count() >= MIN_PERSONAL_INFO_LEN && lowered.contains(&part.to_lowercase())
+        };
+        if contains(username) {
+            failures.push("must not contain the username".to_string());
+        }
+        let local_part = email.split('@').next().unwrap_or_default();
+        if contains(email) || contains(local_part) {
+            failures.push("must not contain the email address".to_string());
+        }
+
+        // Skipped for passwords already rejected for their length, the list can't matter then
+        if length <= self.max_length && self.is_breached(password).await? {
+            failures.push("appears in a list of breached passwords".to_string());
+        }
+
+        Ok(failures)
+    }
+
+    async fn is_breached(&self, password: &str) -> Result<bool, anyhow::Error> {
+        let Some(breached) = &self.breached else {
+            return Ok(false);
+        };
+
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let count = match breached {
+            BreachedPasswords::Hashes(hashes) => hashes.get(&hash).copied(),
+            BreachedPasswords::Ranges(dir) => {
+                let (prefix, suffix) = hash.split_at(5);
+                match read_range(dir, prefix).await? {
+                    Some(range) => parse_hash_lines(&range).find(|(s, _)| s.eq_ignore_ascii_case(suffix)).map(|(_, c)| c),
+                    None => None,
+                }
+            }
+        };
+
+        Ok(count.map(|c| c >= self.breached_min_count).unwrap_or(false))
+    }
+}
+
+fn load_breached(path: &Path) -> Result<BreachedPasswords, anyhow::Error> {
+    if path.is_dir() {
+        return Ok(BreachedPasswords::Ranges(path.to_path_buf()));
+    }
+
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read BREACHED_PASSWORDS_PATH {}: {}", path.display(), e))?;
+    let hashes: HashMap<String, u64> = parse_hash_lines(&content)
+        .map(|(hash, count)| (hash.to_ascii_uppercase(), count))
+        .collect();
+
+    tracing::info!("Loaded {} breached password hashes from {}", hashes.len(), path.display());
+    Ok(BreachedPasswords::Hashes(hashes))
+}
+
+async fn read_range(dir: &Path, prefix: &str) -> Result<Option<String>, anyhow::Error> {
+    for name in [prefix.to_string(), format!("{}.txt", prefix)] {
+        match tokio::fs::read_to_string(dir.join(name)).await {
+            Ok(content) => return Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(None)
+}
+
+/// `HASH:COUNT` lines (hex); a missing count means 1
+fn parse_hash_lines(content: &str) -> impl Iterator<Item = (&str, u64)> {
+    content.lines().filter_map(|line| {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        match line.split_once(':') {
+            Some((hash, count)) => Some((hash, count.trim().parse().unwrap_or(1))),
+            None => Some((line, 1)),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(breached: Option<BreachedPasswords>) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 10,
+            max_length: 64,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            breached,
+            breached_min_count: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lists_every_failed_rule() {
+        let failures = policy(None).check("alice", "alice", "alice@example.com").await.unwrap();
+        assert_eq!(
+            failures,
+            vec![
+                "must be at least 10 characters long",
+                "must contain an uppercase letter",
+                "must contain a digit",
+                "must contain a symbol",
+                "must not contain the username",
+                "must not contain the email address",
+            ]
+        );
+
+        let failures = policy(None).check("Tr0ub4dor&3x", "alice", "alice@example.com").await.unwrap();
+        assert!(failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_breached_range_files() {
+        let dir = std::env::temp_dir().join(format!("nexusdb-pwned-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let hash = hex::encode_upper(Sha1::digest(b"Password123!"));
+        let (prefix, suffix) = hash.split_at(5);
+        std::fs::write(dir.join(format!("{}.txt", prefix)), format!("0000000000000000000000000000000000A:3\r\n{}:42\r\n", suffix)).unwrap();
+
+        let policy = policy(Some(BreachedPasswords::Ranges(dir.clone())));
+        let failures = policy.check("Password123!", "bob", "bob@example.com").await.unwrap();
+        assert_eq!(failures, vec!["appears in a list of breached passwords"]);
+        assert!(policy.check("Password124!", "bob", "bob@example.com").await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
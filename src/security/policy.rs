@@ -0,0 +1,289 @@
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+
+use crate::drivers::mongodb::{parse_query as parse_mongo_query, MongoCommand};
+use crate::drivers::redis::parse_command_line;
+use crate::drivers::SessionLimits;
+use crate::models::{AllowedHours, ExecutionPolicy, StatementClass, WorkspaceRole};
+use crate::security::validation::{FindingCode, QueryAnalysis};
+
+pub const MAX_STATEMENT_TIMEOUT_SECS: i64 = 3600;
+pub const MAX_REQUIRED_APPROVALS: i64 = 5;
+
+/// Checks a policy sent by a client before it is stored
+pub fn validate(policy: &ExecutionPolicy) -> Result<(), String> {
+    if policy.allowed_statements.is_empty() {
+        return Err("allowed_statements can't be empty".to_string());
+    }
+    if policy.max_rows.is_some_and(|rows| rows < 1) {
+        return Err("max_rows must be at least 1".to_string());
+    }
+    if policy.max_statements.is_some_and(|statements| statements < 1) {
+        return Err("max_statements must be at least 1".to_string());
+    }
+    if policy
+        .statement_timeout_secs
+        .is_some_and(|secs| !(1..=MAX_STATEMENT_TIMEOUT_SECS).contains(&secs))
+    {
+        return Err(format!(
+            "statement_timeout_secs must be between 1 and {}",
+            MAX_STATEMENT_TIMEOUT_SECS
+        ));
+    }
+    if !(1..=MAX_REQUIRED_APPROVALS).contains(&policy.required_approvals) {
+        return Err(format!("required_approvals must be between 1 and {}", MAX_REQUIRED_APPROVALS));
+    }
+    // Approvers have to be at least as trusted as the people editing scripts
+    if policy.approver_role < WorkspaceRole::Editor {
+        return Err("approver_role must be editor, admin or owner".to_string());
+    }
+    if let Some(hours) = &policy.allowed_hours {
+        let (from, until) = parse_hours(hours).ok_or_else(|| "allowed_hours must use HH:MM times".to_string())?;
+        if from == until {
+            return Err("allowed_hours needs different from and until times".to_string());
+        }
+        if !(-12 * 60..=14 * 60).contains(&hours.utc_offset_minutes) {
+            return Err("utc_offset_minutes must be between -720 and 840".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// What the session itself is told to enforce
+pub fn session_limits(policy: &ExecutionPolicy) -> SessionLimits {
+    SessionLimits {
+        read_only: policy.read_only,
+        statement_timeout: policy
+            .statement_timeout_secs
+            .map(|secs| std::time::Duration::from_secs(secs as u64)),
+        max_rows: policy.max_rows.map(|rows| rows as usize),
+    }
+}
+
+fn parse_hours(hours: &AllowedHours) -> Option<(NaiveTime, NaiveTime)> {
+    let from = NaiveTime::parse_from_str(&hours.from, "%H:%M").ok()?;
+    let until = NaiveTime::parse_from_str(&hours.until, "%H:%M").ok()?;
+    Some((from, until))
+}
+
+/// Refuses queries outside the connection's allowed hours
+pub fn check_hours(policy: &ExecutionPolicy, now: DateTime<Utc>) -> Result<(), String> {
+    let Some(hours) = &policy.allowed_hours else {
+        return Ok(());
+    };
+    // A window that no longer parses shuts the connection rather than opening it
+    let Some((from, until)) = parse_hours(hours) else {
+        return Err("The connection's allowed hours are misconfigured".to_string());
+    };
+
+    let local = (now + Duration::minutes(hours.utc_offset_minutes as i64)).time();
+    let inside = if from < until {
+        from <= local && local < until
+    } else {
+        local >= from || local < until
+    };
+
+    if inside {
+        Ok(())
+    } else {
+        Err(format!(
+            "This connection only accepts queries between {} and {} (UTC{})",
+            hours.from,
+            hours.until,
+            format_offset(hours.utc_offset_minutes)
+        ))
+    }
+}
+
+fn format_offset(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    format!("{}{:02}:{:02}", sign, minutes.abs() / 60, minutes.abs() % 60)
+}
+
+/// Refuses statement classes the policy doesn't allow. Read-only connections
+/// only take SELECT-class statements whatever else is listed.
+pub fn check_statements(policy: &ExecutionPolicy, classes: &[StatementClass]) -> Result<(), String> {
+    if let Some(max) = policy.max_statements {
+        if classes.len() as i64 > max {
+            return Err(format!(
+                "This connection accepts at most {} statements per query, got {}",
+                max,
+                classes.len()
+            ));
+        }
+    }
+    for class in classes {
+        if policy.read_only && *class != StatementClass::Select {
+            return Err(format!(
+                "This connection is read-only; {} statements are not allowed",
+                class.as_str().to_uppercase()
+            ));
+        }
+        if !policy.allowed_statements.contains(class) {
+            return Err(format!(
+                "{} statements are not allowed on this connection",
+                class.as_str().to_uppercase()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Acts on what the SQL analyzer found
+pub fn check_findings(policy: &ExecutionPolicy, analysis: &QueryAnalysis) -> Result<(), String> {
+    if !policy.allow_unfiltered_writes {
+        let unfiltered = analysis.findings.iter().find(|finding| {
+            matches!(
+                finding.code,
+                FindingCode::UpdateWithoutWhere | FindingCode::DeleteWithoutWhere
+            )
+        });
+        if let Some(finding) = unfiltered {
+            return Err(format!("{}; add a WHERE clause to run it on this connection", finding.message));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the query has to go through a change request on this connection
+pub fn needs_approval(policy: &ExecutionPolicy, classes: &[StatementClass]) -> bool {
+    policy.require_approval && classes.iter().any(|class| *class != StatementClass::Select)
+}
+
+/// Refuses to run writes directly on a protected connection
+pub fn check_approval(policy: &ExecutionPolicy, classes: &[StatementClass]) -> Result<(), String> {
+    if needs_approval(policy, classes) {
+        return Err(
+            "This connection is protected; statements other than SELECT need an approved change request".to_string(),
+        );
+    }
+    Ok(())
+}
+
+const REDIS_READ_COMMANDS: &[&str] = &[
+    "GET", "MGET", "GETRANGE", "SUBSTR", "STRLEN", "LCS", "EXISTS", "TYPE", "TTL", "PTTL", "EXPIRETIME",
+    "PEXPIRETIME", "KEYS", "SCAN", "RANDOMKEY", "DUMP", "OBJECT", "MEMORY", "DBSIZE", "HGET", "HMGET",
+    "HGETALL", "HKEYS", "HVALS", "HLEN", "HEXISTS", "HSTRLEN", "HSCAN", "HRANDFIELD", "LRANGE", "LLEN",
+    "LINDEX", "LPOS", "SMEMBERS", "SISMEMBER", "SMISMEMBER", "SCARD", "SSCAN", "SRANDMEMBER", "SINTER",
+    "SINTERCARD", "SUNION", "SDIFF", "ZRANGE", "ZRANGEBYSCORE", "ZRANGEBYLEX", "ZREVRANGE",
+    "ZREVRANGEBYSCORE", "ZREVRANGEBYLEX", "ZSCORE", "ZMSCORE", "ZCARD", "ZCOUNT", "ZLEXCOUNT", "ZRANK",
+    "ZREVRANK", "ZSCAN", "ZRANDMEMBER", "ZINTER", "ZINTERCARD", "ZUNION", "ZDIFF", "XRANGE", "XREVRANGE",
+    "XLEN", "XINFO", "XPENDING", "GEOPOS", "GEODIST", "GEOHASH", "GEOSEARCH", "GEORADIUS_RO",
+    "GEORADIUSBYMEMBER_RO", "PFCOUNT", "GETBIT", "BITCOUNT", "BITPOS", "BITFIELD_RO", "EVAL_RO",
+    "EVALSHA_RO", "FCALL_RO", "PING", "ECHO", "TIME", "INFO", "LASTSAVE", "MULTI", "EXEC", "DISCARD",
+    "WATCH", "UNWATCH",
+];
+
+const REDIS_ADMIN_COMMANDS: &[&str] = &[
+    "CONFIG", "FLUSHALL", "FLUSHDB", "SHUTDOWN", "DEBUG", "CLIENT", "SAVE", "BGSAVE", "BGREWRITEAOF",
+    "REPLICAOF", "SLAVEOF", "MONITOR", "ACL", "MODULE", "SCRIPT", "FUNCTION", "CLUSTER", "FAILOVER",
+    "SWAPDB", "MIGRATE", "SELECT", "SLOWLOG", "LATENCY", "RESET", "AUTH", "HELLO", "SYNC", "PSYNC",
+];
+
+/// Class of every command in a Redis script, one command per line
+pub fn classify_redis(query: &str) -> Vec<StatementClass> {
+    query
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let command = parse_command_line(line)
+                .ok()
+                .and_then(|args| args.into_iter().next())
+                .unwrap_or_default()
+                .to_uppercase();
+            if REDIS_READ_COMMANDS.contains(&command.as_str()) {
+                StatementClass::Select
+            } else if REDIS_ADMIN_COMMANDS.contains(&command.as_str()) {
+                StatementClass::Admin
+            } else {
+                StatementClass::Dml
+            }
+        })
+        .collect()
+}
+
+/// MongoDB queries only read, except aggregations writing with $out or $merge
+pub fn classify_mongo(query: &str) -> Vec<StatementClass> {
+    let writes = match parse_mongo_query(query) {
+        Ok(parsed) => match parsed.command {
+            MongoCommand::Aggregate { pipeline } => pipeline
+                .iter()
+                .any(|stage| stage.contains_key("$out") || stage.contains_key("$merge")),
+            _ => false,
+        },
+        // The driver refuses it before anything is sent
+        Err(_) => false,
+    };
+
+    if writes {
+        vec![StatementClass::Dml]
+    } else {
+        vec![StatementClass::Select]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use StatementClass::*;
+
+    #[test]
+    fn test_classify_redis_and_mongo() {
+        assert_eq!(classify_redis("GET a\n# note\nset b 1\nFLUSHALL"), vec![Select, Dml, Admin]);
+        assert_eq!(classify_mongo("db.users.find({})"), vec![Select]);
+        assert_eq!(classify_mongo(r#"db.users.aggregate([{"$out": "copy"}])"#), vec![Dml]);
+    }
+
+    #[test]
+    fn test_policy_checks() {
+        let policy = ExecutionPolicy {
+            read_only: true,
+            allowed_hours: Some(AllowedHours {
+                from: "22:00".into(),
+                until: "06:00".into(),
+                utc_offset_minutes: 60,
+            }),
+            ..Default::default()
+        };
+
+        assert!(check_statements(&policy, &[Select, Select]).is_ok());
+        assert!(check_statements(&policy, &[Select, Dml]).is_err());
+        let no_ddl = ExecutionPolicy {
+            allowed_statements: vec![Select, Dml],
+            ..Default::default()
+        };
+        assert!(check_statements(&no_ddl, &[Dml]).is_ok());
+        assert!(check_statements(&no_ddl, &[Ddl]).is_err());
+
+        let careful = ExecutionPolicy {
+            allow_unfiltered_writes: false,
+            max_statements: Some(1),
+            ..Default::default()
+        };
+        let analysis = crate::security::validation::analyze("sqlite", "DELETE FROM t").unwrap();
+        assert!(check_findings(&careful, &analysis).is_err());
+        let analysis = crate::security::validation::analyze("sqlite", "DELETE FROM t WHERE id = 1").unwrap();
+        assert!(check_findings(&careful, &analysis).is_ok());
+        assert!(check_statements(&careful, &[Select, Select]).is_err());
+
+        let protected = ExecutionPolicy {
+            require_approval: true,
+            ..Default::default()
+        };
+        assert!(check_approval(&protected, &[Select, Select]).is_ok());
+        assert!(check_approval(&protected, &[Select, Ddl]).is_err());
+        assert!(check_approval(&ExecutionPolicy::default(), &[Dml]).is_ok());
+        assert!(validate(&ExecutionPolicy {
+            approver_role: WorkspaceRole::Runner,
+            ..protected
+        })
+        .is_err());
+
+        // 21:30 UTC is 22:30 at UTC+01:00, inside a window crossing midnight
+        assert!(check_hours(&policy, Utc.with_ymd_and_hms(2024, 1, 1, 21, 30, 0).unwrap()).is_ok());
+        assert!(check_hours(&policy, Utc.with_ymd_and_hms(2024, 1, 1, 4, 59, 0).unwrap()).is_ok());
+        assert!(check_hours(&policy, Utc.with_ymd_and_hms(2024, 1, 1, 5, 0, 0).unwrap()).is_err());
+        assert!(check_hours(&policy, Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()).is_err());
+    }
+}
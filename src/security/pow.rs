@@ -2,51 +2,172 @@ use sha2::{Sha256, Digest};
 use uuid::Uuid;
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use crate::security::brute_force::BruteForceProtection;
+use crate::security::repository::ENTITY_IP;
+
+// Upper bound on stored challenges; the ones closest to expiring are evicted first
+const MAX_ACTIVE_CHALLENGES: usize = 10_000;
+const MAX_NONCE_LEN: usize = 64;
+
+// Failures across all clients within the brute force window that raise the difficulty
+const ELEVATED_FAILURES: u32 = 20;
+const HIGH_FAILURES: u32 = 100;
+
+struct ActiveChallenge {
+    difficulty: u32,
+    ip: String,
+    expires_at: Instant,
+}
 
 // In-memory store for active challenges (simple for now, could be Redis)
-// Map<ChallengeID, Difficulty>
-static ACTIVE_CHALLENGES: Lazy<Arc<Mutex<HashMap<String, u32>>>> = Lazy::new(|| {
+static ACTIVE_CHALLENGES: Lazy<Arc<Mutex<HashMap<String, ActiveChallenge>>>> = Lazy::new(|| {
     Arc::new(Mutex::new(HashMap::new()))
 });
 
+#[derive(Debug, Serialize)]
+pub struct Challenge {
+    pub challenge_id: String,
+    pub difficulty: u32,
+    pub algorithm: &'static str,
+    pub expires_in_secs: u64,
+}
+
+/// Solved challenge sent along with register and login requests
+#[derive(Debug, Clone, Deserialize)]
+pub struct PowSolution {
+    pub challenge_id: String,
+    pub nonce: String,
+}
+
 pub struct PoWService;
 
 impl PoWService {
-    // Generate a new challenge
-    pub fn generate_challenge(difficulty: u32) -> String {
+    /// Number of leading hex zeros asked from `ip`. Starts at `base` and goes up
+    /// when this IP has recent failed logins and when failures pile up globally.
+    pub fn difficulty_for(ip: &str, base: u32, max: u32) -> u32 {
+        let mut difficulty = base;
+
+        if BruteForceProtection::recent_failures(ENTITY_IP, ip) > 0 {
+            difficulty += 1;
+        }
+
+        let total = BruteForceProtection::total_recent_failures();
+        if total >= HIGH_FAILURES {
+            difficulty += 2;
+        } else if total >= ELEVATED_FAILURES {
+            difficulty += 1;
+        }
+
+        difficulty.min(max)
+    }
+
+    // Generate a new challenge, only valid for requests coming from `ip`
+    pub fn generate_challenge(ip: &str, difficulty: u32, ttl: Duration) -> Challenge {
         let challenge_id = Uuid::new_v4().to_string();
-        
+        let now = Instant::now();
+
         let mut challenges = ACTIVE_CHALLENGES.lock().unwrap();
-        challenges.insert(challenge_id.clone(), difficulty);
-        
-        // Clean up old challenges occasionally (naive implementation)
-        if challenges.len() > 1000 {
-            challenges.clear(); 
+        challenges.retain(|_, c| c.expires_at > now);
+
+        if challenges.len() >= MAX_ACTIVE_CHALLENGES {
+            let oldest = challenges
+                .iter()
+                .min_by_key(|(_, c)| c.expires_at)
+                .map(|(id, _)| id.clone());
+            if let Some(id) = oldest {
+                challenges.remove(&id);
+            }
         }
 
-        challenge_id
+        challenges.insert(
+            challenge_id.clone(),
+            ActiveChallenge {
+                difficulty,
+                ip: ip.to_string(),
+                expires_at: now + ttl,
+            },
+        );
+
+        Challenge {
+            challenge_id,
+            difficulty,
+            algorithm: "sha256",
+            expires_in_secs: ttl.as_secs(),
+        }
     }
 
     // Verify the solution
     // Client sends: challenge_id, nonce
     // We verify: hash(challenge_id + nonce) starts with '0' * difficulty
-    pub fn verify_solution(challenge_id: &str, nonce: &str) -> bool {
-        let difficulty = {
+    // Each challenge can be used once, whatever the outcome
+    pub fn verify_solution(solution: &PowSolution, ip: &str) -> bool {
+        if solution.nonce.len() > MAX_NONCE_LEN {
+            return false;
+        }
+
+        let challenge = {
             let mut challenges = ACTIVE_CHALLENGES.lock().unwrap();
-            match challenges.remove(challenge_id) {
-                Some(d) => d,
-                None => return false, // Challenge not found or expired
+            match challenges.remove(&solution.challenge_id) {
+                Some(c) => c,
+                None => return false, // Challenge not found or already used
             }
         };
 
-        let input = format!("{}{}", challenge_id, nonce);
+        if challenge.expires_at <= Instant::now() || challenge.ip != ip {
+            return false;
+        }
+
+        let input = format!("{}{}", solution.challenge_id, solution.nonce);
         let mut hasher = Sha256::new();
         hasher.update(input);
         let result = hasher.finalize();
         let hex_hash = hex::encode(result);
 
-        let prefix = "0".repeat(difficulty as usize);
+        let prefix = "0".repeat(challenge.difficulty as usize);
         hex_hash.starts_with(&prefix)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(challenge: &Challenge) -> PowSolution {
+        let prefix = "0".repeat(challenge.difficulty as usize);
+        let nonce = (0u64..)
+            .map(|n| n.to_string())
+            .find(|nonce| {
+                let hash = Sha256::digest(format!("{}{}", challenge.challenge_id, nonce));
+                hex::encode(hash).starts_with(&prefix)
+            })
+            .unwrap();
+        PowSolution {
+            challenge_id: challenge.challenge_id.clone(),
+            nonce,
+        }
+    }
+
+    #[test]
+    fn test_challenge_single_use_and_bound_to_ip() {
+        let ttl = Duration::from_secs(60);
+
+        let challenge = PoWService::generate_challenge("10.0.0.1", 2, ttl);
+        let solution = solve(&challenge);
+        assert!(PoWService::verify_solution(&solution, "10.0.0.1"));
+        assert!(!PoWService::verify_solution(&solution, "10.0.0.1"));
+
+        let challenge = PoWService::generate_challenge("10.0.0.1", 2, ttl);
+        let solution = solve(&challenge);
+        assert!(!PoWService::verify_solution(&solution, "10.0.0.2"));
+    }
+
+    #[test]
+    fn test_challenge_expires() {
+        let challenge = PoWService::generate_challenge("10.0.0.3", 1, Duration::ZERO);
+        let solution = solve(&challenge);
+        assert!(!PoWService::verify_solution(&solution, "10.0.0.3"));
+    }
+}
@@ -1,14 +1,16 @@
+use governor::middleware::NoOpMiddleware;
 use tower_governor::{
     governor::GovernorConfigBuilder, key_extractor::SmartIpKeyExtractor,
 };
 
 pub fn create_rate_limiter(
-) -> tower_governor::GovernorLayer<'static, SmartIpKeyExtractor, _> {
+) -> tower_governor::GovernorLayer<'static, SmartIpKeyExtractor, NoOpMiddleware> {
     // 100 requests per minute per IP
     let governor_conf = Box::new(
         GovernorConfigBuilder::default()
             .per_second(2)  // ~120 per minute
             .burst_size(100)
+            .key_extractor(SmartIpKeyExtractor)
             .finish()
             .unwrap(),
     );
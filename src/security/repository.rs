@@ -1,5 +1,5 @@
 use crate::db::DbPool;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -14,6 +14,11 @@ pub struct BannedEntity {
     pub created_by: Option<String>,
 }
 
+pub const ENTITY_IP: &str = "IP";
+pub const ENTITY_USER: &str = "USER";
+// created_by of the lockouts brute-force protection issues
+pub const BANNED_BY_SYSTEM: &str = "SYSTEM";
+
 pub struct SecurityRepository;
 
 impl SecurityRepository {
@@ -22,12 +27,12 @@ impl SecurityRepository {
         entity_type: &str,
         value: &str,
         reason: Option<&str>,
-        duration_hours: Option<i64>,
+        duration: Option<chrono::Duration>,
         created_by: Option<&str>,
     ) -> Result<BannedEntity, anyhow::Error> {
         let id = Uuid::new_v4().to_string();
         let now = Utc::now();
-        let expires_at = duration_hours.map(|h| (now + chrono::Duration::hours(h)).to_rfc3339());
+        let expires_at = duration.map(|d| (now + d).to_rfc3339());
 
         let banned = sqlx::query_as::<_, BannedEntity>(
             r#"
@@ -67,4 +72,72 @@ impl SecurityRepository {
 
         Ok(count.0 > 0)
     }
+
+    /// Bans in force that an admin issued, leaving out brute-force lockouts:
+    /// those only stop new logins, or anyone could end a user's sessions by
+    /// failing to log in as them
+    pub async fn is_banned_by_admin(pool: &DbPool, entity_type: &str, value: &str) -> Result<bool, anyhow::Error> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM banned_entities
+            WHERE entity_type = ? AND value = ?
+            AND (expires_at IS NULL OR expires_at > ?)
+            AND created_by IS NOT ?
+            "#,
+        )
+        .bind(entity_type)
+        .bind(value)
+        .bind(Utc::now().to_rfc3339())
+        .bind(BANNED_BY_SYSTEM)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count.0 > 0)
+    }
+
+    /// Bans that are still in force, newest first
+    pub async fn list_active(
+        pool: &DbPool,
+        entity_type: Option<&str>,
+        value: Option<&str>,
+    ) -> Result<Vec<BannedEntity>, anyhow::Error> {
+        let bans = sqlx::query_as::<_, BannedEntity>(
+            r#"
+            SELECT * FROM banned_entities
+            WHERE (expires_at IS NULL OR expires_at > ?)
+            AND (? IS NULL OR entity_type = ?)
+            AND (? IS NULL OR value = ?)
+            ORDER BY banned_at DESC
+            "#,
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(entity_type)
+        .bind(entity_type)
+        .bind(value)
+        .bind(value)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(bans)
+    }
+
+    /// Ends an active ban now. The row is kept so past bans stay on record.
+    pub async fn lift(pool: &DbPool, id: &str) -> Result<Option<BannedEntity>, anyhow::Error> {
+        let now = Utc::now().to_rfc3339();
+
+        let lifted = sqlx::query_as::<_, BannedEntity>(
+            r#"
+            UPDATE banned_entities SET expires_at = ?
+            WHERE id = ? AND (expires_at IS NULL OR expires_at > ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&now)
+        .bind(id)
+        .bind(&now)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(lifted)
+    }
 }
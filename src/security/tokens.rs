@@ -0,0 +1,40 @@
+use rand::RngCore;
+
+// Lets auth_middleware tell personal access tokens from JWTs at a glance
+pub const PAT_PREFIX: &str = "nxp_";
+
+pub const SCOPE_CONNECTIONS_READ: &str = "connections:read";
+pub const SCOPE_CONNECTIONS_WRITE: &str = "connections:write";
+pub const SCOPE_SCRIPTS_READ: &str = "scripts:read";
+pub const SCOPE_SCRIPTS_WRITE: &str = "scripts:write";
+pub const SCOPE_QUERY_EXECUTE: &str = "query:execute";
+pub const SCOPE_HISTORY_READ: &str = "history:read";
+
+pub const ALL_SCOPES: &[&str] = &[
+    SCOPE_CONNECTIONS_READ,
+    SCOPE_CONNECTIONS_WRITE,
+    SCOPE_SCRIPTS_READ,
+    SCOPE_SCRIPTS_WRITE,
+    SCOPE_QUERY_EXECUTE,
+    SCOPE_HISTORY_READ,
+];
+
+/// New token in plain text, shown to the user once. Only its SHA-256 is stored.
+pub fn generate_personal_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", PAT_PREFIX, hex::encode(bytes))
+}
+
+/// Scopes and connection ids are stored as comma separated lists
+pub fn join_list(items: &[String]) -> String {
+    items.join(",")
+}
+
+pub fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
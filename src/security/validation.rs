@@ -1,38 +1,609 @@
 use regex::Regex;
-use once_cell::sync::Lazy;
-
-static DANGEROUS_SQL_PATTERNS: Lazy<Vec<Regex>> = Lazy::new(|| {
-    vec![
-        // Comentarios SQL que podrían usarse para bypass
-        Regex::new(r"--").unwrap(),
-        Regex::new(r"/\*.*\*/").unwrap(),
-        // Múltiples statements
-        Regex::new(r";\s*(drop|delete|update|insert|create|alter)\s+", ).unwrap(),
-        // UNION attacks básicos
-        Regex::new(r"\bunion\b.*\bselect\b").unwrap(),
-    ]
-});
-
-pub fn validate_query(query: &str) -> Result<(), anyhow::Error> {
-    let query_lower = query.to_lowercase();
-
-    // Verificar patrones peligrosos
-    for pattern in DANGEROUS_SQL_PATTERNS.iter() {
-        if pattern.is_match(&query_lower) {
-            return Err(anyhow::anyhow!(
-                "Query contains potentially dangerous SQL patterns"
-            ));
+use serde::Serialize;
+use sqlparser::ast::{ObjectType, Query, SetExpr, Statement, TransactionAccessMode, TransactionMode};
+use sqlparser::dialect::{Dialect as ParserDialect, MySqlDialect, PostgreSqlDialect, SQLiteDialect};
+use sqlparser::parser::Parser;
+
+use crate::models::StatementClass;
+
+pub const MAX_QUERY_LENGTH: usize = 100_000;
+
+/// What a statement does, as read by the analyzer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementKind {
+    Read,
+    Dml,
+    Ddl,
+    Dcl,
+    Transaction,
+    // Session settings, maintenance and anything not recognised
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingCode {
+    UpdateWithoutWhere,
+    DeleteWithoutWhere,
+    MultipleStatements,
+    Unparsed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub code: FindingCode,
+    pub severity: Severity,
+    // Position of the statement in the batch, from 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatementAnalysis {
+    pub kind: StatementKind,
+    // Class checked against the connection's execution policy
+    pub class: StatementClass,
+    pub keyword: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryAnalysis {
+    // False when the parser couldn't read the batch and statements were
+    // classified by their leading keyword
+    pub parsed: bool,
+    pub statements: Vec<StatementAnalysis>,
+    pub findings: Vec<Finding>,
+}
+
+impl QueryAnalysis {
+    pub fn classes(&self) -> Vec<StatementClass> {
+        self.statements.iter().map(|statement| statement.class).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dialect {
+    Postgres,
+    MySql,
+    Sqlite,
+}
+
+impl Dialect {
+    fn for_db_type(db_type: &str) -> Self {
+        match db_type {
+            "postgres" => Dialect::Postgres,
+            "mysql" | "mariadb" => Dialect::MySql,
+            _ => Dialect::Sqlite,
+        }
+    }
+
+    fn parser(&self) -> Box<dyn ParserDialect> {
+        match self {
+            Dialect::Postgres => Box::new(PostgreSqlDialect {}),
+            Dialect::MySql => Box::new(MySqlDialect {}),
+            Dialect::Sqlite => Box::new(SQLiteDialect {}),
         }
     }
 
-    // Límite de longitud
-    if query.len() > 100_000 {
+    fn name(&self) -> &'static str {
+        match self {
+            Dialect::Postgres => "PostgreSQL",
+            Dialect::MySql => "MySQL",
+            Dialect::Sqlite => "SQLite",
+        }
+    }
+}
+
+/// Parses a SQL batch with the engine's dialect, classifies each statement and
+/// reports what deserves attention. Comments and set operations are fine;
+/// blocking is left to the connection's execution policy.
+pub fn analyze(db_type: &str, query: &str) -> Result<QueryAnalysis, anyhow::Error> {
+    if query.len() > MAX_QUERY_LENGTH {
         return Err(anyhow::anyhow!("Query too long (max 100KB)"));
     }
 
-    Ok(())
+    let dialect = Dialect::for_db_type(db_type);
+    let (lexical, executable) = lex_statements(dialect, query);
+    let mut findings = Vec::new();
+
+    // The parser's split has to match the engine's quoting and comment rules,
+    // which the lexer follows; otherwise a statement the server would run
+    // could be missing from the AST, and the keywords are the safer reading.
+    // The parser also drops MySQL executable comments, which the server runs.
+    let parse = if executable.is_empty() {
+        Parser::parse_sql(dialect.parser().as_ref(), query).ok()
+    } else {
+        None
+    };
+    let (parsed, statements) = match parse {
+        Some(ast) if ast.len() == lexical.len() => {
+            let statements = ast
+                .iter()
+                .enumerate()
+                .map(|(i, statement)| {
+                    if let Some(code) = unfiltered_write(statement) {
+                        findings.push(unfiltered_finding(code, i));
+                    }
+                    analyze_statement(statement)
+                })
+                .collect();
+            (true, statements)
+        }
+        _ => {
+            findings.push(Finding {
+                code: FindingCode::Unparsed,
+                severity: Severity::Info,
+                statement: None,
+                message: if executable.is_empty() {
+                    format!(
+                        "The query couldn't be parsed as {} SQL; statements were classified by their leading keyword",
+                        dialect.name()
+                    )
+                } else {
+                    "The query has MySQL executable comments (/*! ... */); statements were classified by their keywords"
+                        .to_string()
+                },
+            });
+            let statements = lexical
+                .iter()
+                .enumerate()
+                .map(|(i, words)| {
+                    let unfiltered = match words[0].as_str() {
+                        "UPDATE" => Some(FindingCode::UpdateWithoutWhere),
+                        "DELETE" => Some(FindingCode::DeleteWithoutWhere),
+                        _ => None,
+                    };
+                    if let Some(code) = unfiltered.filter(|_| !words.iter().any(|w| w == "WHERE")) {
+                        findings.push(unfiltered_finding(code, i));
+                    }
+                    let analysis = classify_words(words);
+                    // Code in an executable comment could turn any statement into a write
+                    match analysis.kind {
+                        StatementKind::Read | StatementKind::Transaction if executable.contains(&i) => StatementAnalysis {
+                            kind: StatementKind::Dml,
+                            class: StatementClass::Dml,
+                            ..analysis
+                        },
+                        _ => analysis,
+                    }
+                })
+                .collect();
+            (false, statements)
+        }
+    };
+
+    let statements: Vec<StatementAnalysis> = statements;
+    if statements.len() > 1 {
+        findings.insert(
+            0,
+            Finding {
+                code: FindingCode::MultipleStatements,
+                severity: Severity::Info,
+                statement: None,
+                message: format!("The query contains {} statements", statements.len()),
+            },
+        );
+    }
+
+    Ok(QueryAnalysis {
+        parsed,
+        statements,
+        findings,
+    })
+}
+
+fn unfiltered_finding(code: FindingCode, statement: usize) -> Finding {
+    let message = match code {
+        FindingCode::UpdateWithoutWhere => "UPDATE without a WHERE clause changes every row of the table",
+        _ => "DELETE without a WHERE clause removes every row of the table",
+    };
+    Finding {
+        code,
+        severity: Severity::Warning,
+        statement: Some(statement),
+        message: message.to_string(),
+    }
+}
+
+/// ATTACH, DETACH or VACUUM INTO in a SQLite batch, which the driver refuses
+/// whatever the policy: they open files other than the connection's database
+pub fn sqlite_file_access(query: &str) -> Option<&'static str> {
+    sql_statements(Dialect::Sqlite, query)
+        .iter()
+        .find_map(|words| match words[0].as_str() {
+            "ATTACH" => Some("ATTACH"),
+            "DETACH" => Some("DETACH"),
+            "VACUUM" if words.iter().any(|word| word == "INTO") => Some("VACUUM INTO"),
+            _ => None,
+        })
+}
+
+/// Policy class of a statement kind. Transactions only count as reads while
+/// they don't ask for write access.
+fn policy_class(kind: StatementKind, read_write: bool) -> StatementClass {
+    match kind {
+        StatementKind::Read => StatementClass::Select,
+        StatementKind::Dml => StatementClass::Dml,
+        StatementKind::Ddl => StatementClass::Ddl,
+        StatementKind::Transaction if !read_write => StatementClass::Select,
+        StatementKind::Transaction | StatementKind::Dcl | StatementKind::Other => StatementClass::Admin,
+    }
+}
+
+fn analyze_statement(statement: &Statement) -> StatementAnalysis {
+    let kind = statement_kind(statement);
+    let read_write = match statement {
+        Statement::StartTransaction { modes, .. } | Statement::SetTransaction { modes, .. } => {
+            modes.contains(&TransactionMode::AccessMode(TransactionAccessMode::ReadWrite))
+        }
+        _ => false,
+    };
+    let keyword = statement
+        .to_string()
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+
+    StatementAnalysis {
+        kind,
+        class: policy_class(kind, read_write),
+        keyword,
+    }
+}
+
+fn statement_kind(statement: &Statement) -> StatementKind {
+    match statement {
+        Statement::Query(query) => {
+            if query_writes(query) {
+                StatementKind::Dml
+            } else {
+                StatementKind::Read
+            }
+        }
+        // Plain EXPLAIN only plans; EXPLAIN ANALYZE runs the statement
+        Statement::Explain { analyze, statement, .. } => {
+            if *analyze {
+                statement_kind(statement)
+            } else {
+                StatementKind::Read
+            }
+        }
+        Statement::ExplainTable { .. }
+        | Statement::ShowFunctions { .. }
+        | Statement::ShowVariable { .. }
+        | Statement::ShowStatus { .. }
+        | Statement::ShowVariables { .. }
+        | Statement::ShowCreate { .. }
+        | Statement::ShowColumns { .. }
+        | Statement::ShowDatabases { .. }
+        | Statement::ShowSchemas { .. }
+        | Statement::ShowTables { .. }
+        | Statement::ShowViews { .. }
+        | Statement::ShowCollation { .. }
+        | Statement::Pragma { value: None, .. } => StatementKind::Read,
+        Statement::Insert { .. }
+        | Statement::Update { .. }
+        | Statement::Delete { .. }
+        | Statement::Merge { .. }
+        | Statement::Copy { .. }
+        | Statement::CopyIntoSnowflake { .. }
+        | Statement::LoadData { .. }
+        | Statement::Directory { .. }
+        | Statement::Call { .. }
+        | Statement::Execute { .. } => StatementKind::Dml,
+        Statement::Grant { .. }
+        | Statement::Revoke { .. }
+        | Statement::CreateRole { .. }
+        | Statement::AlterRole { .. }
+        | Statement::SetRole { .. }
+        | Statement::CreatePolicy { .. }
+        | Statement::AlterPolicy { .. }
+        | Statement::DropPolicy { .. }
+        | Statement::CreateSecret { .. }
+        | Statement::DropSecret { .. }
+        | Statement::Drop {
+            object_type: ObjectType::Role,
+            ..
+        } => StatementKind::Dcl,
+        Statement::CreateView { .. }
+        | Statement::CreateTable { .. }
+        | Statement::CreateVirtualTable { .. }
+        | Statement::CreateIndex { .. }
+        | Statement::CreateSchema { .. }
+        | Statement::CreateDatabase { .. }
+        | Statement::CreateFunction { .. }
+        | Statement::CreateTrigger { .. }
+        | Statement::CreateProcedure { .. }
+        | Statement::CreateMacro { .. }
+        | Statement::CreateStage { .. }
+        | Statement::CreateSequence { .. }
+        | Statement::CreateType { .. }
+        | Statement::CreateExtension { .. }
+        | Statement::AlterTable { .. }
+        | Statement::AlterIndex { .. }
+        | Statement::AlterView { .. }
+        | Statement::Drop { .. }
+        | Statement::DropFunction { .. }
+        | Statement::DropProcedure { .. }
+        | Statement::DropTrigger { .. }
+        | Statement::Truncate { .. }
+        | Statement::Comment { .. } => StatementKind::Ddl,
+        Statement::StartTransaction { .. }
+        | Statement::SetTransaction { .. }
+        | Statement::Commit { .. }
+        | Statement::Rollback { .. }
+        | Statement::Savepoint { .. }
+        | Statement::ReleaseSavepoint { .. } => StatementKind::Transaction,
+        _ => StatementKind::Other,
+    }
+}
+
+/// SELECT ... INTO and data-modifying CTEs write from inside a query
+fn query_writes(query: &Query) -> bool {
+    let ctes_write = query
+        .with
+        .as_ref()
+        .is_some_and(|with| with.cte_tables.iter().any(|cte| query_writes(&cte.query)));
+    ctes_write || set_expr_writes(&query.body)
+}
+
+fn set_expr_writes(body: &SetExpr) -> bool {
+    match body {
+        SetExpr::Select(select) => select.into.is_some(),
+        SetExpr::Query(query) => query_writes(query),
+        SetExpr::SetOperation { left, right, .. } => set_expr_writes(left) || set_expr_writes(right),
+        SetExpr::Insert(_) | SetExpr::Update(_) => true,
+        SetExpr::Values(_) | SetExpr::Table(_) => false,
+    }
 }
 
+/// UPDATE or DELETE touching every row, directly, under EXPLAIN ANALYZE or in a CTE
+fn unfiltered_write(statement: &Statement) -> Option<FindingCode> {
+    match statement {
+        Statement::Update { selection: None, .. } => Some(FindingCode::UpdateWithoutWhere),
+        Statement::Delete(delete) if delete.selection.is_none() => Some(FindingCode::DeleteWithoutWhere),
+        Statement::Explain {
+            analyze: true,
+            statement,
+            ..
+        } => unfiltered_write(statement),
+        Statement::Query(query) => query_unfiltered_write(query),
+        _ => None,
+    }
+}
+
+fn query_unfiltered_write(query: &Query) -> Option<FindingCode> {
+    let ctes = query.with.iter().flat_map(|with| with.cte_tables.iter());
+    ctes.map(|cte| query_unfiltered_write(&cte.query))
+        .chain(std::iter::once(match query.body.as_ref() {
+            SetExpr::Update(statement) => unfiltered_write(statement),
+            SetExpr::Query(query) => query_unfiltered_write(query),
+            _ => None,
+        }))
+        .flatten()
+        .next()
+}
+
+fn sql_statements(dialect: Dialect, query: &str) -> Vec<Vec<String>> {
+    lex_statements(dialect, query).0
+}
+
+/// Splits a batch on top-level semicolons and returns the upper-cased words
+/// of each statement, skipping literals, quoted identifiers and comments,
+/// along with the statements that have words inside MySQL executable comments.
+/// Quoting follows the engine's rules: reading a literal differently from
+/// the server would let a statement hide inside it.
+fn lex_statements(dialect: Dialect, query: &str) -> (Vec<Vec<String>>, Vec<usize>) {
+    let chars: Vec<char> = query.chars().collect();
+    let at = |i: usize| chars.get(i).copied();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+
+    let mut statements = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut executable = Vec::new();
+    let mut in_executable = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let previous_is_word = i > 0 && is_word_char(chars[i - 1]);
+
+        match c {
+            ';' => {
+                if !words.is_empty() {
+                    statements.push(std::mem::take(&mut words));
+                }
+                i += 1;
+            }
+            // MySQL only treats "--" as a comment when whitespace follows
+            '-' if at(i + 1) == Some('-')
+                && (dialect != Dialect::MySql || at(i + 2).is_none_or(char::is_whitespace)) =>
+            {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '#' if dialect == Dialect::MySql => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            // MySQL runs the body of /*! ... */ comments, so read it as code
+            '/' if dialect == Dialect::MySql && at(i + 1) == Some('*') && at(i + 2) == Some('!') => {
+                i += 3;
+                while at(i).is_some_and(|c| c.is_ascii_digit()) {
+                    i += 1;
+                }
+                in_executable = true;
+            }
+            '*' if in_executable && at(i + 1) == Some('/') => {
+                in_executable = false;
+                i += 2;
+            }
+            '/' if at(i + 1) == Some('*') => {
+                // PostgreSQL block comments nest
+                let mut depth = 0;
+                while i < chars.len() {
+                    if chars[i] == '/' && at(i + 1) == Some('*') {
+                        depth += 1;
+                        i += 2;
+                    } else if chars[i] == '*' && at(i + 1) == Some('/') {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 || dialect != Dialect::Postgres {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            '\'' | '"' | '`' => {
+                let escapes = match dialect {
+                    Dialect::MySql => c != '`',
+                    // E'...' strings take backslash escapes
+                    Dialect::Postgres => {
+                        c == '\''
+                            && i > 0
+                            && matches!(chars[i - 1], 'e' | 'E')
+                            && !(i > 1 && is_word_char(chars[i - 2]))
+                    }
+                    Dialect::Sqlite => false,
+                };
+                i = skip_quoted(&chars, i, c, escapes);
+            }
+            '[' if dialect == Dialect::Sqlite => {
+                while i < chars.len() && chars[i] != ']' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '$' if dialect == Dialect::Postgres && !previous_is_word => {
+                match dollar_tag(&chars, i) {
+                    Some(tag) => i = skip_dollar_quoted(&chars, i, &tag),
+                    None => i += 1,
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while at(i).is_some_and(is_word_char) {
+                    i += 1;
+                }
+                words.push(chars[start..i].iter().collect::<String>().to_uppercase());
+                if in_executable && executable.last() != Some(&statements.len()) {
+                    executable.push(statements.len());
+                }
+            }
+            c if c.is_ascii_digit() => {
+                while at(i).is_some_and(is_word_char) {
+                    i += 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    if !words.is_empty() {
+        statements.push(words);
+    }
+    (statements, executable)
+}
+
+/// Index just past a quoted literal or identifier starting at `start`.
+/// Doubled quotes are part of the value, and so are backslash escapes when
+/// the engine reads them.
+fn skip_quoted(chars: &[char], start: usize, quote: char, escapes: bool) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if escapes && chars[i] == '\\' {
+            i += 2;
+        } else if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+            } else {
+                return i + 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    i
+}
+
+/// The `$tag$` opening a PostgreSQL dollar-quoted string at `start`, if any
+fn dollar_tag(chars: &[char], start: usize) -> Option<String> {
+    let mut i = start + 1;
+    while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+        i += 1;
+    }
+    if chars.get(i) != Some(&'$') || chars.get(start + 1).is_some_and(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(chars[start..=i].iter().collect())
+}
+
+fn skip_dollar_quoted(chars: &[char], start: usize, tag: &str) -> usize {
+    let tag: Vec<char> = tag.chars().collect();
+    let mut i = start + tag.len();
+    while i < chars.len() {
+        if chars[i..].starts_with(&tag) {
+            return i + tag.len();
+        }
+        i += 1;
+    }
+    i
+}
+
+
+// Keywords that make a query-like statement write when found anywhere in it
+const WRITE_KEYWORDS: &[&str] = &["INSERT", "UPDATE", "DELETE", "MERGE", "INTO"];
+
+/// Classifies a statement the parser couldn't read by its keywords, leaning
+/// towards the stricter class when unsure
+fn classify_words(words: &[String]) -> StatementAnalysis {
+    let has = |keyword: &str| words.iter().any(|w| w == keyword);
+    let writes = || WRITE_KEYWORDS.iter().any(|keyword| has(keyword));
+
+    let kind = match words[0].as_str() {
+        "EXPLAIN" if has("CREATE") || has("ALTER") || has("DROP") => StatementKind::Ddl,
+        "SELECT" | "WITH" | "EXPLAIN" | "VALUES" | "TABLE" => {
+            if writes() {
+                StatementKind::Dml
+            } else {
+                StatementKind::Read
+            }
+        }
+        "SHOW" | "DESCRIBE" | "DESC" => StatementKind::Read,
+        "BEGIN" | "START" | "COMMIT" | "ROLLBACK" | "SAVEPOINT" | "RELEASE" | "END" => StatementKind::Transaction,
+        "INSERT" | "UPDATE" | "DELETE" | "MERGE" | "REPLACE" | "UPSERT" | "COPY" | "LOAD" | "CALL" | "DO"
+        | "EXEC" | "EXECUTE" | "HANDLER" => StatementKind::Dml,
+        "GRANT" | "REVOKE" => StatementKind::Dcl,
+        "CREATE" | "ALTER" | "DROP" if matches!(words.get(1).map(String::as_str), Some("ROLE" | "USER" | "POLICY")) => {
+            StatementKind::Dcl
+        }
+        "CREATE" | "ALTER" | "DROP" | "TRUNCATE" | "RENAME" | "COMMENT" | "REFRESH" => StatementKind::Ddl,
+        // SET, VACUUM, PRAGMA, ATTACH, KILL, ... and anything unknown
+        _ => StatementKind::Other,
+    };
+
+    StatementAnalysis {
+        kind,
+        class: policy_class(kind, kind == StatementKind::Transaction && has("WRITE")),
+        keyword: words[0].clone(),
+    }
+}
+
+#[allow(dead_code)]
 pub fn validate_identifier(identifier: &str) -> Result<(), anyhow::Error> {
     // Solo permitir alfanuméricos, guiones bajos y puntos
     let valid_pattern = Regex::new(r"^[a-zA-Z0-9_\.]+$").unwrap();
@@ -48,6 +619,7 @@ pub fn validate_identifier(identifier: &str) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+#[allow(dead_code)]
 pub fn sanitize_table_name(name: &str) -> Result<String, anyhow::Error> {
     validate_identifier(name)?;
     Ok(name.to_string())
@@ -56,16 +628,112 @@ pub fn sanitize_table_name(name: &str) -> Result<String, anyhow::Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use StatementClass::*;
+
+    fn classes(db_type: &str, query: &str) -> Vec<StatementClass> {
+        analyze(db_type, query).unwrap().classes()
+    }
+
+    fn has_finding(analysis: &QueryAnalysis, code: FindingCode) -> bool {
+        analysis.findings.iter().any(|finding| finding.code == code)
+    }
+
+    #[test]
+    fn test_comments_and_unions_are_fine() {
+        let analysis = analyze(
+            "postgres",
+            "-- monthly revenue\nSELECT month, sum(total) FROM orders /* paid only */ WHERE paid GROUP BY month\nUNION ALL SELECT 'total', sum(total) FROM orders WHERE paid",
+        )
+        .unwrap();
+        assert!(analysis.parsed);
+        assert_eq!(analysis.classes(), vec![Select]);
+        assert!(analysis.findings.is_empty());
+
+        assert!(analyze("sqlite", &"x".repeat(MAX_QUERY_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_classify_statements() {
+        let analysis = analyze(
+            "postgres",
+            "SELECT 1; UPDATE t SET x = 1 WHERE id = 2; CREATE TABLE u (x int); GRANT SELECT ON t TO bob; BEGIN READ WRITE; COMMIT; SET search_path = x",
+        )
+        .unwrap();
+        let kinds: Vec<StatementKind> = analysis.statements.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                StatementKind::Read,
+                StatementKind::Dml,
+                StatementKind::Ddl,
+                StatementKind::Dcl,
+                StatementKind::Transaction,
+                StatementKind::Transaction,
+                StatementKind::Other,
+            ]
+        );
+        assert_eq!(analysis.classes(), vec![Select, Dml, Ddl, Admin, Admin, Select, Admin]);
+        assert!(has_finding(&analysis, FindingCode::MultipleStatements));
+
+        assert_eq!(classes("postgres", "SELECT * INTO copy FROM t"), vec![Dml]);
+        assert_eq!(classes("postgres", "EXPLAIN DELETE FROM t"), vec![Select]);
+        assert_eq!(classes("postgres", "EXPLAIN ANALYZE DELETE FROM t WHERE x = 1"), vec![Dml]);
+        assert_eq!(classes("mysql", "SELECT REPLACE(name, 'a', 'b') FROM t"), vec![Select]);
+        assert_eq!(classes("postgres", "WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"), vec![Dml]);
+    }
+
+    #[test]
+    fn test_writes_without_where() {
+        let analysis = analyze("mysql", "UPDATE t SET x = 1; DELETE FROM t WHERE x = 2; DELETE FROM u").unwrap();
+        let flagged: Vec<(FindingCode, Option<usize>)> = analysis
+            .findings
+            .iter()
+            .filter(|f| f.severity == Severity::Warning)
+            .map(|f| (f.code, f.statement))
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![(FindingCode::UpdateWithoutWhere, Some(0)), (FindingCode::DeleteWithoutWhere, Some(2))]
+        );
+
+        // Also when the parser gives up
+        let analysis = analyze("postgres", "DELETE FROM t RETURNING * FOO BAR").unwrap();
+        assert!(!analysis.parsed);
+        assert!(has_finding(&analysis, FindingCode::DeleteWithoutWhere));
+    }
+
+    #[test]
+    fn test_statements_cannot_hide_in_literals() {
+        // Each engine reads these quotes and comments its own way; the DROP runs on all of them
+        assert_eq!(classes("mysql", r"SELECT 'a\''; DROP TABLE t; -- '"), vec![Select, Ddl]);
+        assert_eq!(classes("postgres", r"SELECT 'C:\'; DROP TABLE t; SELECT '"), vec![Select, Ddl, Select]);
+        assert_eq!(classes("postgres", r"SELECT E'a\''; DROP TABLE t; -- '"), vec![Select, Ddl]);
+        assert_eq!(classes("postgres", "SELECT $$'$$; DROP TABLE t; -- '"), vec![Select, Ddl]);
+        assert_eq!(classes("postgres", "/* /* */ ' */ DROP TABLE t; -- '"), vec![Ddl]);
+        assert_eq!(classes("sqlite", "SELECT [a'b]; DROP TABLE t; --'"), vec![Select, Ddl]);
+        assert_eq!(classes("mysql", "SELECT 1 # '\n; DROP TABLE t; -- '"), vec![Select, Ddl]);
+        assert_eq!(classes("mysql", "SELECT 1--1; DROP TABLE t"), vec![Select, Ddl]);
+        assert_eq!(classes("mysql", "SELECT 1 /*!50000 ; DROP TABLE t */"), vec![Select, Ddl]);
+    }
 
     #[test]
-    fn test_validate_safe_query() {
-        assert!(validate_query("SELECT * FROM users WHERE id = 1").is_ok());
+    fn test_mysql_executable_comments() {
+        // sqlparser skips the comment, MySQL runs it
+        let analysis = analyze("mysql", "SELECT * FROM t /*!50000 INTO OUTFILE '/tmp/x' */").unwrap();
+        assert!(!analysis.parsed);
+        assert_eq!(analysis.classes(), vec![Dml]);
+        assert_eq!(classes("mysql", "SELECT 1 /*! , SLEEP(1) */"), vec![Dml]);
+        assert_eq!(classes("mysql", "SELECT 1 /*!50000 ; DROP TABLE t */; SELECT 2"), vec![Select, Ddl, Select]);
+        // Only MySQL reads them as code
+        assert_eq!(classes("postgres", "SELECT 1 /*! , SLEEP(1) */"), vec![Select]);
     }
 
     #[test]
-    fn test_validate_dangerous_query() {
-        assert!(validate_query("SELECT * FROM users; DROP TABLE users;").is_err());
-        assert!(validate_query("SELECT * FROM users UNION SELECT password FROM admin").is_err());
+    fn test_sqlite_file_access() {
+        assert_eq!(sqlite_file_access("SELECT 1; attach '/data/nexusdb.db' AS m"), Some("ATTACH"));
+        assert_eq!(sqlite_file_access("/* x */ DETACH m"), Some("DETACH"));
+        assert_eq!(sqlite_file_access("VACUUM main INTO '/tmp/copy.db'"), Some("VACUUM INTO"));
+        assert_eq!(sqlite_file_access("VACUUM; SELECT 'ATTACH'"), None);
     }
 
     #[test]